@@ -0,0 +1,141 @@
+use std::fmt::Write;
+
+/// Byte range of a node in the source text.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Returns the span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+impl From<pest::Span<'_>> for Span {
+    fn from(span: pest::Span<'_>) -> Self {
+        Span::new(span.start(), span.end())
+    }
+}
+
+/// Returns the 1-based line and column of the byte offset `pos` in `source`.
+pub fn line_col(source: &str, pos: usize) -> (usize, usize) {
+    let pos = pos.min(source.len());
+    let before = &source[..pos];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let col = before[line_start..].chars().count() + 1;
+    (line, col)
+}
+
+/// Errors that can point to a location in the source text.
+pub trait Diagnostic {
+    fn message(&self) -> &str;
+
+    fn span(&self) -> Option<Span>;
+
+    fn set_span(&mut self, span: Span);
+
+    /// Attach `span` to the error unless it already points to a more precise location.
+    fn or_span(mut self, span: Span) -> Self
+    where
+        Self: Sized,
+    {
+        if self.span().is_none() {
+            self.set_span(span);
+        }
+        self
+    }
+
+    /// Format the error with its file, line and column, followed by the source line with the
+    /// span underlined.
+    fn render(&self, file: &str, source: &str) -> String {
+        self.render_as("error", file, source)
+    }
+
+    /// Like `render`, but labeled with `severity` instead of `error`.
+    fn render_as(&self, severity: &str, file: &str, source: &str) -> String {
+        let mut out = format!("{}: {}\n", severity, self.message());
+        let span = match self.span() {
+            Some(span) if span.start <= source.len() => span,
+            _ => {
+                let _ = writeln!(out, " --> {}", file);
+                return out;
+            }
+        };
+
+        let (line, col) = line_col(source, span.start);
+        let line_start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[span.start..]
+            .find('\n')
+            .map_or(source.len(), |i| span.start + i);
+        let text = &source[line_start..line_end];
+        let width = source[span.start..span.end.clamp(span.start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+        let gutter = " ".repeat(line.to_string().len());
+
+        let _ = writeln!(out, "{}--> {}:{}:{}", gutter, file, line, col);
+        let _ = writeln!(out, "{} |", gutter);
+        let _ = writeln!(out, "{} | {}", line, text);
+        let _ = writeln!(
+            out,
+            "{} | {}{}",
+            gutter,
+            " ".repeat(col - 1),
+            "^".repeat(width)
+        );
+        out
+    }
+}
+
+#[test]
+fn test_render() {
+    struct Error(Option<Span>);
+
+    impl Diagnostic for Error {
+        fn message(&self) -> &str {
+            "something is wrong"
+        }
+
+        fn span(&self) -> Option<Span> {
+            self.0
+        }
+
+        fn set_span(&mut self, span: Span) {
+            self.0 = Some(span);
+        }
+    }
+
+    let source = "int main(void) {\n    return foo;\n}\n";
+    assert_eq!(line_col(source, 0), (1, 1));
+    assert_eq!(line_col(source, 28), (2, 12));
+
+    assert_eq!(
+        Error(Some(Span::new(28, 31))).render("main.cb", source),
+        "error: something is wrong\n \
+         --> main.cb:2:12\n  \
+         |\n\
+         2 |     return foo;\n  \
+         |            ^^^\n"
+    );
+    assert_eq!(
+        Error(None).or_span(Span::new(36, 37)).span(),
+        Some(Span::new(36, 37))
+    );
+    assert_eq!(
+        Error(None).render("main.cb", source),
+        "error: something is wrong\n --> main.cb\n"
+    );
+    assert_eq!(
+        Error(None).render_as("warning", "main.cb", source),
+        "warning: something is wrong\n --> main.cb\n"
+    );
+}
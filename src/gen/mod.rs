@@ -1,7 +1,26 @@
-use std::io;
+pub mod peephole;
+pub mod regalloc;
+pub mod select;
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
 use xten::asm::*;
 
-use crate::ir::{Const, DefinedFun, Expr, Stmt, IR};
+use crate::ir::layout::{align_to, Layout};
+use crate::ir::pass::{OptLevel, PassManager};
+use crate::ir::{Const, DefinedFun, DefinedVar, Expr, JumpEntry, Op, Stmt, Type, IR};
+use peephole::{Address, Alu, Inst, Operand, Shift, Unary};
+use select::{AddressMode, Cc, Flags, Select};
+
+/// Registers used for passing the first six integer arguments.
+const ARG_REGS: [Gpr64; 6] = [Rdi, Rsi, Rdx, Rcx, R8, R9];
+
+/// Switches with fewer cases than this never use a jump table.
+const MIN_JUMP_TABLE_CASES: usize = 4;
+/// The most jump table entries allowed per case, so that at least a third of them are cases.
+const MAX_JUMP_TABLE_ENTRIES_PER_CASE: u64 = 3;
+/// Binary search over the cases of a switch stops halving at this many cases.
+const MAX_COMPARE_CHAIN: usize = 3;
 
 pub fn main_object() -> io::Result<Object> {
     let mut w = Writer::new();
@@ -18,57 +37,864 @@ pub fn main_object() -> io::Result<Object> {
     w.produce()
 }
 
-pub fn compile(ir: IR) -> Result<Vec<Object>, io::Error> {
-    let mut objects = vec![];
-    for fun in ir.fun {
-        objects.push(compile_function(fun)?);
-    }
-    Ok(objects)
+fn unsupported<T>(message: String) -> io::Result<T> {
+    Err(io::Error::other(message))
 }
 
-pub fn compile_function(fun: DefinedFun) -> io::Result<Object> {
+pub fn compile(ir: IR) -> io::Result<Object> {
     let mut w = Writer::new();
-    let main = w.get_label(&fun.name);
+    let defined = ir
+        .fun
+        .iter()
+        .map(|f| f.name.clone())
+        .collect::<HashSet<_>>();
+    let globals = ir
+        .var
+        .iter()
+        .map(|v| v.name.clone())
+        .collect::<HashSet<_>>();
 
-    w.define(main, true);
+    for var in ir.var.iter() {
+        compile_variable(&mut w, var)?;
+    }
+    for fun in ir.fun.iter() {
+        compile_function(&mut w, fun, &defined, &globals)?;
+    }
+    w.produce()
+}
+
+/// Allocate a global variable. Initialized variables are placed in `.data`, and the others are
+/// placed in `.bss`.
+pub fn compile_variable(w: &mut Writer, var: &DefinedVar) -> io::Result<()> {
+    let label = w.get_label(&var.name);
+    match &var.init {
+        Some(Const::Int(value)) => {
+            let size = var.layout.size as usize;
+            let mut data = w.data();
+            data.align(var.layout.align as u64)?;
+            data.define(label, !var.is_private);
+            data.write_all(&value.to_le_bytes()[..size])
+        }
+        Some(Const::Str(s)) => {
+            let literal = string_literal(w, s)?;
+            let mut data = w.data();
+            data.align(8)?;
+            data.define(label, !var.is_private);
+            data.use_relative(0, literal, 0, RelocType::Abs64);
+            data.write_all(&[0; 8])
+        }
+        None => {
+            let mut bss = w.bss();
+            bss.align(var.layout.align as u64)?;
+            bss.define(label, !var.is_private);
+            bss.allocate(var.layout.size as u64)
+        }
+    }
+}
+
+/// Write a NUL-terminated string literal into `.rodata` and return its label.
+fn string_literal(w: &mut Writer, s: &[u8]) -> io::Result<Label> {
+    let label = w.issue_label();
+    let mut rodata = w.rodata();
+    rodata.define(label, false);
+    rodata.write_all(s)?;
+    rodata.write_all(&[0])?;
+    Ok(label)
+}
+
+pub fn compile_function(
+    w: &mut Writer,
+    fun: &DefinedFun,
+    defined: &HashSet<String>,
+    globals: &HashSet<String>,
+) -> io::Result<()> {
+    let mut gen = FunctionGen::new(w, fun, defined, globals);
+    let label_uses = select::label_uses(&fun.body);
+    gen.prologue()?;
+    let mut i = 0;
+    while i < fun.body.len() {
+        if let Some(select) = select::select(&fun.body[i..], &label_uses) {
+            gen.select(&select)?;
+            i += select.len;
+        } else {
+            gen.stmt(&fun.body[i])?;
+            i += 1;
+        }
+    }
+    gen.epilogue()?;
+
+    let mut code = gen.code;
+    peephole::optimize(&mut code);
+    for inst in code.iter() {
+        inst.emit(w)?;
+    }
+    Ok(())
+}
+
+/// Whether `op` can be applied to `rax` and `operand` by `FunctionGen::bin_op`.
+fn has_operand_form(op: &Op, operand: Operand) -> bool {
+    match op {
+        Op::Add | Op::Sub | Op::Mul | Op::BitAnd | Op::BitOr | Op::BitXor => true,
+        Op::BitLShift | Op::BitRShift | Op::ArithRShift => matches!(operand, Operand::Imm(_)),
+        _ => false,
+    }
+}
+
+/// Whether the sorted, distinct `cases` of a switch are many and close enough together to be
+/// dispatched through a jump table.
+fn is_dense(cases: &[(i64, Label)]) -> bool {
+    let (Some(&(min, _)), Some(&(max, _))) = (cases.first(), cases.last()) else {
+        return false;
+    };
+    let entries = (max as i128 - min as i128 + 1) as u128;
+    cases.len() >= MIN_JUMP_TABLE_CASES
+        && entries <= (cases.len() as u64 * MAX_JUMP_TABLE_ENTRIES_PER_CASE) as u128
+}
+
+/// Stack-machine style code generator for a single function.
+///
+/// Every expression leaves its value in `rax`, and intermediate values are saved on the stack.
+/// Instructions are buffered in `code` and rewritten by `peephole::optimize` before they are
+/// encoded.
+/// `depth` tracks the number of 8-byte values pushed after the prologue so that `rsp` can be
+/// aligned to 16 bytes at call sites. Variables that are assigned a register by
+/// `regalloc::allocate` are kept in it instead of their stack slot.
+struct FunctionGen<'a> {
+    w: &'a mut Writer,
+    fun: &'a DefinedFun,
+    defined: &'a HashSet<String>,
+    globals: &'a HashSet<String>,
+    slots: HashMap<String, i32>,
+    regs: HashMap<String, Gpr64>,
+    /// Callee-saved registers used for variables, with the slots they are saved in.
+    saved: Vec<(Gpr64, i32)>,
+    frame_size: i32,
+    labels: HashMap<String, Label>,
+    epilogue: Label,
+    depth: usize,
+    code: Vec<Inst>,
+}
+
+impl<'a> FunctionGen<'a> {
+    fn new(
+        w: &'a mut Writer,
+        fun: &'a DefinedFun,
+        defined: &'a HashSet<String>,
+        globals: &'a HashSet<String>,
+    ) -> Self {
+        let allocation = regalloc::allocate(fun);
+        let mut slots = HashMap::new();
+        let mut offset = 0;
+        for (i, (name, _)) in fun.params.iter().enumerate() {
+            if i >= ARG_REGS.len() {
+                // Arguments passed on the stack are placed above the return address.
+                let pos = 16 + 8 * (i - ARG_REGS.len()) as i32;
+                slots.insert(name.clone(), pos);
+            } else if !allocation.regs.contains_key(name) {
+                offset -= 8;
+                slots.insert(name.clone(), offset);
+            }
+        }
+        for local in fun.locals.iter() {
+            if allocation.regs.contains_key(&local.name) {
+                continue;
+            }
+            let Layout { size, align } = local.layout;
+            offset = -align_to(-offset as i64 + size, align) as i32;
+            slots.insert(local.name.clone(), offset);
+        }
+        let saved = allocation
+            .saved
+            .iter()
+            .map(|reg| {
+                offset -= 8;
+                (*reg, offset)
+            })
+            .collect();
+        let epilogue = w.issue_label();
+
+        Self {
+            w,
+            fun,
+            defined,
+            globals,
+            slots,
+            regs: allocation.regs,
+            saved,
+            frame_size: (-offset + 15) / 16 * 16,
+            labels: HashMap::new(),
+            epilogue,
+            depth: 0,
+            code: vec![],
+        }
+    }
+
+    fn label(&mut self, label: &crate::ir::Label) -> Label {
+        if let Some(l) = self.labels.get(&label.0) {
+            *l
+        } else {
+            let l = self.w.issue_label();
+            self.labels.insert(label.0.clone(), l);
+            l
+        }
+    }
+
+    fn emit(&mut self, inst: Inst) -> io::Result<()> {
+        self.code.push(inst);
+        Ok(())
+    }
+
+    /// Returns the operand form of `expr` if it is a constant or a 64-bit variable in a register.
+    /// Narrower variables have to be extended when they are read.
+    fn operand(&self, expr: &Expr) -> Option<Operand> {
+        match expr {
+            Expr::Var(_type, name) if _type.size() == 8 => {
+                self.regs.get(name).copied().map(Operand::Reg)
+            }
+            expr => select::immediate(expr).map(Operand::Imm),
+        }
+    }
+
+    fn prologue(&mut self) -> io::Result<()> {
+        let name = self.w.get_label(&self.fun.name);
+        self.emit(Inst::Label(name, !self.fun.is_private))?;
+        self.emit(Inst::Push(Rbp))?;
+        self.emit(Inst::Mov(Rbp, Rsp))?;
+        if self.frame_size > 0 {
+            self.emit(Inst::Alu(Alu::Sub, Rsp, Operand::Imm(self.frame_size)))?;
+        }
+
+        for (reg, offset) in self.saved.clone() {
+            self.emit(Inst::Store(8, Address::base(Rbp, offset), reg))?;
+        }
+
+        for (i, (name, _type)) in self.fun.params.iter().enumerate() {
+            match (self.regs.get(name), ARG_REGS.get(i)) {
+                (Some(reg), Some(arg)) => self.emit(Inst::Mov(*reg, *arg))?,
+                (Some(reg), None) => {
+                    let addr = Address::base(Rbp, self.slots[name]);
+                    self.emit(Inst::Load(Type::I64, *reg, addr))?;
+                }
+                (None, Some(arg)) => {
+                    let offset = self.slots[name];
+                    self.store(Address::base(Rbp, offset), *arg, _type.size())?;
+                }
+                (None, None) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn epilogue(&mut self) -> io::Result<()> {
+        self.emit(Inst::Label(self.epilogue, false))?;
+        for (reg, offset) in self.saved.clone() {
+            self.emit(Inst::Load(Type::I64, reg, Address::base(Rbp, offset)))?;
+        }
+        self.emit(Inst::Mov(Rsp, Rbp))?;
+        self.emit(Inst::Pop(Rbp))?;
+        self.emit(Inst::Ret)
+    }
+
+    fn push(&mut self, reg: Gpr64) -> io::Result<()> {
+        self.depth += 1;
+        self.emit(Inst::Push(reg))
+    }
+
+    fn pop(&mut self, reg: Gpr64) -> io::Result<()> {
+        self.depth -= 1;
+        self.emit(Inst::Pop(reg))
+    }
+
+    /// Load a value of `_type` at `addr` into `rax`, extending it to 64 bits.
+    fn load(&mut self, addr: Address, _type: Type) -> io::Result<()> {
+        self.emit(Inst::Load(_type, Rax, addr))
+    }
+
+    /// Truncate `rax` to the width of `_type` and extend it back to 64 bits.
+    fn extend(&mut self, _type: Type) -> io::Result<()> {
+        match _type {
+            Type::I64 | Type::U64 => Ok(()),
+            _type => self.emit(Inst::Extend(_type, Rax, Rax)),
+        }
+    }
+
+    /// Store the lower `size` bytes of `reg` at `addr`.
+    fn store(&mut self, addr: Address, reg: Gpr64, size: u8) -> io::Result<()> {
+        self.emit(Inst::Store(size, addr, reg))
+    }
+
+    /// Obtain the memory operand of a variable. This may clobber `rax`.
+    fn variable(&mut self, name: &str) -> io::Result<Address> {
+        if let Some(offset) = self.slots.get(name) {
+            return Ok(Address::base(Rbp, *offset));
+        }
+        self.global_address(name)?;
+        Ok(Address::base(Rax, 0))
+    }
 
-    for stmt in fun.body {
+    /// Load the address of a global symbol into `rax`.
+    fn global_address(&mut self, name: &str) -> io::Result<()> {
+        let label = self.w.get_label(name);
+        if self.globals.contains(name) || self.defined.contains(name) {
+            self.emit(Inst::LeaLabel(Rax, label))
+        } else {
+            self.emit(Inst::LoadAddress(Rax, label))
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> io::Result<()> {
         match stmt {
-            Stmt::ExprStmt(expr) | Stmt::Return(Some(expr)) => match expr {
-                Expr::Const(con) => match con {
-                    Const::Int(i) => {
-                        w.movl(Eax, i)?;
+            Stmt::Return(expr) => {
+                if let Some(expr) = expr {
+                    self.expr(expr)?;
+                }
+                self.emit(Inst::Jmp(self.epilogue))
+            }
+            Stmt::Jump { label } => {
+                let label = self.label(label);
+                self.emit(Inst::Jmp(label))
+            }
+            Stmt::CJump {
+                cond,
+                then_label,
+                else_label,
+            } => {
+                let then_label = self.label(then_label);
+                let else_label = self.label(else_label);
+                let (flags, cc) = select::condition(cond);
+                self.flags(&flags)?;
+                self.emit(Inst::Jcc(cc, then_label))?;
+                self.emit(Inst::Jmp(else_label))
+            }
+            Stmt::Switch {
+                cond,
+                cases,
+                default_label,
+            } => {
+                self.expr(cond)?;
+                let mut cases = cases
+                    .iter()
+                    .map(|JumpEntry { value, label }| (*value, self.label(label)))
+                    .collect::<Vec<_>>();
+                // The sort is stable, so the first of duplicate cases is kept.
+                cases.sort_by_key(|&(value, _)| value);
+                cases.dedup_by_key(|&mut (value, _)| value);
+                let default_label = self.label(default_label);
+                if is_dense(&cases) {
+                    self.jump_table(&cases, default_label)
+                } else {
+                    self.binary_search(&cases, default_label)
+                }
+            }
+            Stmt::Label(label) => {
+                let label = self.label(label);
+                self.emit(Inst::Label(label, false))
+            }
+            Stmt::ExprStmt(expr) => self.expr(expr),
+            Stmt::Assign(_type, dst, src) => self.assign(*_type, dst, src),
+            Stmt::Copy { dst, src, size } => self.copy(dst, src, *size),
+        }
+    }
+
+    /// Jump to the label of the case `rax` matches through a table of label differences on
+    /// `.rodata`, which has an entry for every value between the smallest and the largest case.
+    fn jump_table(&mut self, cases: &[(i64, Label)], default_label: Label) -> io::Result<()> {
+        let min = cases[0].0;
+        let range = cases[cases.len() - 1].0.wrapping_sub(min);
+        if min != 0 {
+            let operand = self.immediate(min)?;
+            self.emit(Inst::Alu(Alu::Sub, Rax, operand))?;
+        }
+        // Values below the smallest case wrap around and compare above the range.
+        self.emit(Inst::Alu(Alu::Cmp, Rax, Operand::Imm(range as i32)))?;
+        self.emit(Inst::Jcc(Cc::A, default_label))?;
+
+        let table = self.w.issue_label();
+        self.emit(Inst::LeaLabel(Rcx, table))?;
+        let entry = Address {
+            base: Some(Rcx),
+            index: Some((Rax, 4)),
+            disp: 0,
+        };
+        self.emit(Inst::Load(Type::I32, Rax, entry))?;
+        self.emit(Inst::Alu(Alu::Add, Rax, Operand::Reg(Rcx)))?;
+        self.emit(Inst::JmpReg(Rax))?;
+
+        let mut rodata = self.w.rodata();
+        rodata.align(4)?;
+        rodata.define(table, false);
+        let mut cases = cases.iter().peekable();
+        for offset in 0..=range {
+            let label = match cases.next_if(|&&(value, _)| value == min.wrapping_add(offset)) {
+                Some(&(_, label)) => label,
+                None => default_label,
+            };
+            rodata.write_label_difference(label, table)?;
+        }
+        Ok(())
+    }
+
+    /// Jump to the label of the case `rax` matches by halving the sorted cases with signed
+    /// comparisons, and comparing them one by one once only a few remain.
+    fn binary_search(&mut self, cases: &[(i64, Label)], default_label: Label) -> io::Result<()> {
+        if cases.len() <= MAX_COMPARE_CHAIN {
+            for &(value, label) in cases {
+                let operand = self.immediate(value)?;
+                self.emit(Inst::Alu(Alu::Cmp, Rax, operand))?;
+                self.emit(Inst::Jcc(Cc::E, label))?;
+            }
+            return self.emit(Inst::Jmp(default_label));
+        }
+
+        let (lower, upper) = cases.split_at(cases.len() / 2);
+        let (value, label) = upper[0];
+        let lower_label = self.w.issue_label();
+        let operand = self.immediate(value)?;
+        self.emit(Inst::Alu(Alu::Cmp, Rax, operand))?;
+        self.emit(Inst::Jcc(Cc::E, label))?;
+        self.emit(Inst::Jcc(Cc::L, lower_label))?;
+        self.binary_search(&upper[1..], default_label)?;
+        self.emit(Inst::Label(lower_label, false))?;
+        self.binary_search(lower, default_label)
+    }
+
+    /// Returns `value` as an operand, loading it into `rcx` if it does not fit in an immediate.
+    fn immediate(&mut self, value: i64) -> io::Result<Operand> {
+        match i32::try_from(value) {
+            Ok(value) => Ok(Operand::Imm(value)),
+            Err(_) => {
+                self.emit(Inst::MovImm(Rcx, value))?;
+                Ok(Operand::Reg(Rcx))
+            }
+        }
+    }
+
+    /// Copy `size` bytes from `src` to `dst` with the widest moves that fit.
+    fn copy(&mut self, dst: &Expr, src: &Expr, size: i64) -> io::Result<()> {
+        self.expr(src)?;
+        self.push(Rax)?;
+        self.expr(dst)?;
+        self.pop(Rcx)?;
+
+        let mut offset = 0;
+        while offset < size {
+            let width = [8, 4, 2, 1]
+                .into_iter()
+                .find(|width| offset + width <= size)
+                .unwrap();
+            let _type = match width {
+                8 => Type::U64,
+                4 => Type::U32,
+                2 => Type::U16,
+                _ => Type::U8,
+            };
+            self.emit(Inst::Load(_type, Rdx, Address::base(Rcx, offset as i32)))?;
+            self.store(Address::base(Rax, offset as i32), Rdx, width as u8)?;
+            offset += width;
+        }
+        Ok(())
+    }
+
+    fn assign(&mut self, _type: Type, dst: &Expr, src: &Expr) -> io::Result<()> {
+        match dst {
+            Expr::Addr(_, name) if self.regs.contains_key(name) => {
+                self.expr(src)?;
+                self.emit(Inst::Mov(self.regs[name], Rax))
+            }
+            Expr::Addr(_, name) if self.slots.contains_key(name) => {
+                let addr = Address::base(Rbp, self.slots[name]);
+                match select::immediate(src) {
+                    Some(value) => self.emit(Inst::StoreImm(_type.size(), addr, value)),
+                    None => {
+                        self.expr(src)?;
+                        self.store(addr, Rax, _type.size())
+                    }
+                }
+            }
+            Expr::Addr(_, name) => {
+                self.expr(src)?;
+                self.emit(Inst::Mov(Rcx, Rax))?;
+                let mem = self.variable(name)?;
+                self.store(mem, Rcx, _type.size())
+            }
+            _ => {
+                let mode = select::address_mode(dst).unwrap_or(AddressMode {
+                    base: Some(dst),
+                    index: None,
+                    disp: 0,
+                    index_first: false,
+                });
+                match select::immediate(src) {
+                    Some(value) => {
+                        let addr = self.address(&mode)?;
+                        self.emit(Inst::StoreImm(_type.size(), addr, value))
+                    }
+                    None => {
+                        self.expr(src)?;
+                        self.push(Rax)?;
+                        let addr = self.address(&mode)?;
+                        self.pop(Rdx)?;
+                        self.store(addr, Rdx, _type.size())
+                    }
+                }
+            }
+        }
+    }
+
+    /// Evaluate the terms of an address into registers and return the address operand. This may
+    /// clobber `rax` and `rcx`.
+    ///
+    /// A variable on the stack is addressed from `rbp` and a 64-bit variable in a register is
+    /// used in place. Otherwise the term that comes last in the expression is evaluated first,
+    /// like the operands of other binary operators.
+    fn address(&mut self, mode: &AddressMode) -> io::Result<Address> {
+        let mut disp = mode.disp;
+        let mut base = mode.base.map(|expr| (self.register_of(expr), expr));
+        if let Some((None, expr @ Expr::Addr(_, name))) = base {
+            if let Some(sum) = self
+                .slots
+                .get(name)
+                .and_then(|offset| disp.checked_add(*offset))
+            {
+                disp = sum;
+                base = Some((Some(Rbp), expr));
+            }
+        }
+        let index = mode.index.map(|(expr, _)| (self.register_of(expr), expr));
+
+        let (base, index) = match (base, index) {
+            (Some((None, base)), Some((None, index))) => {
+                let (first, second) = if mode.index_first {
+                    (base, index)
+                } else {
+                    (index, base)
+                };
+                self.expr(first)?;
+                self.push(Rax)?;
+                self.expr(second)?;
+                self.pop(Rcx)?;
+                if mode.index_first {
+                    (Some(Rcx), Some(Rax))
+                } else {
+                    (Some(Rax), Some(Rcx))
+                }
+            }
+            (base, index) => {
+                let base = match base {
+                    Some((Some(reg), _)) => Some(reg),
+                    Some((None, expr)) => {
+                        self.expr(expr)?;
+                        Some(Rax)
+                    }
+                    None => None,
+                };
+                let index = match index {
+                    Some((Some(reg), _)) => Some(reg),
+                    Some((None, expr)) => {
+                        self.expr(expr)?;
+                        Some(Rax)
                     }
-                    _ => todo!(),
-                },
-                _ => todo!(),
+                    None => None,
+                };
+                (base, index)
+            }
+        };
+        Ok(Address {
+            base,
+            index: index.zip(mode.index.map(|(_, scale)| scale)),
+            disp,
+        })
+    }
+
+    fn register_of(&self, expr: &Expr) -> Option<Gpr64> {
+        match self.operand(expr) {
+            Some(Operand::Reg(reg)) => Some(reg),
+            _ => None,
+        }
+    }
+
+    /// Set the flags with `cmp` or `test`.
+    fn flags(&mut self, flags: &Flags) -> io::Result<()> {
+        match flags {
+            Flags::Compare(lhs, rhs) => match self.operand(rhs) {
+                Some(operand) => {
+                    self.expr(lhs)?;
+                    self.emit(Inst::Alu(Alu::Cmp, Rax, operand))
+                }
+                None => {
+                    self.expr(rhs)?;
+                    self.push(Rax)?;
+                    self.expr(lhs)?;
+                    self.pop(Rcx)?;
+                    self.emit(Inst::Alu(Alu::Cmp, Rax, Operand::Reg(Rcx)))
+                }
             },
-            _ => todo!(),
+            Flags::Test(value, mask) => {
+                self.expr(value)?;
+                let mask = mask.map_or(Operand::Reg(Rax), Operand::Imm);
+                self.emit(Inst::Alu(Alu::Test, Rax, mask))
+            }
         }
     }
-    w.retq()?;
-    w.produce()
+
+    /// Compute a conditional store with `cmovcc` instead of branches. Both values are evaluated
+    /// before the condition, since loading them does not preserve the flags, which `select`
+    /// only allows for conditions without calls.
+    fn select(&mut self, select: &Select) -> io::Result<()> {
+        self.expr(select.then_value)?;
+        self.push(Rax)?;
+        self.expr(select.else_value)?;
+        self.push(Rax)?;
+        let (flags, cc) = select::condition(select.cond);
+        self.flags(&flags)?;
+        self.pop(Rax)?;
+        self.pop(Rcx)?;
+        self.emit(Inst::Cmov(cc, Rax, Rcx))?;
+
+        let name = select.dst;
+        if let Some(reg) = self.regs.get(name) {
+            self.emit(Inst::Mov(*reg, Rax))
+        } else {
+            self.emit(Inst::Mov(Rcx, Rax))?;
+            let addr = self.variable(name)?;
+            self.store(addr, Rcx, select._type.size())
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> io::Result<()> {
+        match expr {
+            Expr::Const(_, Const::Int(i)) => self.emit(Inst::MovImm(Rax, *i)),
+            Expr::Const(_, Const::Str(s)) => {
+                let label = string_literal(self.w, s)?;
+                self.emit(Inst::LeaLabel(Rax, label))
+            }
+            Expr::Var(_type, name) if self.regs.contains_key(name) => {
+                self.emit(Inst::Mov(Rax, self.regs[name]))?;
+                self.extend(*_type)
+            }
+            Expr::Var(_type, name) => {
+                let addr = self.variable(name)?;
+                self.load(addr, *_type)
+            }
+            Expr::Addr(_, name) => {
+                if let Some(offset) = self.slots.get(name) {
+                    self.emit(Inst::Lea(Rax, Address::base(Rbp, *offset)))
+                } else {
+                    self.global_address(name)
+                }
+            }
+            Expr::Mem(_type, addr) => {
+                let mode = select::address_mode(addr).unwrap_or(AddressMode {
+                    base: Some(addr),
+                    index: None,
+                    disp: 0,
+                    index_first: false,
+                });
+                let addr = self.address(&mode)?;
+                self.load(addr, *_type)
+            }
+            Expr::Uni(_, Op::Not, _) => {
+                let (flags, cc) = select::condition(expr);
+                self.flags(&flags)?;
+                self.emit(Inst::Setcc(cc))
+            }
+            Expr::Uni(_type, op, expr) => {
+                self.expr(expr)?;
+                self.uni_op(op)?;
+                self.extend(*_type)
+            }
+            Expr::Bin(_, op, _, _) if Cc::of(op).is_some() => {
+                let (flags, cc) = select::condition(expr);
+                self.flags(&flags)?;
+                self.emit(Inst::Setcc(cc))
+            }
+            Expr::Bin(_type, Op::Add | Op::Sub | Op::Mul | Op::BitLShift, _, _)
+                if select::address_mode(expr).is_some_and(|mode| mode.index.is_some()) =>
+            {
+                let mode = select::address_mode(expr).unwrap();
+                let addr = self.address(&mode)?;
+                self.emit(Inst::Lea(Rax, addr))?;
+                self.extend(*_type)
+            }
+            Expr::Bin(_type, op, lhs, rhs) => {
+                let commutative =
+                    matches!(op, Op::Add | Op::Mul | Op::BitAnd | Op::BitOr | Op::BitXor);
+                let (lhs, rhs) = match (self.operand(lhs), self.operand(rhs)) {
+                    (Some(_), None) if commutative => (rhs, lhs),
+                    _ => (lhs, rhs),
+                };
+                if let Some(operand) = self
+                    .operand(rhs)
+                    .filter(|operand| has_operand_form(op, *operand))
+                {
+                    self.expr(lhs)?;
+                    self.bin_op(op, operand)?;
+                    return self.extend(*_type);
+                }
+                self.expr(rhs)?;
+                self.push(Rax)?;
+                self.expr(lhs)?;
+                self.pop(Rcx)?;
+                self.bin_op(op, Operand::Reg(Rcx))?;
+                self.extend(*_type)
+            }
+            Expr::Call(_type, name, args, variadic) => {
+                self.call(name, args, *variadic)?;
+                // The upper bits of the return value are unspecified, so we extend them by
+                // ourselves.
+                self.extend(*_type)
+            }
+        }
+    }
+
+    fn uni_op(&mut self, op: &Op) -> io::Result<()> {
+        match op {
+            Op::UMinus => self.emit(Inst::Unary(Unary::Neg, Rax)),
+            Op::BitNot => self.emit(Inst::Unary(Unary::Not, Rax)),
+            Op::SCast | Op::UCast => Ok(()),
+            op => unsupported(format!("{:?} is not an unary operator", op)),
+        }
+    }
+
+    /// Apply a binary operator to `rax` (lhs) and `rhs`, which is `rcx` unless the operator has
+    /// a form for it.
+    fn bin_op(&mut self, op: &Op, rhs: Operand) -> io::Result<()> {
+        let alu = match op {
+            Op::Add => Alu::Add,
+            Op::Sub => Alu::Sub,
+            Op::Mul => Alu::Imul,
+            Op::BitAnd => Alu::And,
+            Op::BitOr => Alu::Or,
+            Op::BitXor => Alu::Xor,
+            Op::BitLShift | Op::BitRShift | Op::ArithRShift => {
+                let shift = match op {
+                    Op::BitLShift => Shift::Shl,
+                    Op::BitRShift => Shift::Shr,
+                    _ => Shift::Sar,
+                };
+                let count = match rhs {
+                    Operand::Imm(value) => Some((value & 63) as i8),
+                    Operand::Reg(_) => None,
+                };
+                return self.emit(Inst::Shift(shift, Rax, count));
+            }
+            Op::SDiv | Op::SMod => {
+                self.emit(Inst::Cqto)?;
+                self.emit(Inst::Unary(Unary::Idiv, Rcx))?;
+                if let Op::SMod = op {
+                    self.emit(Inst::Mov(Rax, Rdx))?;
+                }
+                return Ok(());
+            }
+            Op::UDiv | Op::UMod => {
+                self.emit(Inst::Zero(Rdx))?;
+                self.emit(Inst::Unary(Unary::Div, Rcx))?;
+                if let Op::UMod = op {
+                    self.emit(Inst::Mov(Rax, Rdx))?;
+                }
+                return Ok(());
+            }
+            op => return unsupported(format!("{:?} is not a binary operator", op)),
+        };
+        self.emit(Inst::Alu(alu, Rax, rhs))
+    }
+
+    /// Call a function following the System V AMD64 ABI.
+    ///
+    /// The first six arguments are passed in registers and the rest are pushed on the stack from
+    /// right to left. `rsp` is aligned to 16 bytes at the call instruction, and `al` holds the
+    /// number of vector registers used (always zero) when the callee is variadic.
+    fn call(&mut self, name: &str, args: &[Expr], variadic: bool) -> io::Result<()> {
+        let stack_args = args.len().saturating_sub(ARG_REGS.len());
+        let padding = (self.depth + stack_args) % 2 == 1;
+        if padding {
+            self.emit(Inst::Alu(Alu::Sub, Rsp, Operand::Imm(8)))?;
+            self.depth += 1;
+        }
+
+        for arg in args.iter().rev() {
+            self.expr(arg)?;
+            self.push(Rax)?;
+        }
+        for reg in ARG_REGS.iter().take(args.len()) {
+            self.pop(*reg)?;
+        }
+
+        if variadic {
+            self.emit(Inst::Zero(Rax))?;
+        }
+
+        let label = self.w.get_label(name);
+        if self.defined.contains(name) {
+            self.emit(Inst::Call(label))?;
+        } else {
+            self.emit(Inst::CallIndirect(label))?;
+        }
+
+        let cleanup = stack_args + padding as usize;
+        if cleanup > 0 {
+            self.emit(Inst::Alu(Alu::Add, Rsp, Operand::Imm(8 * cleanup as i32)))?;
+            self.depth -= cleanup;
+        }
+        Ok(())
+    }
+}
+
+pub fn compile_from_source(source: &str) -> io::Result<Object> {
+    compile_source("<source>", source)
+}
+
+/// Compile the source text of `file`. Errors are rendered with the location in the source.
+pub fn compile_source(file: &str, source: &str) -> io::Result<Object> {
+    compile_source_with(file, source, &mut PassManager::with_level(OptLevel::O0))
+}
+
+/// Compile the source text of `file`, running `passes` over the IR before generating code.
+pub fn compile_source_with(
+    file: &str,
+    source: &str,
+    passes: &mut PassManager,
+) -> io::Result<Object> {
+    use crate::diagnostic::Diagnostic;
+
+    let mut ir = ir_of_source(file, source)?;
+    passes
+        .run(&mut ir)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.render(file, source)))?;
+    compile(ir)
+}
+
+/// Generate the IR of the source text of `file`. Errors are rendered with the location in the
+/// source.
+pub fn ir_of_source(file: &str, source: &str) -> io::Result<IR> {
+    ir_of_source_with_warnings(file, source).map(|(ir, _)| ir)
 }
 
-pub fn compile_from_source(source: &str) -> Result<Vec<Object>, io::Error> {
+/// Like `ir_of_source`, also returning the warnings rendered with their location in the source.
+pub fn ir_of_source_with_warnings(file: &str, source: &str) -> io::Result<(IR, Vec<String>)> {
     use super::ir::gen_ir;
+    use crate::diagnostic::Diagnostic;
+    use crate::resolve::type_check::type_check;
     use crate::resolve::variable_scope::{gen_scope_toplevel, Scope};
     use std::rc::{Rc, Weak};
 
-    let mut nodes = crate::node::parse(source).unwrap();
+    let error =
+        |e: &dyn Diagnostic| io::Error::new(io::ErrorKind::InvalidInput, e.render(file, source));
 
-    let scope =
-        gen_scope_toplevel(&mut nodes, Rc::new(Scope::default()), Weak::new(), false).unwrap();
+    let mut nodes = crate::node::parse(source).map_err(|e| error(&e))?;
 
-    gen_scope_toplevel(&mut nodes, scope, Weak::new(), true).unwrap();
+    let scope = gen_scope_toplevel(&mut nodes, Rc::new(Scope::default()), Weak::new(), false)
+        .map_err(|e| error(&e))?;
 
-    let ir = gen_ir(nodes);
-    assert!(ir.is_ok());
-    println!("{:#?}", ir);
+    let scope = gen_scope_toplevel(&mut nodes, scope, Weak::new(), true).map_err(|e| error(&e))?;
+    type_check(&nodes, &scope).map_err(|e| error(&e))?;
 
-    let objects = compile(ir.unwrap()).unwrap();
-
-    Ok(objects)
+    let (ir, warnings) = gen_ir(nodes, &scope).map_err(|e| error(&e))?;
+    let warnings = warnings
+        .iter()
+        .map(|w| w.render_as("warning", file, source))
+        .collect();
+    Ok((ir, warnings))
 }
 
 #[test]
@@ -77,7 +903,7 @@ pub fn test_simple() {
     use xten::jit::symbol_resolver;
 
     let mut engine = jit::Engine::new(symbol_resolver::none);
-    let objects = compile_from_source(
+    let object = compile_from_source(
         r#"
         int main(void) {
             return 20;
@@ -86,43 +912,1244 @@ pub fn test_simple() {
     )
     .unwrap();
 
-    for obj in objects {
-        engine.add_object(&obj).unwrap();
+    engine.add_object(&object).unwrap();
+
+    let main = engine.get("main").expect("main not defined");
+    let main = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(main) };
+    assert_eq!(main(), 20);
+}
+
+#[test]
+fn test_fib() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = compile_from_source(
+        r#"
+        int fib(int n) {
+            if (n < 2) {
+                return n;
+            } else {
+                return fib(n - 1) + fib(n - 2);
+            }
+        }
+
+        int main(void) {
+            return fib(10);
+        }
+           "#,
+    )
+    .unwrap();
+
+    engine.add_object(&object).unwrap();
+
+    let main = engine.get("main").expect("main not defined");
+    let main = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(main) };
+    assert_eq!(main(), 55);
+}
+
+#[test]
+fn test_operators() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = compile_from_source(
+        r#"
+        int calc(int a, int b) {
+            int c = a % b;
+            c += a - b;
+            if (a <= b && c == 4) {
+                return -1;
+            } else {
+                return c;
+            }
+        }
+           "#,
+    )
+    .unwrap();
+
+    engine.add_object(&object).unwrap();
+
+    let calc = engine.get("calc").expect("calc not defined");
+    let calc = unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32, i32) -> i32>(calc) };
+    assert_eq!(calc(17, 5), 14);
+    assert_eq!(calc(-7, 3), -11);
+    assert_eq!(calc(4, 5), 3);
+    assert_eq!(calc(5, 6), -1);
+}
+
+#[test]
+fn test_fizzbuzz() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::dl::default);
+    let object = compile_from_source(include_str!("../../examples/fizzbuzz.cb")).unwrap();
+
+    engine.add_object(&object).unwrap();
+
+    let main = engine.get("main").expect("main not defined");
+    let main = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(main) };
+    assert_eq!(main(), 0);
+}
+
+#[test]
+fn test_many_arguments() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = compile_from_source(
+        r#"
+        long sum8(char a, short b, int c, long d, int e, int f, int g, long h) {
+            return h + f + d + b - (g + e + c + a);
+        }
+
+        long nested(int a) {
+            return sum8(a, a + 1, a + 3, a + 7, a + 15, a + 31, a + 63, sum8(1, 2, 4, 8, 16, 32, 64, 128));
+        }
+           "#,
+    )
+    .unwrap();
+
+    engine.add_object(&object).unwrap();
+
+    let sum8 = engine.get("sum8").expect("sum8 not defined");
+    let sum8 = unsafe {
+        std::mem::transmute::<*const u8, extern "C" fn(i8, i16, i32, i64, i32, i32, i32, i64) -> i64>(
+            sum8,
+        )
+    };
+    assert_eq!(sum8(1, 2, 4, 8, 16, 32, 64, 128), 85);
+    assert_eq!(sum8(-1, -2, -4, -8, -16, -32, -64, -128), -85);
+    assert_eq!(sum8(0, 0, 0, 0, 0, 0, 0, 1 << 40), 1 << 40);
+
+    let nested = engine.get("nested").expect("nested not defined");
+    let nested = unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32) -> i64>(nested) };
+    assert_eq!(nested(0), 85 - 63 + 31 - 15 + 7 - 3 + 1);
+}
+
+#[test]
+fn test_register_allocation() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = compile_from_source(
+        r#"
+        long mix(long a, long b, long c, long d, long e, long f, long g, long h) {
+            return a * 2 + b - c + d * e - f + g * h;
+        }
+
+        long run(int n) {
+            long total = 0;
+            long i;
+            long x;
+            long y;
+            long *p = &x;
+            char c = 0;
+            for (i = 0; i < n; i++) {
+                x = i * i;
+                y = *p + i;
+                c = c + 1;
+                total = total + mix(i, x, y, total % 7, c, i + 1, i + 2, i + 3) % 1000;
+            }
+            return total + c;
+        }
+           "#,
+    )
+    .unwrap();
+
+    engine.add_object(&object).unwrap();
+
+    let run = engine.get("run").expect("run not defined");
+    let run = unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32) -> i64>(run) };
+
+    let mix = |a: i64, b: i64, c: i64, d: i64, e: i64, f: i64, g: i64, h: i64| {
+        a * 2 + b - c + d * e - f + g * h
+    };
+    for n in [0, 1, 10, 300] {
+        let mut total = 0;
+        let mut c = 0i8;
+        for i in 0..n as i64 {
+            let x = i * i;
+            let y = x + i;
+            c = c.wrapping_add(1);
+            total += mix(i, x, y, total % 7, c as i64, i + 1, i + 2, i + 3) % 1000;
+        }
+        assert_eq!(run(n), total + c as i64);
     }
+}
+
+#[test]
+fn test_call_libc() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::dl::default);
+    let object = compile_from_source(
+        r#"
+        import stdio;
+        import stdlib;
+
+        int main(void) {
+            char *buf = malloc(32);
+            int n = sprintf(buf, "%d%d%d%d%d%d%d", 1, 2, 3, 4, 5, 6, 7);
+            int value = atoi(buf);
+            free(buf);
+            return n == 7 && value == 1234567;
+        }
+           "#,
+    )
+    .unwrap();
+
+    engine.add_object(&object).unwrap();
 
     let main = engine.get("main").expect("main not defined");
-    let main = unsafe { std::mem::transmute::<_, extern "C" fn() -> i32>(main) };
-    println!("{}", main());
-}
-
-// #[test]
-// fn test_fib() {
-//     use xten::jit;
-//     use xten::jit::symbol_resolver;
-
-//     let mut engine = jit::Engine::new(symbol_resolver::none);
-//     let objects = compile_from_source(
-//         r#"
-//         int fib(int n) {
-//             if (n < 2) {
-//                 return n;
-//             } else {
-//                 return fib(n - 1) + fib(n - 2);
-//             }
-//         }
-
-//         int main(void) {
-//             return fib(10);
-//         }
-//            "#,
-//     )
-//     .unwrap();
-
-//     for obj in objects {
-//         engine.add_object(&obj).unwrap();
-//     }
-
-//     let main = engine.get("main").expect("main not defined");
-//     let main = unsafe { std::mem::transmute::<_, extern "C" fn() -> i32>(main) };
-//     println!("{}", main());
-// }
+    let main = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(main) };
+    assert_eq!(main(), 1);
+}
+
+#[test]
+fn test_global_variables() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::dl::default);
+    let object = compile_from_source(
+        r#"
+        import stdlib;
+
+        int counter;
+        static char small = -2;
+        long big = 1000000;
+        char *digits = "4096";
+
+        int count(void) {
+            counter = counter + 1;
+            return counter;
+        }
+
+        long check(void) {
+            return small + big + atoi(digits);
+        }
+           "#,
+    )
+    .unwrap();
+
+    let binding = |name: &str| {
+        object
+            .symbols
+            .iter()
+            .find(|s| s.name == name)
+            .map(|s| s.binding)
+            .unwrap()
+    };
+    assert!(matches!(
+        binding("counter"),
+        Binding::Global(Some(Location {
+            section: LocationSection::Bss,
+            ..
+        }))
+    ));
+    assert!(matches!(
+        binding("small"),
+        Binding::Local(Location {
+            section: LocationSection::Data,
+            ..
+        })
+    ));
+    assert_eq!(object.bss, 4);
+
+    engine.add_object(&object).unwrap();
+
+    let count = engine.get("count").expect("count not defined");
+    let count = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(count) };
+    assert_eq!(count(), 1);
+    assert_eq!(count(), 2);
+
+    let check = engine.get("check").expect("check not defined");
+    let check = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i64>(check) };
+    assert_eq!(check(), 1000000 - 2 + 4096);
+}
+
+#[test]
+fn test_goto() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = compile_from_source(
+        r#"
+        int sum(int n) {
+            int total = 0;
+        loop:
+            if (n == 0) goto done;
+            total += n;
+            n = n - 1;
+            goto loop;
+        done:
+            if (total < 0)
+                return -1;
+            return total;
+        }
+           "#,
+    )
+    .unwrap();
+
+    engine.add_object(&object).unwrap();
+
+    let sum = engine.get("sum").expect("sum not defined");
+    let sum = unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32) -> i32>(sum) };
+    assert_eq!(sum(0), 0);
+    assert_eq!(sum(10), 55);
+}
+
+#[test]
+fn test_enum() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = compile_from_source(
+        r#"
+        enum Color { Red, Green = 5, Blue, Mask = (1 << 4) - 1 }
+
+        enum Color favorite = Blue;
+
+        int pick(enum Color c) {
+            if (c == Red) return 1;
+            if (c == Green) return 2;
+            if (c == favorite) return Mask;
+            return -1;
+        }
+           "#,
+    )
+    .unwrap();
+
+    engine.add_object(&object).unwrap();
+
+    let pick = engine.get("pick").expect("pick not defined");
+    let pick = unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32) -> i32>(pick) };
+    assert_eq!(pick(0), 1);
+    assert_eq!(pick(5), 2);
+    assert_eq!(pick(6), 15);
+    assert_eq!(pick(7), -1);
+}
+
+#[test]
+fn test_diagnostics() {
+    let message = |source: &str| compile_from_source(source).unwrap_err().to_string();
+
+    assert_eq!(
+        message("int main(void) {\n    return foo;\n}\n"),
+        "error: foo is not defined\n \
+         --> <source>:2:12\n  \
+         |\n\
+         2 |     return foo;\n  \
+         |            ^^^\n"
+    );
+    assert!(message("int main(void) {\n    goto end;\n}\n").contains("<source>:2:5"));
+    assert!(message("int main(void) {\n    return 1 +;\n}\n").starts_with("error: failed to scan"));
+    assert!(message("int main(void) {\n    return '\\q';\n}\n").contains("<source>:2:12"));
+    assert!(
+        message("int main(void) {\n    return *1;\n}\n").starts_with(
+            "error: invalid operand to unary *: int is not a pointer\n --> <source>:2:12"
+        )
+    );
+}
+
+#[test]
+fn test_unsigned_compare() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = compile_from_source(
+        r#"
+        int below(unsigned int a, unsigned int b) {
+            return a < b;
+        }
+        int less(int a, int b) {
+            return a < b;
+        }
+        int rem(unsigned long a, unsigned long b) {
+            return a % b;
+        }
+           "#,
+    )
+    .unwrap();
+
+    engine.add_object(&object).unwrap();
+
+    let below = engine.get("below").expect("below not defined");
+    let below = unsafe { std::mem::transmute::<*const u8, extern "C" fn(u32, u32) -> i32>(below) };
+    let less = engine.get("less").expect("less not defined");
+    let less = unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32, i32) -> i32>(less) };
+    let rem = engine.get("rem").expect("rem not defined");
+    let rem = unsafe { std::mem::transmute::<*const u8, extern "C" fn(u64, u64) -> i32>(rem) };
+    assert_eq!(below(1, u32::MAX), 1);
+    assert_eq!(below(u32::MAX, 1), 0);
+    assert_eq!(less(-1, 1), 1);
+    assert_eq!(less(1, -1), 0);
+    assert_eq!(rem(u64::MAX, 10), (u64::MAX % 10) as i32);
+}
+
+#[test]
+fn test_compound_assign() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = compile_from_source(
+        r#"
+        int signed_ops(int a) {
+            int x = a;
+            x += 10;
+            x -= 3;
+            x *= 4;
+            x /= 3;
+            x %= 7;
+            x <<= 3;
+            x >>= 1;
+            return x;
+        }
+        int bit_ops(int a) {
+            int x = a;
+            x &= 252;
+            x |= 3;
+            x ^= 5;
+            return x;
+        }
+        unsigned int unsigned_div(unsigned int a) {
+            unsigned int u = a;
+            u /= 2;
+            return u;
+        }
+        unsigned int unsigned_shr(unsigned int a) {
+            unsigned int u = a;
+            u >>= 1;
+            return u;
+        }
+        int value(int a) {
+            int x = a;
+            int y = (x += 5);
+            return y + x;
+        }
+           "#,
+    )
+    .unwrap();
+
+    engine.add_object(&object).unwrap();
+
+    let get = |name: &str| engine.get(name).expect("not defined");
+    let signed_ops =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32) -> i32>(get("signed_ops")) };
+    let bit_ops =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32) -> i32>(get("bit_ops")) };
+    let unsigned_div =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(u32) -> u32>(get("unsigned_div")) };
+    let unsigned_shr =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(u32) -> u32>(get("unsigned_shr")) };
+    let value =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32) -> i32>(get("value")) };
+
+    let expected = |a: i32| ((a + 10 - 3) * 4 / 3 % 7) << 3 >> 1;
+    for a in [-20, -1, 0, 5, 100] {
+        assert_eq!(signed_ops(a), expected(a));
+    }
+    assert_eq!(bit_ops(0x1234), ((0x1234 & 252) | 3) ^ 5);
+    assert_eq!(unsigned_div(-2i32 as u32), (-2i32 as u32) / 2);
+    assert_eq!(unsigned_shr(-8i32 as u32), (-8i32 as u32) >> 1);
+    assert_eq!(value(1), 12);
+}
+
+#[test]
+fn test_control_flow() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = compile_from_source(
+        r#"
+        enum Kind { A = 10, B, C }
+
+        int sum_for(int n) {
+            int i;
+            int s = 0;
+            for (i = 0; i < n; i += 1) {
+                if (i == 3) continue;
+                if (i == 8) break;
+                s += i;
+            }
+            return s;
+        }
+
+        int count_dowhile(int n) {
+            int c = 0;
+            do {
+                c += 1;
+                n -= 1;
+            } while (0 < n);
+            return c;
+        }
+
+        int classify(int x) {
+            int r = 0;
+            switch (x) {
+                case 1: r += 1;
+                case 2: case 3: r += 10; break;
+                case A: case 'a': r = 100; break;
+                case (-1): return -1;
+                default: r = 7;
+            }
+            return r;
+        }
+
+        int nested(int n) {
+            int i;
+            int s = 0;
+            for (i = 0; i < n; i += 1) {
+                switch (i % 3) {
+                    case 0: continue;
+                    case 1: s += 1; break;
+                }
+                s += 100;
+            }
+            return s;
+        }
+
+        int no_default(unsigned int x) {
+            switch (x) {
+                case (-1): return 1;
+            }
+            return 0;
+        }
+           "#,
+    )
+    .unwrap();
+
+    engine.add_object(&object).unwrap();
+
+    let get = |name: &str| {
+        let f = engine.get(name).expect("not defined");
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32) -> i32>(f) }
+    };
+    let (sum_for, count_dowhile, classify, nested, no_default) = (
+        get("sum_for"),
+        get("count_dowhile"),
+        get("classify"),
+        get("nested"),
+        get("no_default"),
+    );
+
+    assert_eq!(sum_for(5), 1 + 2 + 4);
+    assert_eq!(sum_for(20), 1 + 2 + 4 + 5 + 6 + 7);
+    assert_eq!(count_dowhile(0), 1);
+    assert_eq!(count_dowhile(4), 4);
+    assert_eq!(classify(1), 11);
+    assert_eq!(classify(2), 10);
+    assert_eq!(classify(3), 10);
+    assert_eq!(classify(10), 100);
+    assert_eq!(classify(97), 100);
+    assert_eq!(classify(-1), -1);
+    assert_eq!(classify(42), 7);
+    assert_eq!(nested(7), 101 + 100 + 101 + 100);
+    assert_eq!(no_default(-1), 1);
+    assert_eq!(no_default(1), 0);
+
+    let message = |source: &str| compile_from_source(source).unwrap_err().to_string();
+    assert!(message("int main(void) {\n    break;\n}\n")
+        .starts_with("error: break statement not within a loop or switch\n --> <source>:2:5"));
+    assert!(message("int main(void) {\n    continue;\n}\n")
+        .starts_with("error: continue statement not within a loop\n --> <source>:2:5"));
+    assert!(
+        message("int main(void) {\n    switch (1) { case 1: case 1: break; }\n}\n")
+            .starts_with("error: duplicate case value 1\n")
+    );
+}
+
+#[test]
+fn test_operator_lowering() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = compile_from_source(
+        r#"
+        int calls = 0;
+
+        int touch(int v) {
+            calls += 1;
+            return v;
+        }
+
+        int logical(int a, int b) {
+            calls = 0;
+            int r = (touch(a) && touch(b)) + (touch(a) || touch(b)) * 10;
+            return r + calls * 100;
+        }
+
+        int arith(int a, int b) {
+            return a * b - a / b + a % b - (a << 2) + (a >> 1) + (a & b) - (a | b) + (a ^ b);
+        }
+
+        int assoc(void) {
+            return 10 - 4 + 3 - 100 / 10 / 5;
+        }
+
+        int compare(int a, int b) {
+            return (a < b) + (a <= b) * 2 + (a > b) * 4 + (a >= b) * 8 + (a == b) * 16 + (a != b) * 32;
+        }
+
+        int unary(int a) {
+            return -a + ~a + !a * 1000 + !!a * 100;
+        }
+
+        int increments(int a) {
+            int x = a;
+            int pre = ++x;
+            int post = x++;
+            int pre_dec = --x;
+            int post_dec = x--;
+            return pre * 1000 + post * 100 + pre_dec * 10 + post_dec + x * 10000;
+        }
+
+        long pointers(long a) {
+            long x = a;
+            long* p = &x;
+            *p = *p + 5;
+            long** pp = &p;
+            **pp += 1;
+            long* q = p;
+            q++;
+            q--;
+            return *q + (q == p) * 100;
+        }
+
+        int ternary(int a) {
+            return a > 0 ? a : 0 < a + 10 ? 100 : 200;
+        }
+
+        int sizes(void) {
+            long l;
+            char c;
+            return sizeof(int) + sizeof l * 10 + sizeof c * 100 + sizeof(long[3]) * 1000 + sizeof(char*) * 10000;
+        }
+
+        unsigned int shifts(unsigned int u, int s) {
+            return (u >> 1) + (s >> 1);
+        }
+           "#,
+    )
+    .unwrap();
+
+    engine.add_object(&object).unwrap();
+
+    let get = |name: &str| engine.get(name).expect("not defined");
+    let logical =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32, i32) -> i32>(get("logical")) };
+    let arith =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32, i32) -> i32>(get("arith")) };
+    let assoc = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(get("assoc")) };
+    let compare =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32, i32) -> i32>(get("compare")) };
+    let unary =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32) -> i32>(get("unary")) };
+    let increments =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32) -> i32>(get("increments")) };
+    let pointers =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i64) -> i64>(get("pointers")) };
+    let ternary =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32) -> i32>(get("ternary")) };
+    let sizes = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(get("sizes")) };
+    let shifts =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(u32, i32) -> u32>(get("shifts")) };
+
+    // && skips rhs when lhs is false and || skips rhs when lhs is true.
+    assert_eq!(logical(0, 5), 10 + 300);
+    assert_eq!(logical(2, 3), 1 + 10 + 300);
+    assert_eq!(logical(2, 0), 10 + 300);
+    assert_eq!(logical(0, 0), 300);
+
+    let expected =
+        |a: i32, b: i32| a * b - a / b + a % b - (a << 2) + (a >> 1) + (a & b) - (a | b) + (a ^ b);
+    for (a, b) in [(17, 5), (-17, 5), (100, -7), (3, 9)] {
+        assert_eq!(arith(a, b), expected(a, b));
+    }
+    assert_eq!(assoc(), 10 - 4 + 3 - 100 / 10 / 5);
+    assert_eq!(compare(1, 2), 1 + 2 + 32);
+    assert_eq!(compare(2, 2), 2 + 8 + 16);
+    assert_eq!(compare(3, 2), 4 + 8 + 32);
+    assert_eq!(unary(5), -5 + !5 + 100);
+    assert_eq!(unary(0), -1 + 1000);
+    assert_eq!(increments(5), 6 * 1000 + 6 * 100 + 6 * 10 + 6 + 5 * 10000);
+    assert_eq!(pointers(10), 116);
+    assert_eq!(ternary(3), 3);
+    assert_eq!(ternary(-3), 100);
+    assert_eq!(ternary(-30), 200);
+    assert_eq!(sizes(), 4 + 80 + 100 + 24000 + 80000);
+    assert_eq!(
+        shifts(u32::MAX, -8),
+        (u32::MAX >> 1).wrapping_add((-8i32 >> 1) as u32)
+    );
+}
+
+#[test]
+fn test_struct() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = compile_from_source(
+        r#"
+        struct Point {
+            long x;
+            long y;
+        }
+        struct Line {
+            struct Point from;
+            struct Point to;
+        }
+        struct Mixed {
+            char c;
+            int i;
+            long l;
+            char d;
+        }
+        union Value {
+            long a;
+            long b;
+            char[9] c;
+        }
+        typedef struct Line line_t;
+
+        struct Point origin;
+
+        long length(line_t *line) {
+            return line->to.x - line->from.x + line->to.y - line->from.y;
+        }
+
+        long members(void) {
+            line_t l;
+            l.from.x = 1;
+            l.from.y = 2;
+            l.to.x = 10;
+            l.to.y = 20;
+            return length(&l);
+        }
+
+        long copy(long flag) {
+            struct Line a;
+            struct Line b;
+            struct Point *p = &origin;
+            a.from.x = 3;
+            a.from.y = 1;
+            a.to.y = 4;
+            b = a;
+            a.from.x = 100;
+            p->y = 5;
+            b.to = flag ? origin : a.from;
+            return b.from.x * 100 + b.to.y * 10 + a.from.x;
+        }
+
+        long unions(void) {
+            union Value v;
+            v.a = 7;
+            return v.b;
+        }
+
+        long sizes(void) {
+            line_t l;
+            return sizeof(struct Mixed) + sizeof l * 100 + sizeof(union Value) * 10000;
+        }
+           "#,
+    )
+    .unwrap();
+
+    engine.add_object(&object).unwrap();
+
+    let get = |name: &str| engine.get(name).expect("not defined");
+    let members =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i64>(get("members")) };
+    let copy = unsafe { std::mem::transmute::<*const u8, extern "C" fn(i64) -> i64>(get("copy")) };
+    let unions = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i64>(get("unions")) };
+    let sizes = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i64>(get("sizes")) };
+
+    assert_eq!(members(), 9 + 18);
+    assert_eq!(copy(1), 300 + 50 + 100);
+    assert_eq!(copy(0), 300 + 10 + 100);
+    assert_eq!(unions(), 7);
+    assert_eq!(sizes(), 24 + 3200 + 160000);
+}
+
+#[test]
+fn test_arrays() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = compile_from_source(
+        r#"
+        struct Point {
+            long x;
+            long y;
+        }
+
+        long[5] table;
+
+        long sum(long[] a, long n) {
+            long total = 0;
+            long i;
+            for (i = 0; i < n; i++) {
+                total += a[i];
+            }
+            return total;
+        }
+
+        long locals(void) {
+            long[4] a;
+            long x = 100;
+            long i;
+            for (i = 0; i < 4; i++) {
+                a[i] = i * 10;
+            }
+            table[3] = 1000;
+            return sum(a, 4) + x + 2[a] + table[3];
+        }
+
+        long matrix(void) {
+            long[3][2] m;
+            long i;
+            long j;
+            for (i = 0; i < 2; i++) {
+                for (j = 0; j < 3; j++) {
+                    m[i][j] = i * 10 + j;
+                }
+            }
+            return m[1][2] * 100 + *(&m[0][0] + 4) + sizeof m[0] * 1000;
+        }
+
+        long pointers(void) {
+            int[10] a;
+            int *p = a;
+            int *q = &a[7];
+            p = p + 2;
+            p += 3;
+            p--;
+            return (q - p) * 100 + (p - a) * 10 + (1 + p == &a[5]);
+        }
+
+        long structs(void) {
+            struct Point[3] pts;
+            struct Point *p = pts;
+            (pts + 1)->x = 5;
+            p[2].y = 7;
+            p++;
+            return p->x * 10 + pts[2].y;
+        }
+           "#,
+    )
+    .unwrap();
+
+    engine.add_object(&object).unwrap();
+
+    let get = |name: &str| engine.get(name).expect("not defined");
+    let locals = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i64>(get("locals")) };
+    let matrix = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i64>(get("matrix")) };
+    let pointers =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i64>(get("pointers")) };
+    let structs =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i64>(get("structs")) };
+
+    assert_eq!(locals(), 60 + 100 + 20 + 1000);
+    assert_eq!(matrix(), 1200 + 11 + 24000);
+    assert_eq!(pointers(), 300 + 40 + 1);
+    assert_eq!(structs(), 57);
+}
+
+#[test]
+fn test_instruction_selection() {
+    use crate::ir::pass::{OptLevel, PassManager};
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = compile_source_with(
+        "<source>",
+        r#"
+        struct Pair {
+            char tag;
+            int value;
+        }
+
+        int[8] table;
+
+        long indices(long i) {
+            int[8] a;
+            int *p = &a[4];
+            unsigned int u = 1;
+            long j;
+            for (j = 0; j < 8; j++) {
+                a[j] = j * 3;
+                table[7 - j] = j;
+            }
+            return p[-2] * 10000 + a[u - 2 + 2] * 1000 + a[i * 2 + 1] * 100 + table[i + 4];
+        }
+
+        long members(long n) {
+            struct Pair[4] pairs;
+            long i;
+            for (i = 0; i < 4; i++) {
+                pairs[i].tag = 'a' + i;
+                pairs[i].value = i * n;
+            }
+            return pairs[n - 1].tag * 1000 + pairs[3].value;
+        }
+
+        long conditions(long n) {
+            long bits = 0;
+            if (5 < n) {
+                bits |= 1;
+            }
+            if (n & 4) {
+                bits |= 2;
+            }
+            if (!(n == 3)) {
+                bits |= 4;
+            }
+            if ((unsigned long)n >= 10) {
+                bits |= 8;
+            }
+            return bits + (n != 7) * 16 + (-1 < n) * 32;
+        }
+
+        long ternaries(long a, long b) {
+            long max = a > b ? a : b;
+            long sign = a < 0 ? -1 : 1;
+            int small = (a & 1) ? 3 : b;
+            return max * 100 + sign * 10 + small;
+        }
+           "#,
+        &mut PassManager::with_level(OptLevel::O2),
+    )
+    .unwrap();
+
+    engine.add_object(&object).unwrap();
+
+    let get = |name: &str| engine.get(name).expect("not defined");
+    let indices =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i64) -> i64>(get("indices")) };
+    let members =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i64) -> i64>(get("members")) };
+    let conditions =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i64) -> i64>(get("conditions")) };
+    let ternaries = unsafe {
+        std::mem::transmute::<*const u8, extern "C" fn(i64, i64) -> i64>(get("ternaries"))
+    };
+
+    assert_eq!(indices(1), 6 * 10000 + 3 * 1000 + 9 * 100 + 2);
+    assert_eq!(members(2), 'b' as i64 * 1000 + 6);
+    assert_eq!(conditions(3), 32 + 16);
+    assert_eq!(conditions(7), 32 + 2 + 4 + 1);
+    assert_eq!(conditions(-4), 16 + 8 + 4 + 2);
+    assert_eq!(ternaries(4, 9), 900 + 10 + 3 - 3 + 9);
+    assert_eq!(ternaries(-3, -5), -300 - 10 + 3);
+}
+
+#[test]
+fn test_switch_lowering() {
+    use xten::asm::LocationSection;
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = compile_from_source(
+        r#"
+        long dense(long x) {
+            switch (x) {
+                case (-2): return 20;
+                case (-1): return 10;
+                case 0: return 0;
+                case 1: case 2: return 12;
+                case 4: return 4;
+                case 6: return 6;
+                default: return -100;
+            }
+        }
+
+        long sparse(long x) {
+            switch (x) {
+                case (-50): return 1;
+                case 1: return 2;
+                case 10: return 3;
+                case 100: return 4;
+                case 1000: return 5;
+                case 10000: return 6;
+                case 100000: return 7;
+                case 1099511627776: return 8;
+            }
+            return 0;
+        }
+
+        long unsigned_dense(unsigned int x) {
+            switch (x) {
+                case 1: return 1;
+                case 2: return 2;
+                case 3: return 3;
+                case 5: return 5;
+            }
+            return 0;
+        }
+
+        long few(long x) {
+            switch (x) {
+                case 3: return 30;
+                case 1099511627776: return 40;
+                default: return 50;
+            }
+        }
+           "#,
+    )
+    .unwrap();
+
+    // Jump tables are written to `.rodata` as label differences to `.text`.
+    assert!(object
+        .relocs
+        .iter()
+        .any(|r| r.location.section == LocationSection::Rodata));
+    engine.add_object(&object).unwrap();
+
+    let get = |name: &str| {
+        let f = engine.get(name).expect("not defined");
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i64) -> i64>(f) }
+    };
+    let (dense, sparse, unsigned_dense, few) = (
+        get("dense"),
+        get("sparse"),
+        get("unsigned_dense"),
+        get("few"),
+    );
+
+    assert_eq!(
+        (-4..9).map(|x| dense(x)).collect::<Vec<_>>(),
+        [-100, -100, 20, 10, 0, 12, 12, -100, 4, -100, 6, -100, -100]
+    );
+    assert_eq!(dense(i64::MIN), -100);
+    assert_eq!(dense(i64::MAX), -100);
+    let values = [-50, 1, 10, 100, 1000, 10000, 100000, 1 << 40];
+    for (i, &value) in values.iter().enumerate() {
+        assert_eq!(sparse(value), i as i64 + 1);
+        assert_eq!(sparse(value + 1), 0);
+        assert_eq!(sparse(value - 1), 0);
+    }
+    assert_eq!(
+        (0..7).map(|x| unsigned_dense(x)).collect::<Vec<_>>(),
+        [0, 1, 2, 3, 0, 5, 0]
+    );
+    assert_eq!(unsigned_dense(-1), 0);
+    assert_eq!(unsigned_dense(1 << 32 | 2), 2);
+    assert_eq!(few(3), 30);
+    assert_eq!(few(1 << 40), 40);
+    assert_eq!(few(4), 50);
+}
+
+#[test]
+fn test_typed_values() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = compile_from_source(
+        r#"
+        struct Packed {
+            char c;
+            short s;
+            int i;
+            unsigned char u;
+        }
+
+        char g = 300;
+        unsigned short h = 65535;
+
+        int narrow(void) {
+            char c = 127;
+            unsigned char u = 255;
+            short s = 32767;
+            unsigned int w = 4294967295;
+            c++;
+            u++;
+            s += 1;
+            w += 2;
+            return c + u + s + w;
+        }
+
+        int members(void) {
+            struct Packed p;
+            p.c = -1;
+            p.s = -2;
+            p.i = 100000;
+            p.u = 200;
+            return p.c + p.s + p.i + p.u + sizeof p;
+        }
+
+        int strings(void) {
+            char *s = "hello";
+            char[4] buf;
+            buf[0] = s[1];
+            buf[1] = 'x';
+            buf[2] = -3;
+            buf[3] = 0;
+            return buf[0] + buf[1] + buf[2] * 1000;
+        }
+
+        int casts(long l) {
+            unsigned int u = 4294967295;
+            return (char)l + (unsigned char)l * 1000 + (u == -1) * 1000000 + ((long)u > 0);
+        }
+
+        unsigned long globals(void) {
+            return g + h;
+        }
+           "#,
+    )
+    .unwrap();
+
+    engine.add_object(&object).unwrap();
+
+    let get = |name: &str| engine.get(name).expect("not defined");
+    let narrow = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(get("narrow")) };
+    let members =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(get("members")) };
+    let strings =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(get("strings")) };
+    let casts =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn(i64) -> i32>(get("casts")) };
+    let globals =
+        unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> u64>(get("globals")) };
+
+    // Every value wraps around at its own width; the unsigned char becomes 0.
+    assert_eq!(narrow(), -128 - 32768 + 1);
+    assert_eq!(members(), -1 - 2 + 100000 + 200 + 12);
+    assert_eq!(strings(), 'e' as i32 + 'x' as i32 - 3000);
+    assert_eq!(casts(0x1ff), -1 + 255000 + 1000000 + 1);
+    assert_eq!(globals(), 44 + 65535);
+}
+
+#[test]
+fn test_shadowed_locals() {
+    use crate::ir::interp::Interpreter;
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let source = r#"
+        long shadow(long n) {
+            long x = 5;
+            {
+                long x = 3;
+                n = n + x;
+            }
+            {
+                long x = n * 2;
+                n = n + x;
+            }
+            return x + n * 100;
+        }
+
+        long siblings(long i) {
+            {
+                char x = 1;
+            }
+            {
+                long[4] x;
+                long j;
+                for (j = 0; j < 4; j++) {
+                    x[j] = 1000;
+                }
+                x[i] = i;
+                return x[0] + x[1] + x[2] + x[3];
+            }
+        }
+    "#;
+    let expected = [("shadow", 1, 5 + 1200), ("siblings", 2, 3002)];
+
+    let ir = ir_of_source("<source>", source).unwrap();
+    let mut interp = Interpreter::new(&ir).unwrap();
+    for (name, arg, result) in expected {
+        assert_eq!(interp.call(name, &[arg]).unwrap(), result, "{}", name);
+    }
+
+    for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        let object =
+            compile_source_with("<source>", source, &mut PassManager::with_level(level)).unwrap();
+        let mut engine = jit::Engine::new(symbol_resolver::none);
+        engine.add_object(&object).unwrap();
+        for (name, arg, result) in expected {
+            let f = engine.get(name).expect("not defined");
+            let f = unsafe { std::mem::transmute::<*const u8, extern "C" fn(i64) -> i64>(f) };
+            assert_eq!(f(arg), result, "{} at {:?}", name, level);
+        }
+    }
+}
+
+#[test]
+fn test_string_literal_bytes() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let object = compile_from_source(
+        r#"
+        int second(void) {
+            char *s = "\377\200" "\xff";
+            return s[1] & 255;
+        }
+        "#,
+    )
+    .unwrap();
+    assert_eq!(object.rodata, b"\xff\x80\xff\0");
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    engine.add_object(&object).unwrap();
+    let second = engine.get("second").expect("not defined");
+    let second = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(second) };
+    assert_eq!(second(), 0x80);
+}
+
+#[test]
+fn test_select_after_call() {
+    use crate::ir::interp::Interpreter;
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let source = r#"
+        long bump(long *p) {
+            *p = 100;
+            return 1;
+        }
+
+        long main(void) {
+            long a = 0;
+            long r;
+            r = bump(&a) ? a : 0;
+            return r;
+        }
+    "#;
+
+    let ir = ir_of_source("<source>", source).unwrap();
+    assert_eq!(
+        Interpreter::new(&ir).unwrap().call("main", &[]).unwrap(),
+        100
+    );
+
+    for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        let object =
+            compile_source_with("<source>", source, &mut PassManager::with_level(level)).unwrap();
+        let mut engine = jit::Engine::new(symbol_resolver::none);
+        engine.add_object(&object).unwrap();
+        let main = engine.get("main").expect("not defined");
+        let main = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i64>(main) };
+        assert_eq!(main(), 100, "at {:?}", level);
+    }
+}
@@ -0,0 +1,761 @@
+use std::io;
+use xten::asm::*;
+
+use super::select::Cc;
+use crate::ir::Type;
+
+/// Address `[base + index * scale + disp]` of a memory operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Address {
+    pub base: Option<Gpr64>,
+    pub index: Option<(Gpr64, u8)>,
+    pub disp: i32,
+}
+
+impl Address {
+    /// `[base + disp]`.
+    pub fn base(base: Gpr64, disp: i32) -> Self {
+        Self {
+            base: Some(base),
+            index: None,
+            disp,
+        }
+    }
+
+    /// Memory operand with the shortest displacement.
+    pub fn memory(&self) -> Memory {
+        let disp = self.disp;
+        match (self.base, self.index, i8::try_from(disp)) {
+            (Some(base), None, _) if disp == 0 => memory(base),
+            (Some(base), None, Ok(disp)) => memory(base + disp),
+            (Some(base), None, Err(_)) => memory(base + disp),
+            (Some(base), Some((index, scale)), _) if disp == 0 => memory(base + index * scale),
+            (Some(base), Some((index, scale)), Ok(disp)) => memory(base + disp + index * scale),
+            (Some(base), Some((index, scale)), Err(_)) => memory(base + disp + index * scale),
+            (None, Some((index, scale)), _) => memory(disp + index * scale),
+            (None, None, _) => memory(disp),
+        }
+    }
+
+    fn regs(&self) -> impl Iterator<Item = Gpr64> {
+        self.base
+            .into_iter()
+            .chain(self.index.map(|(index, _)| index))
+    }
+}
+
+/// Operand that an instruction can take without evaluating it into a register first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Imm(i32),
+    Reg(Gpr64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alu {
+    Add,
+    Sub,
+    And,
+    Or,
+    Xor,
+    Imul,
+    Cmp,
+    Test,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shift {
+    Shl,
+    Shr,
+    Sar,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unary {
+    Neg,
+    Not,
+    /// `idiv` of `rdx:rax`.
+    Idiv,
+    /// `div` of `rdx:rax`.
+    Div,
+}
+
+/// Instruction buffered by the code generator before it is encoded by `Writer`, so that the
+/// peephole rules can rewrite it.
+///
+/// Every instruction operates on 64-bit registers, and narrower values are extended to 64 bits
+/// when they are loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Inst {
+    /// Definition of a label, which is global if the flag is set.
+    Label(Label, bool),
+    Push(Gpr64),
+    Pop(Gpr64),
+    /// `mov dst, src`.
+    Mov(Gpr64, Gpr64),
+    MovImm(Gpr64, i64),
+    /// Load a value of the type, extending it to 64 bits.
+    Load(Type, Gpr64, Address),
+    /// Store the lower bytes of a register.
+    Store(u8, Address, Gpr64),
+    StoreImm(u8, Address, i32),
+    /// Truncate `src` to the width of the type and extend it into `dst`.
+    Extend(Type, Gpr64, Gpr64),
+    Lea(Gpr64, Address),
+    LeaLabel(Gpr64, Label),
+    /// Load the address of a label from the address table.
+    LoadAddress(Gpr64, Label),
+    Alu(Alu, Gpr64, Operand),
+    /// Shift by an immediate, or by `cl` without one.
+    Shift(Shift, Gpr64, Option<i8>),
+    Unary(Unary, Gpr64),
+    Cqto,
+    /// Clear a register with `xor`.
+    Zero(Gpr64),
+    /// Set `rax` to 1 if the condition holds, and to 0 otherwise.
+    Setcc(Cc),
+    Cmov(Cc, Gpr64, Gpr64),
+    /// Jump to a label, which `Writer` relaxes to the shortest encoding.
+    Jmp(Label),
+    Jcc(Cc, Label),
+    /// Jump to the address held in a register.
+    JmpReg(Gpr64),
+    Call(Label),
+    /// Call a label through the address table.
+    CallIndirect(Label),
+    Ret,
+}
+
+impl Inst {
+    pub fn emit(&self, w: &mut Writer) -> io::Result<()> {
+        match *self {
+            Inst::Label(label, is_global) => {
+                w.define(label, is_global);
+                Ok(())
+            }
+            Inst::Push(reg) => w.pushq(reg),
+            Inst::Pop(reg) => w.popq(reg),
+            Inst::Mov(dst, src) => w.movq(dst, src),
+            Inst::MovImm(dst, value) => w.movq(dst, value),
+            Inst::Load(_type, dst, addr) => {
+                let mem = addr.memory();
+                match _type {
+                    Type::I8 => w.movsbq(dst, mem),
+                    Type::U8 => w.movzbq(dst, mem),
+                    Type::I16 => w.movswq(dst, mem),
+                    Type::U16 => w.movzwq(dst, mem),
+                    Type::I32 => w.movslq(dst, mem),
+                    Type::U32 => w.movl(dst.l(), mem),
+                    Type::I64 | Type::U64 => w.movq(dst, mem),
+                }
+            }
+            Inst::Store(size, addr, src) => {
+                let mem = addr.memory();
+                match size {
+                    1 => w.movb(mem, src.b()),
+                    2 => w.movw(mem, src.w()),
+                    4 => w.movl(mem, src.l()),
+                    _ => w.movq(mem, src),
+                }
+            }
+            Inst::StoreImm(size, addr, value) => {
+                let mem = addr.memory();
+                match size {
+                    1 => w.movb(mem, value as i8),
+                    2 => w.movw(mem, value as i16),
+                    4 => w.movl(mem, value),
+                    _ => w.movq(mem, value),
+                }
+            }
+            Inst::Extend(_type, dst, src) => match _type {
+                Type::I8 => w.movsbq(dst, src.b()),
+                Type::U8 => w.movzbq(dst, src.b()),
+                Type::I16 => w.movswq(dst, src.w()),
+                Type::U16 => w.movzwq(dst, src.w()),
+                Type::I32 => w.movslq(dst, src.l()),
+                Type::U32 => w.movl(dst.l(), src.l()),
+                Type::I64 | Type::U64 => w.movq(dst, src),
+            },
+            Inst::Lea(dst, addr) => w.leaq(dst, addr.memory()),
+            Inst::LeaLabel(dst, label) => w.leaq(dst, label),
+            Inst::LoadAddress(dst, label) => w.movq(dst, AddressTable(label)),
+            Inst::Alu(alu, dst, Operand::Reg(src)) => match alu {
+                Alu::Add => w.addq(dst, src),
+                Alu::Sub => w.subq(dst, src),
+                Alu::And => w.andq(dst, src),
+                Alu::Or => w.orq(dst, src),
+                Alu::Xor => w.xorq(dst, src),
+                Alu::Imul => w.imulq2(dst, src),
+                Alu::Cmp => w.cmpq(dst, src),
+                Alu::Test => w.testq(dst, src),
+            },
+            // Immediates that fit in a byte are encoded as such.
+            Inst::Alu(alu, dst, Operand::Imm(value)) => match (alu, i8::try_from(value)) {
+                (Alu::Add, Ok(value)) => w.addq(dst, value),
+                (Alu::Add, Err(_)) => w.addq(dst, value),
+                (Alu::Sub, Ok(value)) => w.subq(dst, value),
+                (Alu::Sub, Err(_)) => w.subq(dst, value),
+                (Alu::And, Ok(value)) => w.andq(dst, value),
+                (Alu::And, Err(_)) => w.andq(dst, value),
+                (Alu::Or, Ok(value)) => w.orq(dst, value),
+                (Alu::Or, Err(_)) => w.orq(dst, value),
+                (Alu::Xor, Ok(value)) => w.xorq(dst, value),
+                (Alu::Xor, Err(_)) => w.xorq(dst, value),
+                (Alu::Imul, Ok(value)) => w.imulq(dst, dst, value),
+                (Alu::Imul, Err(_)) => w.imulq(dst, dst, value),
+                (Alu::Cmp, Ok(value)) => w.cmpq(dst, value),
+                (Alu::Cmp, Err(_)) => w.cmpq(dst, value),
+                (Alu::Test, _) => w.testq(dst, value),
+            },
+            Inst::Shift(shift, dst, Some(count)) => match shift {
+                Shift::Shl => w.shlq(dst, count),
+                Shift::Shr => w.shrq(dst, count),
+                Shift::Sar => w.sarq(dst, count),
+            },
+            Inst::Shift(shift, dst, None) => match shift {
+                Shift::Shl => w.shlq(dst, _Cl),
+                Shift::Shr => w.shrq(dst, _Cl),
+                Shift::Sar => w.sarq(dst, _Cl),
+            },
+            Inst::Unary(unary, reg) => match unary {
+                Unary::Neg => w.negq(reg),
+                Unary::Not => w.notq(reg),
+                Unary::Idiv => w.idivq(reg),
+                Unary::Div => w.divq(reg),
+            },
+            Inst::Cqto => w.cqto(),
+            Inst::Zero(reg) => w.xorl(reg.l(), reg.l()),
+            Inst::Setcc(cc) => {
+                match cc {
+                    Cc::E => w.sete(Al)?,
+                    Cc::Ne => w.setne(Al)?,
+                    Cc::G => w.setg(Al)?,
+                    Cc::A => w.seta(Al)?,
+                    Cc::Ge => w.setge(Al)?,
+                    Cc::Ae => w.setae(Al)?,
+                    Cc::L => w.setl(Al)?,
+                    Cc::B => w.setb(Al)?,
+                    Cc::Le => w.setle(Al)?,
+                    Cc::Be => w.setbe(Al)?,
+                }
+                w.movzbq(Rax, Al)
+            }
+            Inst::Cmov(cc, dst, src) => match cc {
+                Cc::E => w.cmoveq(dst, src),
+                Cc::Ne => w.cmovneq(dst, src),
+                Cc::G => w.cmovgq(dst, src),
+                Cc::A => w.cmovaq(dst, src),
+                Cc::Ge => w.cmovgeq(dst, src),
+                Cc::Ae => w.cmovaeq(dst, src),
+                Cc::L => w.cmovlq(dst, src),
+                Cc::B => w.cmovbq(dst, src),
+                Cc::Le => w.cmovleq(dst, src),
+                Cc::Be => w.cmovbeq(dst, src),
+            },
+            Inst::Jmp(label) => w.jmpq(label),
+            Inst::Jcc(cc, label) => match cc {
+                Cc::E => w.je(label),
+                Cc::Ne => w.jne(label),
+                Cc::G => w.jg(label),
+                Cc::A => w.ja(label),
+                Cc::Ge => w.jge(label),
+                Cc::Ae => w.jae(label),
+                Cc::L => w.jl(label),
+                Cc::B => w.jb(label),
+                Cc::Le => w.jle(label),
+                Cc::Be => w.jbe(label),
+            },
+            Inst::JmpReg(reg) => w.jmpq(reg),
+            Inst::Call(label) => w.callq(label),
+            Inst::CallIndirect(label) => w.callq(AddressTable(label)),
+            Inst::Ret => w.retq(),
+        }
+    }
+
+    /// Returns the registers that the instruction reads and writes, including the implicit ones.
+    /// Calls are not included, as they are handled separately.
+    fn regs(&self) -> (Vec<Gpr64>, Vec<Gpr64>) {
+        match *self {
+            Inst::Label(..)
+            | Inst::Jmp(_)
+            | Inst::Jcc(..)
+            | Inst::Call(_)
+            | Inst::CallIndirect(_)
+            | Inst::Ret => (vec![], vec![]),
+            Inst::JmpReg(reg) => (vec![reg], vec![]),
+            Inst::Push(reg) => (vec![reg, Rsp], vec![Rsp]),
+            Inst::Pop(reg) => (vec![Rsp], vec![reg, Rsp]),
+            Inst::Mov(dst, src) | Inst::Extend(_, dst, src) => (vec![src], vec![dst]),
+            Inst::MovImm(dst, _)
+            | Inst::LeaLabel(dst, _)
+            | Inst::LoadAddress(dst, _)
+            | Inst::Zero(dst) => (vec![], vec![dst]),
+            Inst::Load(_, dst, addr) | Inst::Lea(dst, addr) => (addr.regs().collect(), vec![dst]),
+            Inst::Store(_, addr, src) => (addr.regs().chain([src]).collect(), vec![]),
+            Inst::StoreImm(_, addr, _) => (addr.regs().collect(), vec![]),
+            Inst::Alu(alu, dst, operand) => {
+                let mut reads = vec![dst];
+                if let Operand::Reg(src) = operand {
+                    reads.push(src);
+                }
+                match alu {
+                    Alu::Cmp | Alu::Test => (reads, vec![]),
+                    _ => (reads, vec![dst]),
+                }
+            }
+            Inst::Shift(_, dst, Some(_)) => (vec![dst], vec![dst]),
+            Inst::Shift(_, dst, None) => (vec![dst, Rcx], vec![dst]),
+            Inst::Unary(Unary::Neg | Unary::Not, reg) => (vec![reg], vec![reg]),
+            Inst::Unary(Unary::Idiv | Unary::Div, reg) => (vec![reg, Rax, Rdx], vec![Rax, Rdx]),
+            Inst::Cqto => (vec![Rax], vec![Rdx]),
+            Inst::Setcc(_) => (vec![Rax], vec![Rax]),
+            Inst::Cmov(_, dst, src) => (vec![dst, src], vec![dst]),
+        }
+    }
+
+    /// Whether the instruction may transfer control or use the stack.
+    fn is_barrier(&self) -> bool {
+        let (reads, writes) = self.regs();
+        matches!(
+            self,
+            Inst::Label(..)
+                | Inst::Jmp(_)
+                | Inst::Jcc(..)
+                | Inst::JmpReg(_)
+                | Inst::Call(_)
+                | Inst::CallIndirect(_)
+                | Inst::Ret
+        ) || reads.contains(&Rsp)
+            || writes.contains(&Rsp)
+    }
+
+    fn uses(&self, reg: Gpr64) -> bool {
+        let (reads, writes) = self.regs();
+        reads.contains(&reg) || writes.contains(&reg)
+    }
+}
+
+/// Rules that rewrite the instructions and return whether anything has changed.
+/// They all take a `Vec`, as some of them remove instructions.
+pub const RULES: [fn(&mut Vec<Inst>) -> bool; 5] =
+    [push_pop, mov_to_self, store_load, cmp_zero, jump_to_next];
+
+/// Apply the rules until none of them changes the instructions.
+pub fn optimize(code: &mut Vec<Inst>) {
+    loop {
+        let mut changed = false;
+        for rule in RULES {
+            changed |= rule(code);
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+/// Replace `push src; ...; pop dst` with `mov dst, src; ...`, or remove both if `src` and `dst`
+/// are the same, as long as the instructions in between do not use the stack or `dst`.
+pub fn push_pop(code: &mut Vec<Inst>) -> bool {
+    let mut changed = false;
+    let mut i = 0;
+    while i < code.len() {
+        if let Inst::Push(src) = code[i] {
+            if let Some(j) = matching_pop(code, i) {
+                let Inst::Pop(dst) = code.remove(j) else {
+                    unreachable!()
+                };
+                changed = true;
+                if src == dst {
+                    code.remove(i);
+                    continue;
+                }
+                code[i] = Inst::Mov(dst, src);
+            }
+        }
+        i += 1;
+    }
+    changed
+}
+
+/// Returns the index of the `pop` that takes the value pushed at `i`, if it can be forwarded.
+fn matching_pop(code: &[Inst], i: usize) -> Option<usize> {
+    for (j, inst) in code.iter().enumerate().skip(i + 1) {
+        if let Inst::Pop(dst) = inst {
+            return code[i + 1..j]
+                .iter()
+                .all(|inst| !inst.uses(*dst))
+                .then_some(j);
+        }
+        if inst.is_barrier() {
+            return None;
+        }
+    }
+    None
+}
+
+/// Remove `mov reg, reg`.
+pub fn mov_to_self(code: &mut Vec<Inst>) -> bool {
+    let len = code.len();
+    code.retain(|inst| !matches!(inst, Inst::Mov(dst, src) if dst == src));
+    code.len() != len
+}
+
+/// Replace a load of the value that has just been stored at the same address with a move of the
+/// stored register.
+#[allow(clippy::ptr_arg)]
+pub fn store_load(code: &mut Vec<Inst>) -> bool {
+    let mut changed = false;
+    for i in 1..code.len() {
+        if let (Inst::Store(size, addr, src), Inst::Load(_type, dst, from)) = (code[i - 1], code[i])
+        {
+            if addr == from && _type.size() == size {
+                code[i] = if size == 8 {
+                    Inst::Mov(dst, src)
+                } else {
+                    Inst::Extend(_type, dst, src)
+                };
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/// Replace `cmp reg, 0` with the shorter `test reg, reg`, which sets the flags in the same way.
+#[allow(clippy::ptr_arg)]
+pub fn cmp_zero(code: &mut Vec<Inst>) -> bool {
+    let mut changed = false;
+    for inst in code.iter_mut() {
+        if let Inst::Alu(Alu::Cmp, reg, Operand::Imm(0)) = *inst {
+            *inst = Inst::Alu(Alu::Test, reg, Operand::Reg(reg));
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Remove jumps to a label that directly follows them, and turn `jcc L1; jmp L2; L1:` into
+/// `jncc L2; L1:`.
+pub fn jump_to_next(code: &mut Vec<Inst>) -> bool {
+    let mut changed = false;
+    let mut i = 0;
+    while i < code.len() {
+        match code[i..] {
+            [Inst::Jmp(label), ..] if falls_through(&code[i + 1..], label) => {
+                code.remove(i);
+                changed = true;
+                continue;
+            }
+            [Inst::Jcc(cc, then_label), Inst::Jmp(else_label), ..]
+                if falls_through(&code[i + 2..], then_label) =>
+            {
+                code[i] = Inst::Jcc(cc.negate(), else_label);
+                code.remove(i + 1);
+                changed = true;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    changed
+}
+
+/// Whether `label` is defined before any instruction of `code`.
+fn falls_through(code: &[Inst], label: Label) -> bool {
+    code.iter()
+        .map_while(|inst| match inst {
+            Inst::Label(l, _) => Some(*l),
+            _ => None,
+        })
+        .any(|l| l == label)
+}
+
+#[cfg(test)]
+fn assemble(code: &[Inst]) -> Vec<u8> {
+    let mut w = Writer::new();
+    for _ in 0..4 {
+        w.issue_label();
+    }
+    for inst in code.iter() {
+        inst.emit(&mut w).unwrap();
+    }
+    w.produce().unwrap().text
+}
+
+/// A sequence of instructions and the bytes it assembles to.
+#[cfg(test)]
+type Code<'a> = (&'a [Inst], &'a [u8]);
+
+/// Apply `rule` to `before` and compare the result with `after`. Both sequences must assemble
+/// to the given bytes, and `after` must not be longer.
+#[cfg(test)]
+fn assert_rule(rule: fn(&mut Vec<Inst>) -> bool, before: Code, after: Code) {
+    let mut code = before.0.to_vec();
+    assert_eq!(rule(&mut code), before.0 != after.0);
+    assert_eq!(code, after.0);
+    assert_eq!(assemble(before.0), before.1);
+    assert_eq!(assemble(&code), after.1);
+    assert!(after.1.len() <= before.1.len());
+}
+
+#[cfg(test)]
+fn labels() -> [Label; 4] {
+    let mut w = Writer::new();
+    [0; 4].map(|_| w.issue_label())
+}
+
+#[test]
+fn test_push_pop() {
+    let slot = Address::base(Rbp, -8);
+    assert_rule(
+        push_pop,
+        (
+            &[
+                Inst::Push(Rax),
+                Inst::Load(Type::I32, Rax, slot),
+                Inst::Pop(Rcx),
+                Inst::Alu(Alu::Add, Rax, Operand::Reg(Rcx)),
+                Inst::Push(Rax),
+                Inst::Pop(Rax),
+            ],
+            &[
+                0x50, // push rax
+                0x48, 0x63, 0x45, 0xf8, // movsxd rax, dword [rbp-8]
+                0x59, // pop rcx
+                0x48, 0x03, 0xc1, // add rax, rcx
+                0x50, // push rax
+                0x58, // pop rax
+            ],
+        ),
+        (
+            &[
+                Inst::Mov(Rcx, Rax),
+                Inst::Load(Type::I32, Rax, slot),
+                Inst::Alu(Alu::Add, Rax, Operand::Reg(Rcx)),
+            ],
+            &[
+                0x48, 0x8b, 0xc8, // mov rcx, rax
+                0x48, 0x63, 0x45, 0xf8, // movsxd rax, dword [rbp-8]
+                0x48, 0x03, 0xc1, // add rax, rcx
+            ],
+        ),
+    );
+    // The popped register is used in between, or the stack is.
+    let [l1, ..] = labels();
+    let code = [
+        Inst::Label(l1, false),
+        Inst::Push(Rax),
+        Inst::Shift(Shift::Shl, Rax, None),
+        Inst::Pop(Rcx),
+        Inst::Push(Rax),
+        Inst::Call(l1),
+        Inst::Pop(Rcx),
+        Inst::Push(Rax),
+        Inst::Alu(Alu::Sub, Rsp, Operand::Imm(8)),
+        Inst::Pop(Rcx),
+    ];
+    let bytes = [
+        0x50, // push rax
+        0x48, 0xd3, 0xe0, // shl rax, cl
+        0x59, // pop rcx
+        0x50, // push rax
+        0xe8, 0xf5, 0xff, 0xff, 0xff, // call l1
+        0x59, // pop rcx
+        0x50, // push rax
+        0x48, 0x83, 0xec, 0x08, // sub rsp, 8
+        0x59, // pop rcx
+    ];
+    assert_rule(push_pop, (&code, &bytes), (&code, &bytes));
+}
+
+#[test]
+fn test_mov_to_self() {
+    assert_rule(
+        mov_to_self,
+        (
+            &[
+                Inst::Mov(Rax, Rax),
+                Inst::Mov(Rcx, Rax),
+                Inst::Extend(Type::U32, Rax, Rax),
+            ],
+            &[
+                0x48, 0x8b, 0xc0, // mov rax, rax
+                0x48, 0x8b, 0xc8, // mov rcx, rax
+                0x8b, 0xc0, // mov eax, eax
+            ],
+        ),
+        (
+            &[Inst::Mov(Rcx, Rax), Inst::Extend(Type::U32, Rax, Rax)],
+            &[
+                0x48, 0x8b, 0xc8, // mov rcx, rax
+                0x8b, 0xc0, // mov eax, eax
+            ],
+        ),
+    );
+}
+
+#[test]
+fn test_store_load() {
+    let slot = Address::base(Rbp, -16);
+    let other = Address::base(Rbp, -8);
+    assert_rule(
+        store_load,
+        (
+            &[
+                Inst::Store(8, slot, Rax),
+                Inst::Load(Type::I64, Rax, slot),
+                Inst::Store(4, slot, Rcx),
+                Inst::Load(Type::I32, Rax, slot),
+                Inst::Store(4, slot, Rax),
+                Inst::Load(Type::I64, Rax, slot),
+                Inst::Store(8, slot, Rax),
+                Inst::Load(Type::I64, Rax, other),
+            ],
+            &[
+                0x48, 0x89, 0x45, 0xf0, // mov [rbp-16], rax
+                0x48, 0x8b, 0x45, 0xf0, // mov rax, [rbp-16]
+                0x89, 0x4d, 0xf0, // mov [rbp-16], ecx
+                0x48, 0x63, 0x45, 0xf0, // movsxd rax, dword [rbp-16]
+                0x89, 0x45, 0xf0, // mov [rbp-16], eax
+                0x48, 0x8b, 0x45, 0xf0, // mov rax, [rbp-16]
+                0x48, 0x89, 0x45, 0xf0, // mov [rbp-16], rax
+                0x48, 0x8b, 0x45, 0xf8, // mov rax, [rbp-8]
+            ],
+        ),
+        (
+            &[
+                Inst::Store(8, slot, Rax),
+                Inst::Mov(Rax, Rax),
+                Inst::Store(4, slot, Rcx),
+                Inst::Extend(Type::I32, Rax, Rcx),
+                Inst::Store(4, slot, Rax),
+                Inst::Load(Type::I64, Rax, slot),
+                Inst::Store(8, slot, Rax),
+                Inst::Load(Type::I64, Rax, other),
+            ],
+            &[
+                0x48, 0x89, 0x45, 0xf0, // mov [rbp-16], rax
+                0x48, 0x8b, 0xc0, // mov rax, rax
+                0x89, 0x4d, 0xf0, // mov [rbp-16], ecx
+                0x48, 0x63, 0xc1, // movsxd rax, ecx
+                0x89, 0x45, 0xf0, // mov [rbp-16], eax
+                0x48, 0x8b, 0x45, 0xf0, // mov rax, [rbp-16]
+                0x48, 0x89, 0x45, 0xf0, // mov [rbp-16], rax
+                0x48, 0x8b, 0x45, 0xf8, // mov rax, [rbp-8]
+            ],
+        ),
+    );
+}
+
+#[test]
+fn test_cmp_zero() {
+    assert_rule(
+        cmp_zero,
+        (
+            &[
+                Inst::Alu(Alu::Cmp, Rax, Operand::Imm(0)),
+                Inst::Alu(Alu::Cmp, Rax, Operand::Imm(1)),
+            ],
+            &[
+                0x48, 0x83, 0xf8, 0x00, // cmp rax, 0
+                0x48, 0x83, 0xf8, 0x01, // cmp rax, 1
+            ],
+        ),
+        (
+            &[
+                Inst::Alu(Alu::Test, Rax, Operand::Reg(Rax)),
+                Inst::Alu(Alu::Cmp, Rax, Operand::Imm(1)),
+            ],
+            &[
+                0x48, 0x85, 0xc0, // test rax, rax
+                0x48, 0x83, 0xf8, 0x01, // cmp rax, 1
+            ],
+        ),
+    );
+}
+
+#[test]
+fn test_jump_to_next() {
+    let [l1, l2, l3, l4] = labels();
+    assert_rule(
+        jump_to_next,
+        (
+            &[
+                Inst::Jmp(l1),
+                Inst::Label(l2, false),
+                Inst::Label(l1, false),
+                Inst::Jcc(Cc::L, l3),
+                Inst::Jmp(l4),
+                Inst::Label(l3, false),
+                Inst::Jcc(Cc::E, l4),
+                Inst::Jmp(l1),
+                Inst::Label(l4, false),
+                Inst::Ret,
+            ],
+            &[
+                0xeb, 0x00, // jmp l1
+                0x7c, 0x02, // l1: jl l3
+                0xeb, 0x04, // jmp l4
+                0x74, 0x02, // l3: je l4
+                0xeb, 0xf8, // jmp l1
+                0xc3, // l4: ret
+            ],
+        ),
+        (
+            &[
+                Inst::Label(l2, false),
+                Inst::Label(l1, false),
+                Inst::Jcc(Cc::Ge, l4),
+                Inst::Label(l3, false),
+                Inst::Jcc(Cc::Ne, l1),
+                Inst::Label(l4, false),
+                Inst::Ret,
+            ],
+            &[
+                0x7d, 0x02, // l1: jge l4
+                0x75, 0xfc, // l3: jne l1
+                0xc3, // l4: ret
+            ],
+        ),
+    );
+}
+
+#[test]
+fn test_emit() {
+    let entry = Address {
+        base: Some(Rcx),
+        index: Some((Rax, 4)),
+        disp: 0,
+    };
+    let cases: [(Inst, &[u8]); 8] = [
+        (
+            Inst::Alu(Alu::Add, Rax, Operand::Imm(1)),
+            &[0x48, 0x83, 0xc0, 0x01],
+        ),
+        (
+            Inst::Alu(Alu::Cmp, Rcx, Operand::Imm(1000)),
+            &[0x48, 0x81, 0xf9, 0xe8, 0x03, 0x00, 0x00],
+        ),
+        (
+            Inst::Alu(Alu::Imul, Rax, Operand::Imm(3)),
+            &[0x48, 0x6b, 0xc0, 0x03],
+        ),
+        (Inst::Zero(Rcx), &[0x33, 0xc9]),
+        (
+            Inst::Load(Type::I64, Rax, Address::base(Rbp, -8)),
+            &[0x48, 0x8b, 0x45, 0xf8],
+        ),
+        (Inst::Load(Type::I32, Rax, entry), &[0x48, 0x63, 0x04, 0x81]),
+        (
+            Inst::StoreImm(1, Address::base(Rbp, -1), 7),
+            &[0xc6, 0x45, 0xff, 0x07],
+        ),
+        (
+            Inst::Setcc(Cc::L),
+            &[0x0f, 0x9c, 0xc0, 0x48, 0x0f, 0xb6, 0xc0],
+        ),
+    ];
+    for (inst, bytes) in cases {
+        assert_eq!(assemble(&[inst]), bytes, "{:?}", inst);
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::{HashMap, HashSet};
+use xten::asm::*;
+
+use crate::ir::cfg::Cfg;
+use crate::ir::{DefinedFun, Expr, Stmt};
+
+/// Registers that a callee may clobber. The other caller-saved registers are used by the code
+/// generator for intermediate values, divisions, shifts and arguments.
+const CALLER_SAVED: [Gpr64; 2] = [R10, R11];
+
+/// Registers that a callee must preserve.
+const CALLEE_SAVED: [Gpr64; 5] = [Rbx, R12, R13, R14, R15];
+
+/// Range of program points in which a variable is live.
+///
+/// Statement `i` of the body reads its operands at point `2 * i` and stores to a variable at
+/// point `2 * i + 1`. Parameters are stored at point `-1`, before the first statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interval {
+    pub name: String,
+    pub start: i64,
+    pub end: i64,
+    /// Whether a call may be made while the variable holds a value that is read later.
+    pub crosses_call: bool,
+}
+
+/// Registers assigned to the variables of a function.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Allocation {
+    pub regs: HashMap<String, Gpr64>,
+    /// Callee-saved registers that are assigned to a variable, which the function has to save.
+    pub saved: Vec<Gpr64>,
+}
+
+/// Assign registers to the variables of `fun` by linear scan over their live intervals.
+///
+/// A variable that is live across a call only gets a callee-saved register. When the registers
+/// run out, the variable whose interval ends last is spilled and stays in its stack slot for
+/// its entire lifetime.
+pub fn allocate(fun: &DefinedFun) -> Allocation {
+    let mut intervals = intervals(fun);
+    intervals.sort_by_key(|interval| interval.start);
+
+    let mut regs = HashMap::new();
+    let mut free = CALLER_SAVED
+        .iter()
+        .chain(CALLEE_SAVED.iter())
+        .copied()
+        .collect::<Vec<_>>();
+    let mut active: Vec<&Interval> = vec![];
+    for interval in intervals.iter() {
+        active.retain(|other| {
+            if other.end < interval.start {
+                free.push(regs[&other.name]);
+                false
+            } else {
+                true
+            }
+        });
+
+        // Caller-saved registers are preferred as they need not be saved by the function.
+        let usable = |reg: &Gpr64| !interval.crosses_call || CALLEE_SAVED.contains(reg);
+        let free_reg = free
+            .iter()
+            .position(|reg| usable(reg) && CALLER_SAVED.contains(reg))
+            .or_else(|| free.iter().position(usable));
+        if let Some(i) = free_reg {
+            regs.insert(interval.name.clone(), free.remove(i));
+            active.push(interval);
+            continue;
+        }
+
+        let victim = active
+            .iter()
+            .enumerate()
+            .filter(|(_, other)| usable(&regs[&other.name]))
+            .max_by_key(|(_, other)| other.end);
+        if let Some((i, other)) = victim {
+            if other.end > interval.end {
+                let reg = regs.remove(&other.name).unwrap();
+                regs.insert(interval.name.clone(), reg);
+                active.remove(i);
+                active.push(interval);
+            }
+        }
+    }
+
+    let saved = CALLEE_SAVED
+        .into_iter()
+        .filter(|reg| regs.values().any(|r| r == reg))
+        .collect();
+    Allocation { regs, saved }
+}
+
+/// Returns the variables of `fun` that can be kept in a register.
+///
+/// Only the variables that are read by `Expr::Var` and written by `Stmt::Assign` to their
+/// `Expr::Addr` with their own size are candidates, since any other use of the address may
+/// read or write them through memory.
+fn candidates(fun: &DefinedFun) -> HashSet<&str> {
+    let mut sizes = HashMap::new();
+    for (name, _type) in fun.params.iter() {
+        sizes.insert(name.as_str(), _type.size() as i64);
+    }
+    for local in fun.locals.iter() {
+        let size = sizes
+            .entry(local.name.as_str())
+            .or_insert(local.layout.size);
+        if *size != local.layout.size {
+            *size = 0;
+        }
+    }
+    sizes.retain(|_, size| matches!(size, 1 | 2 | 4 | 8));
+
+    let mut excluded = HashSet::new();
+    for stmt in fun.body.iter() {
+        let exprs = match stmt {
+            Stmt::Assign(_type, Expr::Addr(_, name), src) => {
+                if sizes.get(name.as_str()) != Some(&(_type.size() as i64)) {
+                    excluded.insert(name.as_str());
+                }
+                vec![src]
+            }
+            stmt => stmt.exprs(),
+        };
+        for expr in exprs {
+            expr.walk(&mut |expr| match expr {
+                Expr::Var(_type, name)
+                    if sizes.get(name.as_str()) == Some(&(_type.size() as i64)) => {}
+                Expr::Var(_, name) | Expr::Addr(_, name) => {
+                    excluded.insert(name.as_str());
+                }
+                _ => {}
+            });
+        }
+    }
+
+    sizes
+        .into_keys()
+        .filter(|name| !excluded.contains(name))
+        .collect()
+}
+
+/// Returns the live intervals of the variables of `fun` that can be kept in a register.
+///
+/// Liveness is computed on the basic blocks, and an interval spans every point at which the
+/// variable is live, so it also covers the holes in between.
+pub fn intervals(fun: &DefinedFun) -> Vec<Interval> {
+    let candidates = candidates(fun);
+    let cfg = Cfg::new(&fun.body);
+
+    // Points at which each variable is read or written, and at which a call is made.
+    let mut points = HashMap::<&str, Vec<i64>>::new();
+    let mut calls = vec![];
+    for (name, _) in fun.params.iter() {
+        if candidates.contains(name.as_str()) {
+            points.entry(name).or_default().push(-1);
+        }
+    }
+    for (i, stmt) in fun.body.iter().enumerate() {
+        let point = 2 * i as i64;
+        for expr in stmt.exprs() {
+            expr.walk(&mut |expr| match expr {
+                Expr::Var(_, name) if candidates.contains(name.as_str()) => {
+                    points.entry(name).or_default().push(point);
+                }
+                Expr::Call(..) => calls.push(point),
+                _ => {}
+            });
+        }
+        if let Some(name) = assigned(stmt).filter(|name| candidates.contains(name)) {
+            points.entry(name).or_default().push(point + 1);
+        }
+    }
+
+    // Upward-exposed reads and writes of each block, and the index of its first statement.
+    let mut starts = vec![];
+    let mut uses = vec![];
+    let mut defs = vec![];
+    let mut start = 0;
+    for block in cfg.blocks.iter() {
+        let mut used = HashSet::new();
+        let mut defined = HashSet::new();
+        for stmt in fun.body[start..start + block.stmts.len()].iter() {
+            for expr in stmt.exprs() {
+                expr.walk(&mut |expr| {
+                    if let Expr::Var(_, name) = expr {
+                        if candidates.contains(name.as_str()) && !defined.contains(name) {
+                            used.insert(name.clone());
+                        }
+                    }
+                });
+            }
+            if let Some(name) = assigned(stmt).filter(|name| candidates.contains(name)) {
+                defined.insert(name.to_string());
+            }
+        }
+        starts.push(start);
+        uses.push(used);
+        defs.push(defined);
+        start += block.stmts.len();
+    }
+
+    let mut live_in = vec![HashSet::<String>::new(); cfg.blocks.len()];
+    let mut live_out = vec![HashSet::<String>::new(); cfg.blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in (0..cfg.blocks.len()).rev() {
+            let out = cfg.blocks[i]
+                .succs
+                .iter()
+                .flat_map(|succ| live_in[*succ].iter().cloned())
+                .collect::<HashSet<_>>();
+            let mut live = uses[i].clone();
+            live.extend(out.difference(&defs[i]).cloned());
+            if live != live_in[i] || out != live_out[i] {
+                live_in[i] = live;
+                live_out[i] = out;
+                changed = true;
+            }
+        }
+    }
+
+    for (i, block) in cfg.blocks.iter().enumerate() {
+        let first = 2 * starts[i] as i64;
+        let last = 2 * (starts[i] + block.stmts.len()) as i64 - 1;
+        for name in live_in[i].iter() {
+            points.get_mut(name.as_str()).unwrap().push(first);
+        }
+        for name in live_out[i].iter() {
+            points.get_mut(name.as_str()).unwrap().push(last);
+        }
+    }
+
+    let mut intervals = points
+        .into_iter()
+        .map(|(name, points)| {
+            let start = *points.iter().min().unwrap();
+            let end = *points.iter().max().unwrap();
+            Interval {
+                name: name.to_string(),
+                start,
+                end,
+                crosses_call: calls.iter().any(|call| start < *call && *call <= end),
+            }
+        })
+        .collect::<Vec<_>>();
+    intervals.sort_by(|a, b| (a.start, &a.name).cmp(&(b.start, &b.name)));
+    intervals
+}
+
+/// Returns the variable that `stmt` stores to.
+fn assigned(stmt: &Stmt) -> Option<&str> {
+    match stmt {
+        Stmt::Assign(_, Expr::Addr(_, name), _) => Some(name),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+fn fun_of(source: &str) -> DefinedFun {
+    let mut ir = crate::ir::text::parse(source).unwrap();
+    ir.fun.remove(0)
+}
+
+#[test]
+fn test_intervals() {
+    let fun = fun_of(
+        r#"
+        fun f(i32 n) -> i32 {
+            local i size 4 align 4
+            local s size 4 align 4
+            local buf size 4 align 4
+            local c size 1 align 1
+            store i32 (addr u64 i), (const i32 0)
+            store i32 (addr u64 s), (const i32 0)
+        .L1:
+            cjump (slt i32 (var i32 i) (var i32 n)), .L2, .L3
+        .L2:
+            store i32 (addr u64 s), (add i32 (var i32 s) (call i32 g (var i32 i)))
+            store i32 (addr u64 i), (add i32 (var i32 i) (const i32 1))
+            jump .L1
+        .L3:
+            eval (call i32 h (addr u64 buf))
+            store i16 (addr u64 c), (const i16 1)
+            return (var i32 s)
+        }
+        "#,
+    );
+    let interval = |name: &str, start, end| Interval {
+        name: name.to_string(),
+        start,
+        end,
+        crosses_call: true,
+    };
+    assert_eq!(
+        intervals(&fun),
+        vec![
+            interval("n", -1, 15),
+            interval("i", 1, 15),
+            interval("s", 3, 22)
+        ]
+    );
+
+    let allocation = allocate(&fun);
+    assert_eq!(allocation.regs["n"], Rbx);
+    assert_eq!(allocation.regs["i"], R12);
+    assert_eq!(allocation.regs["s"], R13);
+    assert_eq!(allocation.saved, vec![Rbx, R12, R13]);
+}
+
+#[test]
+fn test_spill() {
+    let fun = fun_of(
+        r#"
+        fun f() -> i64 {
+            local a size 8 align 8
+            local b size 8 align 8
+            local c size 8 align 8
+            local d size 8 align 8
+            local e size 8 align 8
+            local f size 8 align 8
+            local g size 8 align 8
+            local h size 8 align 8
+            store i64 (addr u64 a), (const i64 1)
+            store i64 (addr u64 b), (const i64 2)
+            store i64 (addr u64 c), (const i64 3)
+            store i64 (addr u64 d), (const i64 4)
+            store i64 (addr u64 e), (const i64 5)
+            store i64 (addr u64 f), (const i64 6)
+            store i64 (addr u64 g), (const i64 7)
+            store i64 (addr u64 h), (const i64 8)
+            eval (add i64 (add i64 (add i64 (var i64 b) (var i64 c)) (add i64 (var i64 d) (var i64 e))) (add i64 (add i64 (var i64 f) (var i64 g)) (var i64 h)))
+            return (var i64 a)
+        }
+        "#,
+    );
+    let allocation = allocate(&fun);
+    assert_eq!(allocation.regs.len(), 7);
+    assert!(!allocation.regs.contains_key("a"));
+    assert_eq!(allocation.regs["h"], R10);
+    assert_eq!(allocation.regs["b"], R11);
+    assert_eq!(allocation.saved, vec![Rbx, R12, R13, R14, R15]);
+}
@@ -0,0 +1,415 @@
+//! Patterns over IR trees that map to a single x86-64 instruction or operand.
+
+use std::collections::HashMap;
+
+use crate::ir::cfg::jump_targets;
+use crate::ir::{Const, Expr, Label, Op, Stmt, Type};
+
+/// Condition code of `jcc`, `setcc` and `cmovcc`, after a `cmp` or a `test`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cc {
+    E,
+    Ne,
+    G,
+    A,
+    Ge,
+    Ae,
+    L,
+    B,
+    Le,
+    Be,
+}
+
+impl Cc {
+    /// The condition code of a comparison operator.
+    pub fn of(op: &Op) -> Option<Cc> {
+        match op {
+            Op::EQ => Some(Cc::E),
+            Op::NEQ => Some(Cc::Ne),
+            Op::SGt => Some(Cc::G),
+            Op::UGt => Some(Cc::A),
+            Op::SGteq => Some(Cc::Ge),
+            Op::UGteq => Some(Cc::Ae),
+            Op::SLt => Some(Cc::L),
+            Op::ULt => Some(Cc::B),
+            Op::SLteq => Some(Cc::Le),
+            Op::ULteq => Some(Cc::Be),
+            _ => None,
+        }
+    }
+
+    /// The condition code that holds exactly when this one does not.
+    pub fn negate(self) -> Cc {
+        match self {
+            Cc::E => Cc::Ne,
+            Cc::Ne => Cc::E,
+            Cc::G => Cc::Le,
+            Cc::A => Cc::Be,
+            Cc::Ge => Cc::L,
+            Cc::Ae => Cc::B,
+            Cc::L => Cc::Ge,
+            Cc::B => Cc::Ae,
+            Cc::Le => Cc::G,
+            Cc::Be => Cc::A,
+        }
+    }
+
+    /// The condition code that holds when the operands of the comparison are swapped.
+    pub fn swap(self) -> Cc {
+        match self {
+            Cc::E | Cc::Ne => self,
+            Cc::G => Cc::L,
+            Cc::A => Cc::B,
+            Cc::Ge => Cc::Le,
+            Cc::Ae => Cc::Be,
+            Cc::L => Cc::G,
+            Cc::B => Cc::A,
+            Cc::Le => Cc::Ge,
+            Cc::Be => Cc::Ae,
+        }
+    }
+}
+
+/// Instruction that sets the flags for a condition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Flags<'a> {
+    /// `cmp lhs, rhs`.
+    Compare(&'a Expr, &'a Expr),
+    /// `test value, mask`, or `test value, value` without a mask.
+    Test(&'a Expr, Option<i32>),
+}
+
+/// Select the instruction that sets the flags for `cond`, and the condition code that holds when
+/// `cond` is true. Values are compared in 64 bits, as they are already normalized to their type.
+pub fn condition(cond: &Expr) -> (Flags<'_>, Cc) {
+    match cond {
+        Expr::Bin(_, op, lhs, rhs) if Cc::of(op).is_some() => {
+            let cc = Cc::of(op).unwrap();
+            if immediate(lhs).is_some() && immediate(rhs).is_none() {
+                (Flags::Compare(rhs, lhs), cc.swap())
+            } else {
+                (Flags::Compare(lhs, rhs), cc)
+            }
+        }
+        Expr::Uni(_, Op::Not, expr) => {
+            let (flags, cc) = condition(expr);
+            (flags, cc.negate())
+        }
+        // Sign-extended bits are only set in both operands if their sign bit is, so the masked
+        // value is zero exactly when its normalized value is.
+        Expr::Bin(_type, Op::BitAnd, lhs, rhs)
+            if lhs._type() == *_type && rhs._type() == *_type && immediate(rhs).is_some() =>
+        {
+            (Flags::Test(lhs, immediate(rhs)), Cc::Ne)
+        }
+        _ => (Flags::Test(cond, None), Cc::Ne),
+    }
+}
+
+/// Returns the value of `expr` if it can be encoded as a sign-extended 32-bit immediate.
+pub fn immediate(expr: &Expr) -> Option<i32> {
+    match expr {
+        Expr::Const(_, Const::Int(value)) => i32::try_from(*value).ok(),
+        _ => None,
+    }
+}
+
+/// Address `base + index * scale + disp` computed by a memory operand or `lea`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddressMode<'a> {
+    pub base: Option<&'a Expr>,
+    pub index: Option<(&'a Expr, u8)>,
+    pub disp: i32,
+    /// Whether `index` comes before `base` in the expression, so that it is evaluated last.
+    pub index_first: bool,
+}
+
+/// Match `expr` as a sum of a constant and at most two other terms, one of which may be
+/// multiplied by 1, 2, 4 or 8.
+///
+/// Additions and multiplications are only taken apart if they are at least as wide as `expr`,
+/// as the lower bits of the sum do not depend on the upper bits of the terms.
+pub fn address_mode(expr: &Expr) -> Option<AddressMode<'_>> {
+    let mut terms = vec![];
+    let mut disp = 0i64;
+    collect_terms(expr, expr._type().size(), 1, &mut terms, &mut disp);
+    let disp = i32::try_from(disp).ok()?;
+
+    let (base, index, index_first) = match terms[..] {
+        [] => return None,
+        [(expr, 1)] => (Some(expr), None, false),
+        [(expr, scale)] => (None, Some((expr, scale)), false),
+        [(base, 1), (index, scale)] => (Some(base), Some((index, scale)), false),
+        [(index, scale), (base, 1)] => (Some(base), Some((index, scale)), true),
+        _ => return None,
+    };
+    Some(AddressMode {
+        base,
+        index,
+        disp,
+        index_first,
+    })
+}
+
+fn collect_terms<'a>(
+    expr: &'a Expr,
+    size: u8,
+    scale: u8,
+    terms: &mut Vec<(&'a Expr, u8)>,
+    disp: &mut i64,
+) {
+    match expr {
+        Expr::Const(_, Const::Int(value)) => {
+            *disp = disp.wrapping_add(value.wrapping_mul(scale as i64));
+        }
+        Expr::Bin(_type, Op::Add, lhs, rhs) if _type.size() >= size => {
+            collect_terms(lhs, size, scale, terms, disp);
+            collect_terms(rhs, size, scale, terms, disp);
+        }
+        Expr::Bin(_type, Op::Sub, lhs, rhs) if _type.size() >= size && immediate(rhs).is_some() => {
+            collect_terms(lhs, size, scale, terms, disp);
+            *disp = disp.wrapping_sub(immediate(rhs).unwrap() as i64 * scale as i64);
+        }
+        Expr::Bin(_type, op @ (Op::Mul | Op::BitLShift), lhs, rhs) if _type.size() >= size => {
+            let factor = match (op, immediate(lhs), immediate(rhs)) {
+                (Op::Mul, Some(factor), None) => Some((factor, &**rhs)),
+                (Op::Mul, None, Some(factor)) => Some((factor, &**lhs)),
+                (Op::BitLShift, None, Some(shift @ 0..=3)) => Some((1 << shift, &**lhs)),
+                _ => None,
+            };
+            match factor {
+                Some((factor, expr)) if matches!(scale as i64 * factor as i64, 1 | 2 | 4 | 8) => {
+                    collect_terms(expr, size, scale * factor as u8, terms, disp);
+                }
+                _ => terms.push((expr, scale)),
+            }
+        }
+        _ => terms.push((expr, scale)),
+    }
+}
+
+/// `cond ? then_value : else_value` stored to a variable, which `gen_ir` lowers to
+///
+/// ```text
+///     cjump cond, T, F
+/// T:
+///     store _type (addr dst), then_value
+///     jump E
+/// F:
+///     store _type (addr dst), else_value
+///     jump E      // optional
+/// E:
+/// ```
+///
+/// and which can be computed with `cmovcc` if both values are constants or variables, whose
+/// evaluation cannot fail. The values are loaded before the condition is evaluated, so the
+/// condition must not call a function, which could change them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Select<'a> {
+    pub cond: &'a Expr,
+    pub _type: Type,
+    pub dst: &'a str,
+    pub then_value: &'a Expr,
+    pub else_value: &'a Expr,
+    /// Number of statements up to the label `E`.
+    pub len: usize,
+}
+
+/// Returns the number of jumps to each label in `body`.
+pub fn label_uses(body: &[Stmt]) -> HashMap<&str, usize> {
+    let mut uses = HashMap::new();
+    for label in body.iter().flat_map(jump_targets) {
+        *uses.entry(label.0.as_str()).or_default() += 1;
+    }
+    uses
+}
+
+/// Match a conditional store at the beginning of `stmts`. The labels `T` and `F` must not be
+/// used by any other jump in the function, as their code is replaced.
+pub fn select<'a>(stmts: &'a [Stmt], label_uses: &HashMap<&str, usize>) -> Option<Select<'a>> {
+    let is_simple = |expr: &Expr| matches!(expr, Expr::Const(_, Const::Int(_)) | Expr::Var(..));
+    let used_once = |label: &Label| label_uses.get(label.0.as_str()) == Some(&1);
+    let has_call = |expr: &Expr| {
+        let mut found = false;
+        expr.walk(&mut |expr| found |= matches!(expr, Expr::Call(..)));
+        found
+    };
+
+    let [Stmt::CJump {
+        cond,
+        then_label,
+        else_label,
+    }, Stmt::Label(t), Stmt::Assign(_type, Expr::Addr(_, dst), then_value), Stmt::Jump { label: end }, Stmt::Label(f), Stmt::Assign(else_type, Expr::Addr(_, else_dst), else_value), rest @ ..] =
+        stmts
+    else {
+        return None;
+    };
+    let len = match rest {
+        [Stmt::Label(e), ..] if e == end => 6,
+        [Stmt::Jump { label }, Stmt::Label(e), ..] if label == end && e == end => 7,
+        _ => return None,
+    };
+    if t != then_label
+        || f != else_label
+        || t == f
+        || !used_once(t)
+        || !used_once(f)
+        || _type != else_type
+        || dst != else_dst
+        || !is_simple(then_value)
+        || !is_simple(else_value)
+        || has_call(cond)
+    {
+        return None;
+    }
+    Some(Select {
+        cond,
+        _type: *_type,
+        dst,
+        then_value,
+        else_value,
+        len,
+    })
+}
+
+#[cfg(test)]
+fn expr_of(source: &str) -> Expr {
+    let source = format!("fun f() {{\n    eval {}\n}}\n", source);
+    let ir = crate::ir::text::parse(&source).unwrap();
+    match &ir.fun[0].body[0] {
+        Stmt::ExprStmt(expr) => expr.clone(),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_address_mode() {
+    let expr = expr_of(
+        "(add u64 (add u64 (var u64 p) (const i64 8)) (mul i64 (scast i64 (var i32 i)) (const i64 4)))",
+    );
+    let mode = address_mode(&expr).unwrap();
+    assert_eq!(mode.base, Some(&expr_of("(var u64 p)")));
+    assert_eq!(mode.index, Some((&expr_of("(scast i64 (var i32 i))"), 4)));
+    assert_eq!(mode.disp, 8);
+    assert!(!mode.index_first);
+
+    let expr = expr_of(
+        "(sub i64 (add i64 (shl i64 (mul i64 (const i64 2) (var i64 i)) (const i64 1)) (var i64 a)) (const i64 3))",
+    );
+    let mode = address_mode(&expr).unwrap();
+    assert_eq!(mode.base, Some(&expr_of("(var i64 a)")));
+    assert_eq!(mode.index, Some((&expr_of("(var i64 i)"), 4)));
+    assert_eq!(mode.disp, -3);
+    assert!(mode.index_first);
+
+    // The 32-bit sum wraps around, so it cannot be part of a 64-bit address.
+    let expr = expr_of("(add u64 (var u64 p) (add i32 (var i32 i) (const i32 1)))");
+    let mode = address_mode(&expr).unwrap();
+    assert_eq!(
+        mode.index,
+        Some((&expr_of("(add i32 (var i32 i) (const i32 1))"), 1))
+    );
+
+    let expr = expr_of("(mul i64 (var i64 i) (const i64 8))");
+    let mode = address_mode(&expr).unwrap();
+    assert_eq!(mode.base, None);
+    assert_eq!(mode.index, Some((&expr_of("(var i64 i)"), 8)));
+
+    for source in [
+        "(add i64 (add i64 (var i64 a) (var i64 b)) (var i64 c))",
+        "(add i64 (mul i64 (var i64 a) (const i64 2)) (mul i64 (var i64 b) (const i64 2)))",
+        "(add i64 (var i64 a) (const i64 4294967296))",
+        "(const i64 1)",
+    ] {
+        assert_eq!(address_mode(&expr_of(source)), None, "{}", source);
+    }
+}
+
+#[test]
+fn test_condition() {
+    let expr = expr_of("(slt i32 (const i32 1) (var i32 n))");
+    assert_eq!(
+        condition(&expr),
+        (
+            Flags::Compare(&expr_of("(var i32 n)"), &expr_of("(const i32 1)")),
+            Cc::G
+        )
+    );
+
+    let expr = expr_of("(not i32 (ule i32 (var i32 n) (const i32 1)))");
+    assert_eq!(
+        condition(&expr),
+        (
+            Flags::Compare(&expr_of("(var i32 n)"), &expr_of("(const i32 1)")),
+            Cc::A
+        )
+    );
+
+    let expr = expr_of("(not i32 (band i32 (var i32 n) (const i32 4)))");
+    assert_eq!(
+        condition(&expr),
+        (Flags::Test(&expr_of("(var i32 n)"), Some(4)), Cc::E)
+    );
+
+    let expr = expr_of("(var i32 n)");
+    assert_eq!(condition(&expr), (Flags::Test(&expr, None), Cc::Ne));
+}
+
+#[test]
+fn test_select() {
+    let ir = crate::ir::text::parse(
+        r#"
+        fun f(i32 n) -> i32 {
+            local x size 4 align 4
+            cjump (var i32 n), .L1, .L2
+        .L1:
+            store i32 (addr u64 x), (const i32 1)
+            jump .L3
+        .L2:
+            store i32 (addr u64 x), (var i32 n)
+            jump .L3
+        .L3:
+            cjump (var i32 n), .L4, .L5
+        .L4:
+            store i32 (addr u64 x), (const i32 1)
+            jump .L6
+        .L5:
+            store i32 (addr u64 x), (call i32 g)
+        .L6:
+            return (var i32 x)
+        }
+        "#,
+    )
+    .unwrap();
+    let body = &ir.fun[0].body;
+    let uses = label_uses(body);
+
+    let matched = select(body, &uses).unwrap();
+    assert_eq!(matched.dst, "x");
+    assert_eq!(matched.then_value, &expr_of("(const i32 1)"));
+    assert_eq!(matched.else_value, &expr_of("(var i32 n)"));
+    assert_eq!(matched.len, 7);
+    assert!(matches!(body[matched.len], Stmt::Label(_)));
+
+    assert_eq!(select(&body[7..], &uses), None);
+    assert_eq!(select(&body[1..], &uses), None);
+
+    let ir = crate::ir::text::parse(
+        r#"
+        fun f(i32 n) -> i32 {
+            local x size 4 align 4
+            cjump (call i32 g), .L1, .L2
+        .L1:
+            store i32 (addr u64 x), (var i32 n)
+            jump .L3
+        .L2:
+            store i32 (addr u64 x), (const i32 0)
+        .L3:
+            return (var i32 x)
+        }
+        "#,
+    )
+    .unwrap();
+    let body = &ir.fun[0].body;
+    assert_eq!(select(body, &label_uses(body)), None);
+}
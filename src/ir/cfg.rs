@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use super::{Const, Expr, Label, Stmt};
+
+/// A sequence of statements that is entered only at the top and left only at the bottom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    /// A label can only be the first statement, and a jump, a conditional jump, a switch or a
+    /// return only the last.
+    pub stmts: Vec<Stmt>,
+    /// Indices of the blocks that control can pass to, without duplicates.
+    pub succs: Vec<usize>,
+    /// Indices of the blocks that can pass control to this block, without duplicates.
+    pub preds: Vec<usize>,
+}
+
+impl BasicBlock {
+    pub fn label(&self) -> Option<&Label> {
+        match self.stmts.first() {
+            Some(Stmt::Label(label)) => Some(label),
+            _ => None,
+        }
+    }
+
+    /// Returns whether control continues to the next block after the last statement.
+    pub fn falls_through(&self) -> bool {
+        !self.stmts.last().is_some_and(is_terminator)
+    }
+}
+
+/// Returns whether `stmt` ends a basic block.
+pub fn is_terminator(stmt: &Stmt) -> bool {
+    matches!(
+        stmt,
+        Stmt::Return(_) | Stmt::Jump { .. } | Stmt::CJump { .. } | Stmt::Switch { .. }
+    )
+}
+
+/// Returns the labels that `stmt` can jump to.
+pub fn jump_targets(stmt: &Stmt) -> Vec<&Label> {
+    match stmt {
+        Stmt::Jump { label } => vec![label],
+        Stmt::CJump {
+            then_label,
+            else_label,
+            ..
+        } => vec![then_label, else_label],
+        Stmt::Switch {
+            cases,
+            default_label,
+            ..
+        } => cases
+            .iter()
+            .map(|case| &case.label)
+            .chain([default_label])
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Control-flow graph of a function body. The first block is the entry of the function, and
+/// control falls off the end of the function after the last block if it falls through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+}
+
+impl Cfg {
+    /// Split `body` into basic blocks. Jumps to undefined labels get no edge, and a label that
+    /// is defined more than once refers to its first definition. A conditional jump on a
+    /// constant only gets an edge to the label it takes, so that `while (1)` has no exit.
+    pub fn new(body: &[Stmt]) -> Self {
+        let mut blocks = vec![];
+        let mut stmts = vec![];
+        for stmt in body {
+            if let Stmt::Label(_) = stmt {
+                if !stmts.is_empty() {
+                    blocks.push(std::mem::take(&mut stmts));
+                }
+            }
+            stmts.push(stmt.clone());
+            if is_terminator(stmt) {
+                blocks.push(std::mem::take(&mut stmts));
+            }
+        }
+        if !stmts.is_empty() || blocks.is_empty() {
+            blocks.push(stmts);
+        }
+
+        let mut blocks = blocks
+            .into_iter()
+            .map(|stmts| BasicBlock {
+                stmts,
+                succs: vec![],
+                preds: vec![],
+            })
+            .collect::<Vec<_>>();
+
+        let mut labels = HashMap::new();
+        for (i, block) in blocks.iter().enumerate() {
+            if let Some(label) = block.label() {
+                labels.entry(label.0.clone()).or_insert(i);
+            }
+        }
+
+        for i in 0..blocks.len() {
+            let block = &blocks[i];
+            let targets = match block.stmts.last() {
+                Some(Stmt::CJump {
+                    cond: Expr::Const(_, Const::Int(value)),
+                    then_label,
+                    else_label,
+                }) => {
+                    let label = if *value != 0 { then_label } else { else_label };
+                    labels.get(&label.0).copied().into_iter().collect()
+                }
+                Some(stmt) if is_terminator(stmt) => jump_targets(stmt)
+                    .into_iter()
+                    .filter_map(|label| labels.get(&label.0).copied())
+                    .collect(),
+                _ if i + 1 < blocks.len() => vec![i + 1],
+                _ => vec![],
+            };
+            let mut succs = vec![];
+            for succ in targets {
+                if !succs.contains(&succ) {
+                    succs.push(succ);
+                }
+            }
+            for succ in succs.iter() {
+                blocks[*succ].preds.push(i);
+            }
+            blocks[i].succs = succs;
+        }
+
+        Self { blocks }
+    }
+
+    /// Returns whether each block can be reached from the entry.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            if !reachable[i] {
+                reachable[i] = true;
+                stack.extend(self.blocks[i].succs.iter().copied());
+            }
+        }
+        reachable
+    }
+
+    /// Concatenate the blocks back into a function body.
+    pub fn into_body(self) -> Vec<Stmt> {
+        self.blocks
+            .into_iter()
+            .flat_map(|block| block.stmts)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+fn body_of(source: &str) -> Vec<Stmt> {
+    let mut ir = super::text::parse(source).unwrap();
+    ir.fun.remove(0).body
+}
+
+#[test]
+fn test_cfg() {
+    let body = body_of(
+        r#"
+        fun f(i32 n) -> i32 {
+            store i32 (addr u64 n), (add i32 (var i32 n) (const i32 1))
+        .L1:
+            cjump (var i32 n), .L2, .L3
+        .L2:
+            switch (var i32 n) [1: .L1, 2: .L3, 3: .L1] default .L4
+        .L3:
+            jump .L1
+            eval (var i32 n)
+        .L4:
+        .L5:
+            return (var i32 n)
+        }
+        "#,
+    );
+    let cfg = Cfg::new(&body);
+
+    let labels = cfg
+        .blocks
+        .iter()
+        .map(|block| block.label().map(|label| label.0.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        labels,
+        vec![
+            None,
+            Some(".L1"),
+            Some(".L2"),
+            Some(".L3"),
+            None,
+            Some(".L4"),
+            Some(".L5")
+        ]
+    );
+
+    let succs = cfg
+        .blocks
+        .iter()
+        .map(|block| block.succs.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        succs,
+        vec![
+            vec![1],
+            vec![2, 3],
+            vec![1, 3, 5],
+            vec![1],
+            vec![5],
+            vec![6],
+            vec![]
+        ]
+    );
+    let preds = cfg
+        .blocks
+        .iter()
+        .map(|block| block.preds.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        preds,
+        vec![
+            vec![],
+            vec![0, 2, 3],
+            vec![1],
+            vec![1, 2],
+            vec![],
+            vec![2, 4],
+            vec![5]
+        ]
+    );
+
+    assert_eq!(
+        cfg.reachable(),
+        vec![true, true, true, true, false, true, true]
+    );
+    assert!(cfg.blocks[5].falls_through());
+    assert!(!cfg.blocks[6].falls_through());
+    assert_eq!(cfg.into_body(), body);
+
+    let body = body_of(
+        r#"
+        fun f() {
+        .L1:
+            cjump (const i32 1), .L1, .L2
+        .L2:
+            return
+        }
+        "#,
+    );
+    let cfg = Cfg::new(&body);
+    assert_eq!(cfg.blocks[0].succs, vec![0]);
+    assert_eq!(cfg.reachable(), vec![true, false]);
+
+    let cfg = Cfg::new(&[]);
+    assert_eq!(cfg.blocks.len(), 1);
+    assert!(cfg.blocks[0].falls_through());
+}
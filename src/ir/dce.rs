@@ -0,0 +1,318 @@
+use std::collections::{HashMap, HashSet};
+
+use super::cfg::{jump_targets, Cfg};
+use super::pass::Pass;
+use super::{Const, DefinedFun, Expr, GenError, Label, Stmt, IR};
+
+/// Removes unreachable blocks, unused labels, jumps to the next statement and stores to
+/// temporaries that are never read, and retargets jumps to jumps.
+pub struct DeadCode;
+
+impl Pass for DeadCode {
+    fn name(&self) -> &'static str {
+        "dce"
+    }
+
+    fn run(&mut self, ir: &mut IR) -> Result<bool, GenError> {
+        let mut changed = false;
+        for fun in ir.fun.iter_mut() {
+            let locals = fun.locals.len();
+            loop {
+                let body = fun.body.clone();
+                simplify_jumps(&mut fun.body);
+                thread_jumps(&mut fun.body);
+                fun.body = remove_unreachable(std::mem::take(&mut fun.body));
+                remove_jumps_to_next(&mut fun.body);
+                remove_unused_labels(&mut fun.body);
+                remove_dead_tmps(fun);
+                if fun.body == body {
+                    break;
+                }
+                changed = true;
+            }
+            changed |= fun.locals.len() != locals;
+        }
+        Ok(changed)
+    }
+}
+
+/// Turn conditional jumps whose outcome is known into jumps, so that the label they do not take
+/// can be removed with its block.
+fn simplify_jumps(body: &mut [Stmt]) {
+    for stmt in body.iter_mut() {
+        let label = match stmt {
+            Stmt::CJump {
+                cond: Expr::Const(_, Const::Int(value)),
+                then_label,
+                else_label,
+            } => {
+                if *value != 0 {
+                    then_label.clone()
+                } else {
+                    else_label.clone()
+                }
+            }
+            Stmt::CJump {
+                cond,
+                then_label,
+                else_label,
+            } if then_label == else_label && !has_call(cond) => then_label.clone(),
+            _ => continue,
+        };
+        *stmt = Stmt::Jump { label };
+    }
+}
+
+/// Retarget jumps to a label that is directly followed by a jump, and jumps to a label that
+/// directly follows another label to the first label.
+fn thread_jumps(body: &mut [Stmt]) {
+    let mut forward = HashMap::new();
+    for (i, stmt) in body.iter().enumerate() {
+        if let Stmt::Label(label) = stmt {
+            let next = body[i..]
+                .iter()
+                .find(|stmt| !matches!(stmt, Stmt::Label(_)));
+            let target = match (next, &body[..i]) {
+                (Some(Stmt::Jump { label: target }), _) => target,
+                (_, [.., Stmt::Label(previous)]) => previous,
+                _ => continue,
+            };
+            forward.entry(label.0.clone()).or_insert(target.clone());
+        }
+    }
+
+    let resolve = |label: &mut Label| {
+        let mut seen = HashSet::new();
+        while let Some(target) = forward.get(&label.0) {
+            if !seen.insert(label.0.clone()) {
+                break;
+            }
+            *label = target.clone();
+        }
+    };
+    for stmt in body.iter_mut() {
+        match stmt {
+            Stmt::Jump { label } => resolve(label),
+            Stmt::CJump {
+                then_label,
+                else_label,
+                ..
+            } => {
+                resolve(then_label);
+                resolve(else_label);
+            }
+            Stmt::Switch {
+                cases,
+                default_label,
+                ..
+            } => {
+                cases.iter_mut().for_each(|case| resolve(&mut case.label));
+                resolve(default_label);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn remove_unreachable(body: Vec<Stmt>) -> Vec<Stmt> {
+    let mut cfg = Cfg::new(&body);
+    let reachable = cfg.reachable();
+    let mut i = 0;
+    cfg.blocks.retain(|_| {
+        i += 1;
+        reachable[i - 1]
+    });
+    cfg.into_body()
+}
+
+/// Remove jumps to a label that directly follows them.
+fn remove_jumps_to_next(body: &mut Vec<Stmt>) {
+    let redundant = (0..body.len())
+        .map(|i| match &body[i] {
+            Stmt::Jump { label } => body[i + 1..]
+                .iter()
+                .map_while(|stmt| match stmt {
+                    Stmt::Label(next) => Some(next),
+                    _ => None,
+                })
+                .any(|next| next == label),
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+    let mut i = 0;
+    body.retain(|_| {
+        i += 1;
+        !redundant[i - 1]
+    });
+}
+
+fn remove_unused_labels(body: &mut Vec<Stmt>) {
+    let used = body
+        .iter()
+        .flat_map(jump_targets)
+        .map(|label| label.0.clone())
+        .collect::<HashSet<_>>();
+    body.retain(|stmt| !matches!(stmt, Stmt::Label(label) if !used.contains(&label.0)));
+}
+
+/// Remove stores to temporaries made by `TmpVarGenerator` that are never read, keeping the calls
+/// in the stored value, and then the temporaries that are no longer used.
+fn remove_dead_tmps(fun: &mut DefinedFun) {
+    let is_tmp =
+        |name: &str| name.starts_with("__tmp") && fun.locals.iter().any(|local| local.name == name);
+
+    let mut used = HashSet::new();
+    for stmt in fun.body.iter() {
+        let exprs = match stmt {
+            Stmt::Assign(_, Expr::Addr(_, name), src) if is_tmp(name) => vec![src],
+            stmt => stmt.exprs(),
+        };
+        for expr in exprs {
+            expr.walk(&mut |expr| {
+                if let Expr::Var(_, name) | Expr::Addr(_, name) = expr {
+                    used.insert(name.clone());
+                }
+            });
+        }
+    }
+
+    let mut body = vec![];
+    for stmt in std::mem::take(&mut fun.body) {
+        match stmt {
+            Stmt::Assign(_, Expr::Addr(_, name), src) if is_tmp(&name) && !used.contains(&name) => {
+                if has_call(&src) {
+                    body.push(Stmt::ExprStmt(src));
+                }
+            }
+            stmt => body.push(stmt),
+        }
+    }
+    fun.body = body;
+
+    let is_tmp = |name: &str| name.starts_with("__tmp");
+    fun.locals
+        .retain(|local| !is_tmp(&local.name) || used.contains(&local.name));
+}
+
+fn has_call(expr: &Expr) -> bool {
+    let mut found = false;
+    expr.walk(&mut |expr| found |= matches!(expr, Expr::Call(..)));
+    found
+}
+
+#[cfg(test)]
+fn dce_text(source: &str) -> String {
+    let mut ir = super::text::parse(source).unwrap();
+    DeadCode.run(&mut ir).unwrap();
+    ir.to_string()
+}
+
+#[test]
+fn test_dce() {
+    let ir = dce_text(
+        r#"
+        fun f(i32 n) -> i32 {
+            local __tmp0 size 4 align 4
+            local __tmp1 size 4 align 4
+            local __tmp2 size 4 align 4
+            local x size 4 align 4
+            store i32 (addr u64 __tmp0), (add i32 (var i32 n) (const i32 1))
+            store i32 (addr u64 __tmp1), (call i32 g)
+            store i32 (addr u64 __tmp2), (const i32 2)
+            store i32 (addr u64 x), (const i32 3)
+            cjump (var i32 n), .L1, .L2
+        .L1:
+            jump .L3
+        .L2:
+        .L4:
+            jump .L5
+            eval (var i32 n)
+        .L3:
+            jump .L4
+        .L5:
+            cjump (const i32 0), .L6, .L7
+        .L6:
+            return (var i32 n)
+        .L7:
+            cjump (call i32 g), .L8, .L8
+        .L8:
+            return (var i32 __tmp2)
+        }
+
+        fun loop() {
+        top:
+            jump top
+        }
+        "#,
+    );
+    assert_eq!(
+        ir,
+        "fun f(i32 n) -> i32 {\n    \
+         local __tmp2 size 4 align 4\n    \
+         local x size 4 align 4\n    \
+         eval (call i32 g)\n    \
+         store i32 (addr u64 __tmp2), (const i32 2)\n    \
+         store i32 (addr u64 x), (const i32 3)\n    \
+         cjump (call i32 g), .L8, .L8\n\
+         .L8:\n    \
+         return (var i32 __tmp2)\n\
+         }\n\
+         \n\
+         fun loop() {\n\
+         top:\n    \
+         jump top\n\
+         }\n"
+    );
+}
+
+#[test]
+fn test_dce_gen_ir() {
+    use super::interp::Interpreter;
+    use super::pass::{OptLevel, PassManager};
+    use super::verify::verify;
+
+    let source = r#"
+        import stdio;
+
+        int sign(int n) {
+            if (n < 0) {
+                return -1;
+            } else {
+                return 1;
+            }
+            puts("unreachable");
+        }
+
+        int main(void) {
+            int i = 0;
+            int total = 0;
+            while (i > 10) {
+            }
+            while (i < 5) {
+                total += i++;
+            }
+            if (total) {
+            }
+            printf("%d %d\n", sign(-total), total > 3 ? 1 : 0);
+            return total;
+        }
+    "#;
+    let ir = crate::gen::ir_of_source("<source>", source).unwrap();
+    let mut optimized = crate::gen::ir_of_source("<source>", source).unwrap();
+    PassManager::with_level(OptLevel::O1)
+        .run(&mut optimized)
+        .unwrap();
+
+    assert!(verify(&optimized).is_empty(), "{}", optimized);
+    let statements = |ir: &IR| ir.fun.iter().map(|fun| fun.body.len()).sum::<usize>();
+    assert!(statements(&optimized) < statements(&ir), "{}", optimized);
+    assert!(!optimized.to_string().contains("unreachable"));
+
+    let mut expected = Interpreter::new(&ir).unwrap();
+    let mut actual = Interpreter::new(&optimized).unwrap();
+    assert_eq!(
+        expected.call("main", &[]).unwrap(),
+        actual.call("main", &[]).unwrap()
+    );
+    assert_eq!(expected.output, actual.output);
+}
@@ -0,0 +1,296 @@
+use std::collections::{HashMap, HashSet};
+
+use super::pass::Pass;
+use super::unit::int_const;
+use super::{Const, DefinedFun, Expr, GenError, Stmt, Type, IR};
+
+/// Folds operators over constants, propagates constants through locals that are assigned once,
+/// and turns conditional jumps and switches on constants into jumps.
+///
+/// Constants are folded with the same 64-bit arithmetic as the generated code, so the result
+/// wraps around and shift counts are masked. A division by zero is left to fail at run time.
+pub struct ConstFold;
+
+impl Pass for ConstFold {
+    fn name(&self) -> &'static str {
+        "const-fold"
+    }
+
+    fn run(&mut self, ir: &mut IR) -> Result<bool, GenError> {
+        let mut changed = false;
+        for fun in ir.fun.iter_mut() {
+            changed |= fold_fun(fun);
+        }
+        Ok(changed)
+    }
+}
+
+/// Returns whether anything has been folded.
+fn fold_fun(fun: &mut DefinedFun) -> bool {
+    let mut changed = false;
+    loop {
+        for stmt in fun.body.iter_mut() {
+            for expr in stmt.exprs_mut() {
+                changed |= fold_expr(expr);
+            }
+            changed |= fold_jump(stmt);
+        }
+        let constants = single_assignments(fun);
+        if !propagate(&mut fun.body, &constants) {
+            return changed;
+        }
+        changed = true;
+    }
+}
+
+/// Returns the value of an operator whose operands are constants.
+fn eval(expr: &Expr) -> Option<i64> {
+    match expr {
+        Expr::Uni(_, op, value) => match **value {
+            Expr::Const(_, Const::Int(value)) => op.eval_uni(value).ok(),
+            _ => None,
+        },
+        Expr::Bin(_, op, lhs, rhs) => match (&**lhs, &**rhs) {
+            (Expr::Const(_, Const::Int(lhs)), Expr::Const(_, Const::Int(rhs))) => {
+                op.eval_bin(*lhs, *rhs).ok()
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn fold_expr(expr: &mut Expr) -> bool {
+    let mut changed = false;
+    expr.walk_mut(&mut |expr| {
+        if let Some(value) = eval(expr) {
+            *expr = int_const(expr._type(), value);
+            changed = true;
+        }
+    });
+    changed
+}
+
+fn fold_jump(stmt: &mut Stmt) -> bool {
+    let label = match stmt {
+        Stmt::CJump {
+            cond: Expr::Const(_, Const::Int(value)),
+            then_label,
+            else_label,
+        } => {
+            if *value != 0 {
+                then_label.clone()
+            } else {
+                else_label.clone()
+            }
+        }
+        Stmt::Switch {
+            cond: Expr::Const(_, Const::Int(value)),
+            cases,
+            default_label,
+        } => cases
+            .iter()
+            .find(|case| case.value == *value)
+            .map_or(&*default_label, |case| &case.label)
+            .clone(),
+        _ => return false,
+    };
+    *stmt = Stmt::Jump { label };
+    true
+}
+
+/// Returns the locals that are only ever stored a constant once, with the type of the store.
+/// A local whose address is used anywhere else may be changed through it.
+fn single_assignments(fun: &DefinedFun) -> HashMap<String, (Type, i64)> {
+    let params = fun
+        .params
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<HashSet<_>>();
+    let mut addrs = HashMap::new();
+    for stmt in fun.body.iter() {
+        for expr in stmt.exprs() {
+            expr.walk(&mut |expr| {
+                if let Expr::Addr(_, name) = expr {
+                    *addrs.entry(name.as_str()).or_insert(0) += 1;
+                }
+            });
+        }
+    }
+
+    let mut constants = HashMap::new();
+    for stmt in fun.body.iter() {
+        if let Stmt::Assign(_type, Expr::Addr(_, name), Expr::Const(_, Const::Int(value))) = stmt {
+            if addrs[name.as_str()] == 1
+                && !params.contains(name.as_str())
+                && fun.locals.iter().any(|local| &local.name == name)
+            {
+                constants.insert(name.clone(), (*_type, *value));
+            }
+        }
+    }
+    constants
+}
+
+/// Replace the reads of `constants` in `body` and returns whether anything has changed. A read
+/// that is wider than the store is left alone.
+fn propagate(body: &mut [Stmt], constants: &HashMap<String, (Type, i64)>) -> bool {
+    let mut changed = false;
+    for stmt in body.iter_mut() {
+        for expr in stmt.exprs_mut() {
+            expr.walk_mut(&mut |expr| {
+                if let Expr::Var(_type, name) = expr {
+                    if let Some((store, value)) = constants.get(name) {
+                        if _type.size() <= store.size() {
+                            *expr = int_const(*_type, store.normalize(*value));
+                            changed = true;
+                        }
+                    }
+                }
+            });
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+fn fold_text(source: &str) -> String {
+    let mut ir = super::text::parse(source).unwrap();
+    ConstFold.run(&mut ir).unwrap();
+    ir.to_string()
+}
+
+#[test]
+fn test_fold() {
+    let ir = fold_text(
+        r#"
+        fun f(i32 n) -> i64 {
+            eval (add i32 (const i32 2147483647) (const i32 1))
+            eval (sdiv i32 (const i32 -7) (const i32 2))
+            eval (udiv u32 (const u32 4294967289) (const u32 2))
+            eval (smod i32 (const i32 -7) (const i32 2))
+            eval (shl i8 (const i8 1) (const i8 7))
+            eval (shr u64 (const u64 -1) (const u64 60))
+            eval (sar i64 (const i64 -16) (const i64 2))
+            eval (ult i32 (const i32 -1) (const i32 1))
+            eval (slt i32 (const i32 -1) (const i32 1))
+            eval (ucast u64 (neg i32 (const i32 1)))
+            eval (not i32 (bnot i32 (const i32 -1)))
+            eval (sdiv i32 (var i32 n) (const i32 0))
+            eval (sdiv i32 (const i32 1) (const i32 0))
+            return (mul i64 (add i64 (const i64 1) (const i64 2)) (var i64 n))
+        }
+        "#,
+    );
+    assert_eq!(
+        ir,
+        "fun f(i32 n) -> i64 {\n    \
+         eval (const i32 -2147483648)\n    \
+         eval (const i32 -3)\n    \
+         eval (const u32 2147483644)\n    \
+         eval (const i32 -1)\n    \
+         eval (const i8 -128)\n    \
+         eval (const u64 15)\n    \
+         eval (const i64 -4)\n    \
+         eval (const i32 0)\n    \
+         eval (const i32 1)\n    \
+         eval (const u64 -1)\n    \
+         eval (const i32 1)\n    \
+         eval (sdiv i32 (var i32 n) (const i32 0))\n    \
+         eval (sdiv i32 (const i32 1) (const i32 0))\n    \
+         return (mul i64 (const i64 3) (var i64 n))\n\
+         }\n"
+    );
+}
+
+#[test]
+fn test_propagate() {
+    let ir = fold_text(
+        r#"
+        fun f(i32 p) -> i32 {
+            local a size 4 align 4
+            local b size 4 align 4
+            local c size 4 align 4
+            local d size 8 align 8
+            store i32 (addr u64 a), (const i32 300)
+            store i32 (addr u64 b), (add i32 (var i32 a) (const i32 1))
+            store i32 (addr u64 c), (const i32 1)
+            store i32 (addr u64 c), (const i32 2)
+            store i32 (addr u64 d), (const i32 -1)
+            store i32 (addr u64 p), (const i32 5)
+            cjump (ugt i32 (var i32 b) (const i32 300)), .L1, .L2
+        .L1:
+            switch (var u8 a) [44: .L2] default .L3
+        .L2:
+            return (add i32 (var i32 c) (var i32 p))
+        .L3:
+            return (add i32 (var i32 d) (var i64 d))
+        }
+        "#,
+    );
+    assert_eq!(
+        ir,
+        "fun f(i32 p) -> i32 {\n    \
+         local a size 4 align 4\n    \
+         local b size 4 align 4\n    \
+         local c size 4 align 4\n    \
+         local d size 8 align 8\n    \
+         store i32 (addr u64 a), (const i32 300)\n    \
+         store i32 (addr u64 b), (const i32 301)\n    \
+         store i32 (addr u64 c), (const i32 1)\n    \
+         store i32 (addr u64 c), (const i32 2)\n    \
+         store i32 (addr u64 d), (const i32 -1)\n    \
+         store i32 (addr u64 p), (const i32 5)\n    \
+         jump .L1\n\
+         .L1:\n    \
+         jump .L2\n\
+         .L2:\n    \
+         return (add i32 (var i32 c) (var i32 p))\n\
+         .L3:\n    \
+         return (add i32 (const i32 -1) (var i64 d))\n\
+         }\n"
+    );
+}
+
+#[test]
+fn test_fold_gen_ir() {
+    use super::interp::Interpreter;
+    use super::pass::{OptLevel, PassManager};
+
+    let source = r#"
+        import stdio;
+
+        int main(void) {
+            int i;
+            int count = 15;
+            unsigned int u = -1;
+            if (count % 5 == 0) {
+                printf("%d %u %d\n", 1 + 2, u / 2, (char)200 >> 1);
+            }
+            for (i = 0; i < count; i++) {
+                if (i % 5 == 0) {
+                    putchar('0' + i / 5);
+                }
+            }
+            return count * 2;
+        }
+    "#;
+    let ir = crate::gen::ir_of_source("<source>", source).unwrap();
+    let mut optimized = crate::gen::ir_of_source("<source>", source).unwrap();
+    PassManager::with_level(OptLevel::O2)
+        .run(&mut optimized)
+        .unwrap();
+
+    let printed = optimized.to_string();
+    assert!(printed.contains("(const i32 3)"), "{}", printed);
+    assert!(!printed.contains("(var i32 count)"), "{}", printed);
+
+    let mut expected = Interpreter::new(&ir).unwrap();
+    let mut actual = Interpreter::new(&optimized).unwrap();
+    assert_eq!(
+        expected.call("main", &[]).unwrap(),
+        actual.call("main", &[]).unwrap()
+    );
+    assert_eq!(expected.output, actual.output);
+    assert_eq!(actual.output, b"3 2147483647 -28\n012");
+}
@@ -1,23 +1,58 @@
 use crate::ir::DefinedFun;
 use crate::ir::GenError;
 use crate::node::def::def_fun::DefFun;
+use crate::node::param::ParamsNode;
+use crate::resolve::type_check::{expand, is_void};
 
+use super::layout::layout_of;
 use super::unit::transform_stmt;
-use super::IRInfo;
+use super::{IRInfo, Local, Type};
 
 pub fn gen_def_fun(fun: &DefFun, info: &mut IRInfo) -> Result<DefinedFun, GenError> {
     let mut stmts = vec![];
 
-    info.push_scope(fun.scope.as_ref().unwrap().clone());
+    let scope = fun.scope.as_ref().unwrap();
+    info.push_scope(scope.clone());
+    // Parameters keep their names, which locals declared in the body must not take.
+    info.blocks.push(match &fun.params {
+        ParamsNode::Some { fixed, .. } => fixed
+            .iter()
+            .map(|param| (param.name.clone(), param.name.clone()))
+            .collect(),
+        ParamsNode::Void => Default::default(),
+    });
 
     for stmt in fun.block.iter() {
         stmts.extend(transform_stmt(stmt, info)?);
     }
 
+    info.pop_block();
+    info.pop_scope();
+
+    let locals = std::mem::take(&mut info.locals)
+        .into_iter()
+        .map(|(name, _type)| {
+            let layout = layout_of(&_type, scope)?;
+            Ok(Local { name, layout })
+        })
+        .collect::<Result<_, GenError>>()?;
+
+    let params = match &fun.params {
+        ParamsNode::Some { fixed, .. } => fixed
+            .iter()
+            .map(|param| Ok((param.name.clone(), Type::of(&expand(&param._type, scope)?))))
+            .collect::<Result<_, GenError>>()?,
+        ParamsNode::Void => vec![],
+    };
+    let ret = expand(&fun._type, scope)?;
+    let ret = (!is_void(&ret)).then(|| Type::of(&ret));
+
     Ok(DefinedFun {
         name: fun.name.clone(),
-        _type: (fun._type.clone(), fun.params.clone()),
+        params,
+        ret,
         is_private: fun.is_static,
+        locals,
         body: stmts,
     })
 }
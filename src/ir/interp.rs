@@ -0,0 +1,786 @@
+//! Interpreter for the IR.
+//!
+//! Runs an `IR` without generating native code, so that the result of `gen::compile` can be
+//! checked against it. Memory is byte-addressed like on the target: globals, string literals
+//! and heap blocks live in a data segment, and each call allocates the slots of its parameters
+//! and locals on a stack segment. Functions that are not defined in the IR are looked up among
+//! the host functions declared in `import/stdio.hb` and `import/stdlib.hb`.
+//!
+//! Operands are evaluated in the same order as the native code generator: right to left, and
+//! the value of an assignment before its address.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+
+use super::layout::{align_to, Layout};
+use super::{Const, DefinedFun, Expr, GenError, JumpEntry, Stmt, Type, IR};
+
+/// Addresses below this are never mapped, so that null pointer accesses are caught.
+const DATA_BASE: i64 = 0x1000;
+const STACK_BASE: i64 = 0x7fff_0000_0000;
+const STACK_SIZE: usize = 1 << 20;
+/// Calls are interpreted recursively on a thread with a stack of `HOST_STACK_SIZE` bytes, which
+/// is enough for `MAX_DEPTH` nested calls.
+const MAX_DEPTH: usize = 10_000;
+const HOST_STACK_SIZE: usize = 1 << 28;
+/// Functions get addresses in a range without memory, so that they can be compared but not
+/// accessed.
+const FUNCTION_BASE: i64 = 0x4000_0000_0000;
+
+fn error<T>(message: String) -> Result<T, GenError> {
+    Err(GenError {
+        message,
+        span: None,
+    })
+}
+
+/// Reasons to stop running a function early.
+enum Trap {
+    Exit(i64),
+    Error(GenError),
+}
+
+impl From<GenError> for Trap {
+    fn from(e: GenError) -> Self {
+        Trap::Error(e)
+    }
+}
+
+/// Statement index of each label in a function body.
+type Labels<'a> = Arc<HashMap<&'a str, usize>>;
+
+struct Segment {
+    base: i64,
+    bytes: Vec<u8>,
+}
+
+impl Segment {
+    fn get(&mut self, addr: i64, size: usize) -> Option<&mut [u8]> {
+        let start = usize::try_from(addr.checked_sub(self.base)?).ok()?;
+        self.bytes.get_mut(start..start.checked_add(size)?)
+    }
+}
+
+pub struct Interpreter<'a> {
+    functions: HashMap<&'a str, (&'a DefinedFun, Labels<'a>)>,
+    symbols: HashMap<&'a str, i64>,
+    strings: HashMap<Vec<u8>, i64>,
+    data: Segment,
+    stack: Segment,
+    sp: i64,
+    depth: usize,
+    /// Everything written to the standard output by host functions.
+    pub output: Vec<u8>,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Load `ir`, allocating and initializing its global variables.
+    pub fn new(ir: &'a IR) -> Result<Self, GenError> {
+        let mut interp = Self {
+            functions: HashMap::new(),
+            symbols: HashMap::new(),
+            strings: HashMap::new(),
+            data: Segment {
+                base: DATA_BASE,
+                bytes: vec![],
+            },
+            stack: Segment {
+                base: STACK_BASE,
+                bytes: vec![0; STACK_SIZE],
+            },
+            sp: STACK_BASE + STACK_SIZE as i64,
+            depth: 0,
+            output: vec![],
+        };
+
+        for (i, fun) in ir.fun.iter().enumerate() {
+            let labels = fun
+                .body
+                .iter()
+                .enumerate()
+                .filter_map(|(pc, stmt)| match stmt {
+                    Stmt::Label(label) => Some((label.0.as_str(), pc)),
+                    _ => None,
+                })
+                .collect();
+            interp.functions.insert(&fun.name, (fun, Arc::new(labels)));
+            interp
+                .symbols
+                .insert(&fun.name, FUNCTION_BASE + 16 * i as i64);
+        }
+        for var in ir.var.iter() {
+            let addr = interp.allocate(var.layout);
+            interp.symbols.insert(&var.name, addr);
+            match &var.init {
+                Some(Const::Int(value)) => {
+                    let size = var.layout.size as usize;
+                    interp.memory(addr, size)?[..size]
+                        .copy_from_slice(&value.to_le_bytes()[..size]);
+                }
+                Some(Const::Str(s)) => {
+                    let literal = interp.string_literal(s);
+                    interp.store(addr, Type::U64, literal)?;
+                }
+                None => {}
+            }
+        }
+        Ok(interp)
+    }
+
+    /// Call the function `name` with `args` and return its result. If the program calls `exit`,
+    /// the exit status is returned instead.
+    pub fn call(&mut self, name: &str, args: &[i64]) -> Result<i64, GenError> {
+        let result = thread::scope(|scope| {
+            let handle = thread::Builder::new()
+                .stack_size(HOST_STACK_SIZE)
+                .spawn_scoped(scope, || self.call_(name, args))
+                .expect("failed to spawn the interpreter thread");
+            handle
+                .join()
+                .unwrap_or_else(|e| std::panic::resume_unwind(e))
+        });
+        match result {
+            Ok(value) => Ok(value),
+            Err(Trap::Exit(status)) => Ok(status),
+            Err(Trap::Error(e)) => Err(e),
+        }
+    }
+
+    /// Allocate zero-filled memory in the data segment.
+    fn allocate(&mut self, layout: Layout) -> i64 {
+        let offset = align_to(self.data.bytes.len() as i64, layout.align.max(1));
+        self.data.bytes.resize((offset + layout.size) as usize, 0);
+        DATA_BASE + offset
+    }
+
+    fn string_literal(&mut self, s: &[u8]) -> i64 {
+        if let Some(addr) = self.strings.get(s) {
+            return *addr;
+        }
+        let addr = self.allocate(Layout {
+            size: s.len() as i64 + 1,
+            align: 1,
+        });
+        self.data.bytes[(addr - DATA_BASE) as usize..][..s.len()].copy_from_slice(s);
+        self.strings.insert(s.to_vec(), addr);
+        addr
+    }
+
+    fn memory(&mut self, addr: i64, size: usize) -> Result<&mut [u8], GenError> {
+        if self.stack.get(addr, size).is_some() {
+            return Ok(self.stack.get(addr, size).unwrap());
+        }
+        match self.data.get(addr, size) {
+            Some(bytes) => Ok(bytes),
+            None => error(format!(
+                "invalid memory access of {} bytes at {:#x}",
+                size, addr
+            )),
+        }
+    }
+
+    fn load(&mut self, addr: i64, _type: Type) -> Result<i64, GenError> {
+        let mut bytes = [0; 8];
+        let size = _type.size() as usize;
+        bytes[..size].copy_from_slice(self.memory(addr, size)?);
+        Ok(_type.normalize(i64::from_le_bytes(bytes)))
+    }
+
+    fn store(&mut self, addr: i64, _type: Type, value: i64) -> Result<(), GenError> {
+        let size = _type.size() as usize;
+        self.memory(addr, size)?
+            .copy_from_slice(&value.to_le_bytes()[..size]);
+        Ok(())
+    }
+
+    /// Read the NUL-terminated string at `addr`.
+    fn c_string(&mut self, addr: i64) -> Result<Vec<u8>, GenError> {
+        let mut s = vec![];
+        loop {
+            match self.memory(addr + s.len() as i64, 1)?[0] {
+                0 => return Ok(s),
+                c => s.push(c),
+            }
+        }
+    }
+
+    /// Allocate a slot on the stack.
+    fn push(&mut self, layout: Layout) -> Result<i64, GenError> {
+        let sp = (self.sp - layout.size) / layout.align.max(1) * layout.align.max(1);
+        if sp < STACK_BASE {
+            return error("stack overflow".into());
+        }
+        self.sp = sp;
+        Ok(sp)
+    }
+
+    fn call_(&mut self, name: &str, args: &[i64]) -> Result<i64, Trap> {
+        let (fun, labels) = match self.functions.get(name) {
+            Some((fun, labels)) => (*fun, labels.clone()),
+            None => return self.host(name, args),
+        };
+        if args.len() != fun.params.len() {
+            error(format!(
+                "{} takes {} arguments but {} were given",
+                name,
+                fun.params.len(),
+                args.len()
+            ))?;
+        }
+
+        if self.depth == MAX_DEPTH {
+            error("stack overflow".into())?;
+        }
+
+        let sp = self.sp;
+        self.depth += 1;
+        let result = self.run_function(fun, &labels, args);
+        self.depth -= 1;
+        self.sp = sp;
+        result
+    }
+
+    fn run_function(
+        &mut self,
+        fun: &'a DefinedFun,
+        labels: &HashMap<&'a str, usize>,
+        args: &[i64],
+    ) -> Result<i64, Trap> {
+        let mut frame = HashMap::new();
+        for ((name, _type), value) in fun.params.iter().zip(args) {
+            let size = _type.size() as i64;
+            let addr = self.push(Layout { size, align: size })?;
+            self.store(addr, *_type, *value)?;
+            frame.insert(name.as_str(), addr);
+        }
+        for local in fun.locals.iter() {
+            let addr = self.push(local.layout)?;
+            frame.insert(local.name.as_str(), addr);
+        }
+
+        let mut pc = 0;
+        while let Some(stmt) = fun.body.get(pc) {
+            pc += 1;
+            let target = match stmt {
+                Stmt::Return(expr) => {
+                    return match expr {
+                        Some(expr) => self.expr(expr, &frame),
+                        None => Ok(0),
+                    };
+                }
+                Stmt::Jump { label } => label,
+                Stmt::CJump {
+                    cond,
+                    then_label,
+                    else_label,
+                } => {
+                    if self.expr(cond, &frame)? != 0 {
+                        then_label
+                    } else {
+                        else_label
+                    }
+                }
+                Stmt::Switch {
+                    cond,
+                    cases,
+                    default_label,
+                } => {
+                    let value = self.expr(cond, &frame)?;
+                    cases
+                        .iter()
+                        .find(|case| case.value == value)
+                        .map_or(default_label, |JumpEntry { label, .. }| label)
+                }
+                Stmt::Label(_) => continue,
+                Stmt::ExprStmt(expr) => {
+                    self.expr(expr, &frame)?;
+                    continue;
+                }
+                Stmt::Assign(_type, dst, src) => {
+                    let value = self.expr(src, &frame)?;
+                    let addr = self.expr(dst, &frame)?;
+                    self.store(addr, *_type, value)?;
+                    continue;
+                }
+                Stmt::Copy { dst, src, size } => {
+                    let src = self.expr(src, &frame)?;
+                    let dst = self.expr(dst, &frame)?;
+                    let bytes = self.memory(src, *size as usize)?.to_vec();
+                    self.memory(dst, *size as usize)?.copy_from_slice(&bytes);
+                    continue;
+                }
+            };
+            pc = match labels.get(target.0.as_str()) {
+                Some(pc) => *pc,
+                None => error(format!("label {} is not defined in {}", target.0, fun.name))?,
+            };
+        }
+        Ok(0)
+    }
+
+    fn variable(&self, name: &str, frame: &HashMap<&str, i64>) -> Result<i64, GenError> {
+        match frame.get(name).or_else(|| self.symbols.get(name)) {
+            Some(addr) => Ok(*addr),
+            None => error(format!("{} is not defined", name)),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, frame: &HashMap<&str, i64>) -> Result<i64, Trap> {
+        let value = match expr {
+            Expr::Const(_, Const::Int(value)) => *value,
+            Expr::Const(_, Const::Str(s)) => self.string_literal(s),
+            Expr::Var(_type, name) => {
+                let addr = self.variable(name, frame)?;
+                self.load(addr, *_type)?
+            }
+            Expr::Addr(_, name) => self.variable(name, frame)?,
+            Expr::Mem(_type, expr) => {
+                let addr = self.expr(expr, frame)?;
+                self.load(addr, *_type)?
+            }
+            Expr::Uni(_type, op, expr) => {
+                let value = self.expr(expr, frame)?;
+                _type.normalize(op.eval_uni(value)?)
+            }
+            Expr::Bin(_type, op, lhs, rhs) => {
+                let rhs = self.expr(rhs, frame)?;
+                let lhs = self.expr(lhs, frame)?;
+                _type.normalize(op.eval_bin(lhs, rhs)?)
+            }
+            Expr::Call(_type, name, args, _) => {
+                let mut values = vec![0; args.len()];
+                for (value, arg) in values.iter_mut().zip(args).rev() {
+                    *value = self.expr(arg, frame)?;
+                }
+                _type.normalize(self.call_(name, &values)?)
+            }
+        };
+        Ok(value)
+    }
+
+    fn host(&mut self, name: &str, args: &[i64]) -> Result<i64, Trap> {
+        let arg = |i: usize| match args.get(i) {
+            Some(value) => Ok(*value),
+            None => error(format!("too few arguments to {}", name)),
+        };
+        let value = match name {
+            "putchar" => {
+                self.output.push(arg(0)? as u8);
+                arg(0)? as u8 as i64
+            }
+            "puts" => {
+                let s = self.c_string(arg(0)?)?;
+                self.output.extend(&s);
+                self.output.push(b'\n');
+                s.len() as i64 + 1
+            }
+            "printf" => {
+                let s = self.format(arg(0)?, &args[1..])?;
+                self.output.extend(&s);
+                s.len() as i64
+            }
+            "sprintf" => {
+                let s = self.format(arg(1)?, &args[2..])?;
+                let buf = self.memory(arg(0)?, s.len() + 1)?;
+                buf[..s.len()].copy_from_slice(&s);
+                buf[s.len()] = 0;
+                s.len() as i64
+            }
+            "exit" => return Err(Trap::Exit(arg(0)? as i32 as i64)),
+            "malloc" => self.allocate(Layout {
+                size: arg(0)?,
+                align: 16,
+            }),
+            "free" => 0,
+            "atoi" => {
+                let s = self.c_string(arg(0)?)?;
+                let s = String::from_utf8_lossy(&s);
+                let s = s.trim_start();
+                let end = s
+                    .char_indices()
+                    .find(|&(i, c)| !(c.is_ascii_digit() || i == 0 && (c == '-' || c == '+')))
+                    .map_or(s.len(), |(i, _)| i);
+                s[..end].parse::<i64>().unwrap_or(0) as i32 as i64
+            }
+            _ => error(format!("{} is not defined", name))?,
+        };
+        Ok(value)
+    }
+
+    /// Format `args` according to the `printf` format string at `fmt`.
+    ///
+    /// Supports the flags `-+ 0#`, field width and precision (also given as `*`), the length
+    /// modifiers `hh h l ll z j t`, and the conversions `d i u x X o c s p %`.
+    fn format(&mut self, fmt: i64, args: &[i64]) -> Result<Vec<u8>, GenError> {
+        let fmt = self.c_string(fmt)?;
+        let mut args = args.iter().copied();
+        let mut next = |fmt: &[u8]| match args.next() {
+            Some(value) => Ok(value),
+            None => error(format!(
+                "too few arguments for \"{}\"",
+                String::from_utf8_lossy(fmt)
+            )),
+        };
+
+        let mut out = vec![];
+        let mut chars = fmt.iter().copied().peekable();
+        while let Some(c) = chars.next() {
+            if c != b'%' {
+                out.push(c);
+                continue;
+            }
+
+            let mut flags = vec![];
+            while let Some(&c) = chars.peek().filter(|c| b"-+ 0#".contains(c)) {
+                flags.push(c);
+                chars.next();
+            }
+            let mut number = |chars: &mut std::iter::Peekable<_>| -> Result<Option<i64>, GenError> {
+                if chars.peek() == Some(&b'*') {
+                    chars.next();
+                    return Ok(Some(next(&fmt)? as i32 as i64));
+                }
+                let mut n = None;
+                while let Some(d) = chars.peek().filter(|c: &&u8| c.is_ascii_digit()) {
+                    n = Some(n.unwrap_or(0) * 10 + (d - b'0') as i64);
+                    chars.next();
+                }
+                Ok(n)
+            };
+            let mut width = number(&mut chars)?;
+            if width.is_some_and(|w| w < 0) {
+                flags.push(b'-');
+                width = width.map(|w| -w);
+            }
+            let precision = if chars.peek() == Some(&b'.') {
+                chars.next();
+                Some(number(&mut chars)?.unwrap_or(0)).filter(|p| *p >= 0)
+            } else {
+                None
+            };
+            let mut length = vec![];
+            while let Some(&c) = chars.peek().filter(|c| b"hlzjt".contains(c)) {
+                length.push(c);
+                chars.next();
+            }
+            let size = match length.as_slice() {
+                b"hh" => Type::I8,
+                b"h" => Type::I16,
+                b"" => Type::I32,
+                _ => Type::I64,
+            };
+            let unsigned = |value: i64| match size {
+                Type::I8 => value as u8 as u64,
+                Type::I16 => value as u16 as u64,
+                Type::I32 => value as u32 as u64,
+                _ => value as u64,
+            };
+
+            let conversion = match chars.next() {
+                Some(c) => c,
+                None => break,
+            };
+            let (prefix, body) = match conversion {
+                b'%' => {
+                    out.push(b'%');
+                    continue;
+                }
+                b'd' | b'i' => {
+                    let value = size.normalize(next(&fmt)?);
+                    let sign = if value < 0 {
+                        "-"
+                    } else if flags.contains(&b'+') {
+                        "+"
+                    } else if flags.contains(&b' ') {
+                        " "
+                    } else {
+                        ""
+                    };
+                    (
+                        sign.to_string(),
+                        digits(value.unsigned_abs().to_string(), precision),
+                    )
+                }
+                b'u' => (
+                    String::new(),
+                    digits(unsigned(next(&fmt)?).to_string(), precision),
+                ),
+                b'x' | b'X' | b'o' => {
+                    let value = unsigned(next(&fmt)?);
+                    let (body, prefix) = match conversion {
+                        b'x' => (format!("{:x}", value), "0x"),
+                        b'X' => (format!("{:X}", value), "0X"),
+                        _ => (format!("{:o}", value), "0"),
+                    };
+                    let body = digits(body, precision);
+                    let prefix = if flags.contains(&b'#') && value != 0 && !body.starts_with('0') {
+                        prefix
+                    } else {
+                        ""
+                    };
+                    (prefix.to_string(), body)
+                }
+                b'p' => match next(&fmt)? {
+                    0 => (String::new(), "(nil)".to_string()),
+                    value => ("0x".to_string(), format!("{:x}", value)),
+                },
+                b'c' => {
+                    let c = next(&fmt)? as u8;
+                    pad(&mut out, &[], &[c], width, &flags, false);
+                    continue;
+                }
+                b's' => {
+                    let mut s = self.c_string(next(&fmt)?)?;
+                    if let Some(precision) = precision {
+                        s.truncate(precision as usize);
+                    }
+                    pad(&mut out, &[], &s, width, &flags, false);
+                    continue;
+                }
+                c => {
+                    return error(format!(
+                        "unsupported conversion %{} in \"{}\"",
+                        c as char,
+                        String::from_utf8_lossy(&fmt)
+                    ))
+                }
+            };
+            let zero = flags.contains(&b'0') && precision.is_none();
+            pad(
+                &mut out,
+                prefix.as_bytes(),
+                body.as_bytes(),
+                width,
+                &flags,
+                zero,
+            );
+        }
+        Ok(out)
+    }
+}
+
+/// Pad `digits` with zeros to at least `precision` digits.
+fn digits(digits: String, precision: Option<i64>) -> String {
+    match precision {
+        Some(0) if digits == "0" => String::new(),
+        Some(precision) => format!("{:0>1$}", digits, precision as usize),
+        None => digits,
+    }
+}
+
+/// Write `prefix` and `body` to `out`, padded to `width`. Zeros are inserted between the prefix
+/// and the body if `zero` is set and the field is not left-justified.
+fn pad(
+    out: &mut Vec<u8>,
+    prefix: &[u8],
+    body: &[u8],
+    width: Option<i64>,
+    flags: &[u8],
+    zero: bool,
+) {
+    let fill = (width.unwrap_or(0) as usize).saturating_sub(prefix.len() + body.len());
+    if flags.contains(&b'-') {
+        out.extend(prefix);
+        out.extend(body);
+        out.extend(std::iter::repeat_n(b' ', fill));
+    } else if zero {
+        out.extend(prefix);
+        out.extend(std::iter::repeat_n(b'0', fill));
+        out.extend(body);
+    } else {
+        out.extend(std::iter::repeat_n(b' ', fill));
+        out.extend(prefix);
+        out.extend(body);
+    }
+}
+
+#[cfg(test)]
+fn interpret(source: &str) -> (Result<i64, GenError>, String) {
+    let ir = crate::gen::ir_of_source("<source>", source).unwrap();
+    let mut interp = Interpreter::new(&ir).unwrap();
+    let result = interp.call("main", &[]);
+    (result, String::from_utf8(interp.output).unwrap())
+}
+
+#[test]
+fn test_fizzbuzz() {
+    let (result, output) = interpret(include_str!("../../examples/fizzbuzz.cb"));
+    assert_eq!(result.unwrap(), 0);
+    assert_eq!(
+        output,
+        "1\n2\nFizz\n4\nBuzz\nFizz\n7\n8\nFizz\nBuzz\n11\nFizz\n13\n14\nFizzBuzz\n"
+    );
+}
+
+#[test]
+fn test_same_as_native() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let sources = [
+        r#"
+        int fib(int n) {
+            if (n < 2) {
+                return n;
+            }
+            return fib(n - 1) + fib(n - 2);
+        }
+
+        int main(void) {
+            return fib(20) % 251;
+        }
+        "#,
+        r#"
+        struct Point {
+            char tag;
+            long x;
+            short[3] ys;
+        }
+        struct Point origin;
+
+        long sum(struct Point *p) {
+            return p->tag + p->x + p->ys[0] + p->ys[2];
+        }
+
+        int main(void) {
+            struct Point p;
+            struct Point q;
+            p.tag = 200;
+            p.x = -7;
+            p.ys[0] = 40000;
+            p.ys[2] = 3;
+            q = p;
+            origin = q;
+            return sum(&origin) + sizeof(struct Point);
+        }
+        "#,
+        r#"
+        unsigned char[8] bytes;
+        long *cursor;
+
+        int main(void) {
+            long[4] values;
+            int i;
+            unsigned int u = 1;
+            for (i = 0; i < 8; i++) {
+                bytes[i] = i * 37;
+            }
+            values[0] = 5;
+            values[3] = 9;
+            cursor = values;
+            cursor += 3;
+            u = u << 31 >> 30;
+            return bytes[7] + *cursor + (cursor - values) + u + (-1 < u) * 100;
+        }
+        "#,
+        r#"
+        import stdio;
+        import stdlib;
+
+        int classify(int n) {
+            switch (n % 4) {
+                case 0: return 10;
+                case (-1): return 20;
+                case 3:
+                case 2: break;
+                default: return 30;
+            }
+            return 40;
+        }
+
+        int main(void) {
+            char *buf = malloc(64);
+            int total = 0;
+            int i;
+            for (i = -3; i < 4; i++) {
+                total = total * 3 + classify(i);
+            }
+            sprintf(buf, "%d%d", 12, 34);
+            total += atoi(buf);
+            free(buf);
+            return total % 256;
+        }
+        "#,
+    ];
+
+    for source in sources {
+        let mut engine = jit::Engine::new(symbol_resolver::dl::default);
+        let object = crate::gen::compile_from_source(source).unwrap();
+        engine.add_object(&object).unwrap();
+        let main = engine.get("main").expect("main not defined");
+        let main = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(main) };
+
+        let (result, _) = interpret(source);
+        assert_eq!(result.unwrap(), main() as i64, "{}", source);
+    }
+}
+
+#[test]
+fn test_printf() {
+    let (result, output) = interpret(
+        r#"
+        import stdio;
+
+        int main(void) {
+            char[32] buf;
+            long big = 1;
+            printf("[%5d|%-5d|%05d|%+d|% d]\n", 42, 42, -42, 7, 7);
+            printf("[%x|%#X|%o|%u|%ld|%hhd]\n", 255, 255, 8, -1, big << 40, 300);
+            printf("[%s|%.2s|%6s|%-3c|%*d|%.3d|%%]\n", "abc", "abc", "abc", 'z', 4, 9, 5);
+            sprintf(buf, "%d-%s", -12, "x");
+            puts(buf);
+            putchar('!');
+            return printf("");
+        }
+        "#,
+    );
+    assert_eq!(result.unwrap(), 0);
+    assert_eq!(
+        output,
+        "[   42|42   |-0042|+7| 7]\n\
+         [ff|0XFF|10|4294967295|1099511627776|44]\n\
+         [abc|ab|   abc|z  |   9|005|%]\n\
+         -12-x\n!"
+    );
+}
+
+#[test]
+fn test_runtime_errors() {
+    let (result, output) = interpret(
+        r#"
+        import stdio;
+        import stdlib;
+
+        int main(void) {
+            puts("bye");
+            exit(3);
+            return 1;
+        }
+        "#,
+    );
+    assert_eq!(result.unwrap(), 3);
+    assert_eq!(output, "bye\n");
+
+    let cases = [
+        (
+            "int *p; int main(void) { return *p; }",
+            "invalid memory access of 4 bytes at 0x0",
+        ),
+        (
+            "int main(void) { int z = 0; return 1 / z; }",
+            "division by zero",
+        ),
+        (
+            "int f(int n) { return f(n + 1); } int main(void) { return f(0); }",
+            "stack overflow",
+        ),
+        (
+            "extern int g(void); int main(void) { return g(); }",
+            "g is not defined",
+        ),
+    ];
+    for (source, message) in cases {
+        assert_eq!(interpret(source).0.unwrap_err().message, message);
+    }
+}
@@ -0,0 +1,246 @@
+use std::rc::Rc;
+
+use crate::node::def::Member;
+use crate::node::type_::{TypeBaseNode, TypeNode, TypeSuffix};
+use crate::resolve::type_check::{expand, integer_kind, type_name};
+use crate::resolve::variable_scope::{get_ref, Entity, Scope};
+
+use super::GenError;
+
+/// Size and alignment in bytes of a value in memory, following the System V AMD64 ABI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub size: i64,
+    pub align: i64,
+}
+
+impl Layout {
+    fn scalar(size: i64) -> Self {
+        Self { size, align: size }
+    }
+}
+
+/// Round `offset` up to the next multiple of `align`.
+pub fn align_to(offset: i64, align: i64) -> i64 {
+    (offset + align - 1) / align * align
+}
+
+fn unknown_size<T>(_type: &TypeNode) -> Result<T, GenError> {
+    Err(GenError {
+        message: format!("size of {} is not known", type_name(_type)),
+        span: None,
+    })
+}
+
+/// Returns the layout of a value of the given type. Typedef names are expanded in `scope`.
+pub fn layout_of(_type: &TypeNode, scope: &Rc<Scope>) -> Result<Layout, GenError> {
+    let _type = expand(_type, scope)?;
+    if let Some((size, _)) = integer_kind(&_type) {
+        return Ok(Layout::scalar(size as i64));
+    }
+
+    let mut element = _type.clone();
+    match element.suffixs.pop() {
+        Some(TypeSuffix::Pointer) => Ok(Layout::scalar(8)),
+        Some(TypeSuffix::ArrayWithValue(n)) => {
+            let element = layout_of(&element, scope)?;
+            Ok(Layout {
+                size: element.size * n as i64,
+                align: element.align,
+            })
+        }
+        Some(TypeSuffix::Array) | Some(TypeSuffix::Params(_)) => unknown_size(&_type),
+        None => match &_type.base {
+            TypeBaseNode::Struct(..) | TypeBaseNode::Union(..) => {
+                Ok(aggregate_layout(&_type, scope)?.0)
+            }
+            _ => unknown_size(&_type),
+        },
+    }
+}
+
+/// Returns the size in bytes of a value of the given type.
+pub fn size_of(_type: &TypeNode, scope: &Rc<Scope>) -> Result<i64, GenError> {
+    Ok(layout_of(_type, scope)?.size)
+}
+
+/// Returns the layout of a struct or union together with its members and their offsets.
+///
+/// Struct members are placed in declaration order, each at the next offset satisfying its
+/// alignment. Union members all start at offset 0. The size is padded to a multiple of the
+/// alignment so that the type can be used as an array element.
+pub fn aggregate_layout(
+    _type: &TypeNode,
+    scope: &Rc<Scope>,
+) -> Result<(Layout, Vec<(Member, i64)>), GenError> {
+    let (kind, tag, entity) = match &_type.base {
+        TypeBaseNode::Struct(tag, entity) => ("struct", tag, entity),
+        TypeBaseNode::Union(tag, entity) => ("union", tag, entity),
+        _ => unreachable!(),
+    };
+    let entity = entity.as_deref().cloned().or_else(|| get_ref(scope, tag));
+    let (is_union, member_list) = match entity {
+        Some(Entity::Struct { member_list }) => (false, member_list),
+        Some(Entity::Union { member_list }) => (true, member_list),
+        _ => Err(GenError {
+            message: format!("{} {} is not defined", kind, tag),
+            span: None,
+        })?,
+    };
+
+    let mut layout = Layout { size: 0, align: 1 };
+    let mut members = vec![];
+    for member in member_list {
+        let Layout { size, align } = layout_of(&member._type, scope)?;
+        let offset = if is_union {
+            layout.size = layout.size.max(size);
+            0
+        } else {
+            let offset = align_to(layout.size, align);
+            layout.size = offset + size;
+            offset
+        };
+        layout.align = layout.align.max(align);
+        members.push((member, offset));
+    }
+    layout.size = align_to(layout.size, layout.align);
+
+    Ok((layout, members))
+}
+
+/// Returns the offset and the type of the member `name` of a struct or union.
+pub fn member_offset(
+    _type: &TypeNode,
+    name: &str,
+    scope: &Rc<Scope>,
+) -> Result<(i64, TypeNode), GenError> {
+    let (_, members) = aggregate_layout(_type, scope)?;
+    match members.into_iter().find(|(member, _)| member.name == name) {
+        Some((member, offset)) => Ok((offset, expand(&member._type, scope)?)),
+        None => Err(GenError {
+            message: format!("{} has no member named {}", type_name(_type), name),
+            span: None,
+        }),
+    }
+}
+
+#[cfg(test)]
+fn resolve_source(source: &str) -> Rc<Scope> {
+    use crate::resolve::variable_scope::gen_scope_toplevel;
+    use std::rc::Weak;
+
+    let mut nodes = crate::node::parse(source).unwrap();
+    let scope =
+        gen_scope_toplevel(&mut nodes, Rc::new(Scope::default()), Weak::new(), false).unwrap();
+    gen_scope_toplevel(&mut nodes, scope, Weak::new(), true).unwrap()
+}
+
+#[cfg(test)]
+fn variable_type(scope: &Rc<Scope>, name: &str) -> TypeNode {
+    match get_ref(scope, name) {
+        Some(Entity::Variable { _type, .. }) => _type,
+        e => panic!("{} is not a variable: {:?}", name, e),
+    }
+}
+
+#[test]
+fn test_layout() {
+    let scope = resolve_source(
+        r#"
+        struct A {
+            char c;
+            int i;
+            char d;
+        }
+        struct B {
+            char c;
+            struct A a;
+            long l;
+            short s;
+        }
+        union U {
+            char[5] c;
+            int i;
+        }
+        typedef struct A TA;
+        struct C {
+            TA[3] a;
+            union U u;
+            char *p;
+        }
+        struct E {
+        }
+
+        char c;
+        unsigned short s;
+        int *p;
+        long[10] a;
+        char[3][5] m;
+        struct A sa;
+        struct B sb;
+        union U su;
+        TA ta;
+        TA[2] ta2;
+        struct C sc;
+        struct E se;
+           "#,
+    );
+    let layouts = [
+        ("c", 1, 1),
+        ("s", 2, 2),
+        ("p", 8, 8),
+        ("a", 80, 8),
+        ("m", 15, 1),
+        ("sa", 12, 4),
+        ("sb", 32, 8),
+        ("su", 8, 4),
+        ("ta", 12, 4),
+        ("ta2", 24, 4),
+        ("sc", 56, 8),
+        ("se", 0, 1),
+    ];
+    for (name, size, align) in layouts {
+        let layout = layout_of(&variable_type(&scope, name), &scope).unwrap();
+        assert_eq!(layout, Layout { size, align }, "layout of {}", name);
+    }
+}
+
+#[test]
+fn test_member_offset() {
+    let scope = resolve_source(
+        r#"
+        struct A {
+            char c;
+            int i;
+            char d;
+        }
+        struct B {
+            char c;
+            struct A a;
+            long l;
+        }
+        union U {
+            long l;
+            char c;
+        }
+        struct B b;
+        union U u;
+           "#,
+    );
+
+    let b = variable_type(&scope, "b");
+    let offsets = ["c", "a", "l"]
+        .iter()
+        .map(|name| member_offset(&b, name, &scope).unwrap().0)
+        .collect::<Vec<_>>();
+    assert_eq!(offsets, vec![0, 4, 16]);
+
+    let (_, a) = member_offset(&b, "a", &scope).unwrap();
+    assert_eq!(member_offset(&a, "d", &scope).unwrap().0, 8);
+
+    let u = variable_type(&scope, "u");
+    assert_eq!(member_offset(&u, "c", &scope).unwrap().0, 0);
+
+    let e = member_offset(&b, "nope", &scope).unwrap_err();
+    assert_eq!(e.message, "struct B has no member named nope");
+}
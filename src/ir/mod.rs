@@ -1,48 +1,94 @@
 use crate::{
+    diagnostic::{Diagnostic, Span},
     node::type_::TypeBaseNode,
-    resolve::variable_scope::{get_ref, Entity},
+    resolve::type_check::integer_kind,
+    resolve::variable_scope::{get_ref, ResolverError},
 };
+use std::collections::HashMap;
 use std::rc::Rc;
 
+pub mod cfg;
+pub mod dce;
+pub mod fold;
 pub mod fun;
+pub mod interp;
+pub mod layout;
+pub mod pass;
+pub mod text;
 pub mod unit;
 pub mod var;
+pub mod verify;
 
 use crate::{
-    node::{def::DefNode, param::ParamsNode, type_::TypeNode, Node},
+    node::{def::DefNode, type_::TypeNode, Node},
     resolve::variable_scope::Scope,
 };
 
-use self::{fun::gen_def_fun, var::gen_def_var};
+use self::{
+    fun::gen_def_fun, layout::Layout, var::gen_def_var, verify::check_fun, verify::warnings,
+};
 
 #[derive(Debug)]
 pub struct GenError {
     pub message: String,
+    pub span: Option<Span>,
 }
 
-#[derive(Debug)]
+impl Diagnostic for GenError {
+    fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    fn set_span(&mut self, span: Span) {
+        self.span = Some(span);
+    }
+}
+
+impl From<ResolverError> for GenError {
+    fn from(e: ResolverError) -> Self {
+        GenError {
+            message: e.message,
+            span: e.span,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub struct DefinedFun {
     pub name: String,
-    pub _type: (TypeNode, ParamsNode),
+    pub params: Vec<(String, Type)>,
+    /// Machine type of the return value, or `None` if the function returns `void`.
+    pub ret: Option<Type>,
     pub is_private: bool,
+    pub locals: Vec<Local>,
     pub body: Vec<Stmt>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
+pub struct Local {
+    pub name: String,
+    pub layout: Layout,
+}
+
+#[derive(Debug, PartialEq)]
 pub struct DefinedVar {
     pub name: String,
-    pub _type: TypeNode,
+    pub layout: Layout,
     pub is_private: bool,
     pub init: Option<Const>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct IR {
     pub var: Vec<DefinedVar>,
     pub fun: Vec<DefinedFun>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Stmt {
     Return(Option<Expr>),
     Jump {
@@ -53,38 +99,169 @@ pub enum Stmt {
         then_label: Label,
         else_label: Label,
     },
-    Switch,
+    Switch {
+        cond: Expr,
+        cases: Vec<JumpEntry>,
+        default_label: Label,
+    },
     Label(Label),
     ExprStmt(Expr),
-    Assign(Expr, Expr),
+    /// Store the value of the second operand of the given type at the address of the first.
+    Assign(Type, Expr, Expr),
+    /// Copy `size` bytes from the address `src` to the address `dst`.
+    Copy {
+        dst: Expr,
+        src: Expr,
+        size: i64,
+    },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Const {
-    Int(i32),
-    Str(String),
+    Int(i64),
+    /// Bytes of a string literal, without the terminating NUL.
+    Str(Vec<u8>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
-    Uni(Op, Box<Expr>),
-    Bin(Op, Box<Expr>, Box<Expr>),
-    Call(String, Vec<Expr>, Entity),
-    Addr(String, Entity),
-    Mem(Box<Expr>),
-    Var(String, Entity),
-    Const(Const),
+    Uni(Type, Op, Box<Expr>),
+    Bin(Type, Op, Box<Expr>, Box<Expr>),
+    /// Call a function by name. The flag is set when the function takes variable arguments.
+    Call(Type, String, Vec<Expr>, bool),
+    Addr(Type, String),
+    Mem(Type, Box<Expr>),
+    Var(Type, String),
+    Const(Type, Const),
 }
 
-#[derive(Debug)]
+impl Expr {
+    /// Call `f` on the operands of the expression and then on the expression itself.
+    pub fn walk<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+        match self {
+            Expr::Uni(_, _, expr) | Expr::Mem(_, expr) => expr.walk(f),
+            Expr::Bin(_, _, lhs, rhs) => {
+                lhs.walk(f);
+                rhs.walk(f);
+            }
+            Expr::Call(_, _, args, _) => args.iter().for_each(|arg| arg.walk(f)),
+            Expr::Addr(..) | Expr::Var(..) | Expr::Const(..) => {}
+        }
+        f(self)
+    }
+
+    /// Call `f` on the operands of the expression and then on the expression itself, which `f`
+    /// may replace.
+    pub fn walk_mut(&mut self, f: &mut impl FnMut(&mut Expr)) {
+        match self {
+            Expr::Uni(_, _, expr) | Expr::Mem(_, expr) => expr.walk_mut(f),
+            Expr::Bin(_, _, lhs, rhs) => {
+                lhs.walk_mut(f);
+                rhs.walk_mut(f);
+            }
+            Expr::Call(_, _, args, _) => args.iter_mut().for_each(|arg| arg.walk_mut(f)),
+            Expr::Addr(..) | Expr::Var(..) | Expr::Const(..) => {}
+        }
+        f(self)
+    }
+
+    pub fn _type(&self) -> Type {
+        match self {
+            Expr::Uni(_type, ..)
+            | Expr::Bin(_type, ..)
+            | Expr::Call(_type, ..)
+            | Expr::Addr(_type, ..)
+            | Expr::Mem(_type, ..)
+            | Expr::Var(_type, ..)
+            | Expr::Const(_type, ..) => *_type,
+        }
+    }
+}
+
+impl Stmt {
+    /// Returns the expressions that appear directly in the statement.
+    pub fn exprs(&self) -> Vec<&Expr> {
+        match self {
+            Stmt::Return(expr) => expr.iter().collect(),
+            Stmt::CJump { cond, .. } | Stmt::Switch { cond, .. } => vec![cond],
+            Stmt::ExprStmt(expr) => vec![expr],
+            Stmt::Assign(_, dst, src) => vec![dst, src],
+            Stmt::Copy { dst, src, .. } => vec![dst, src],
+            Stmt::Jump { .. } | Stmt::Label(_) => vec![],
+        }
+    }
+
+    pub fn exprs_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Stmt::Return(expr) => expr.iter_mut().collect(),
+            Stmt::CJump { cond, .. } | Stmt::Switch { cond, .. } => vec![cond],
+            Stmt::ExprStmt(expr) => vec![expr],
+            Stmt::Assign(_, dst, src) => vec![dst, src],
+            Stmt::Copy { dst, src, .. } => vec![dst, src],
+            Stmt::Jump { .. } | Stmt::Label(_) => vec![],
+        }
+    }
+}
+
+/// Machine type of a value. A value is held in a 64-bit register, sign-extended from its width
+/// for the `I` types and zero-extended for the `U` types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Type {
     I8,
     I16,
     I32,
     I64,
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl Type {
+    /// Returns the machine type of a value of `_type`, whose typedef names must already be
+    /// expanded. Values other than integers are addresses.
+    pub fn of(_type: &TypeNode) -> Type {
+        match integer_kind(_type) {
+            Some((1, true)) => Type::I8,
+            Some((2, true)) => Type::I16,
+            Some((4, true)) => Type::I32,
+            Some((8, true)) => Type::I64,
+            Some((1, false)) => Type::U8,
+            Some((2, false)) => Type::U16,
+            Some((4, false)) => Type::U32,
+            _ => Type::U64,
+        }
+    }
+
+    /// Size in bytes.
+    pub fn size(self) -> u8 {
+        match self {
+            Type::I8 | Type::U8 => 1,
+            Type::I16 | Type::U16 => 2,
+            Type::I32 | Type::U32 => 4,
+            Type::I64 | Type::U64 => 8,
+        }
+    }
+
+    pub fn is_signed(self) -> bool {
+        matches!(self, Type::I8 | Type::I16 | Type::I32 | Type::I64)
+    }
+
+    /// Truncate `value` to the width of this type and extend it back to 64 bits.
+    pub fn normalize(self, value: i64) -> i64 {
+        match self {
+            Type::I8 => value as i8 as i64,
+            Type::I16 => value as i16 as i64,
+            Type::I32 => value as i32 as i64,
+            Type::U8 => value as u8 as i64,
+            Type::U16 => value as u16 as i64,
+            Type::U32 => value as u32 as i64,
+            Type::I64 | Type::U64 => value,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Op {
     Add,
     Sub,
@@ -116,17 +293,90 @@ pub enum Op {
     UCast,
 }
 
-#[derive(Debug, Clone)]
-pub struct Label(String);
+impl Op {
+    /// Apply a unary operator to a 64-bit value. The result still has to be normalized to the
+    /// type of the expression.
+    pub fn eval_uni(&self, value: i64) -> Result<i64, GenError> {
+        match self {
+            Op::UMinus => Ok(value.wrapping_neg()),
+            Op::BitNot => Ok(!value),
+            Op::Not => Ok((value == 0) as i64),
+            Op::SCast | Op::UCast => Ok(value),
+            op => Err(GenError {
+                message: format!("{:?} is not an unary operator", op),
+                span: None,
+            }),
+        }
+    }
 
-#[derive(Debug)]
-pub struct JumpEntry {}
+    /// Apply a binary operator to 64-bit values like the generated code does. The result still
+    /// has to be normalized to the type of the expression.
+    pub fn eval_bin(&self, lhs: i64, rhs: i64) -> Result<i64, GenError> {
+        let (ul, ur) = (lhs as u64, rhs as u64);
+        let divide = |value: Option<i64>| {
+            value.ok_or_else(|| GenError {
+                message: if rhs == 0 {
+                    "division by zero".into()
+                } else {
+                    "division overflow".into()
+                },
+                span: None,
+            })
+        };
+        let value = match self {
+            Op::Add => lhs.wrapping_add(rhs),
+            Op::Sub => lhs.wrapping_sub(rhs),
+            Op::Mul => lhs.wrapping_mul(rhs),
+            Op::SDiv => divide(lhs.checked_div(rhs))?,
+            Op::SMod => divide(lhs.checked_rem(rhs))?,
+            Op::UDiv => divide(ul.checked_div(ur).map(|v| v as i64))?,
+            Op::UMod => divide(ul.checked_rem(ur).map(|v| v as i64))?,
+            Op::BitAnd => lhs & rhs,
+            Op::BitOr => lhs | rhs,
+            Op::BitXor => lhs ^ rhs,
+            // The shift count is masked to 6 bits like the x86-64 shift instructions.
+            Op::BitLShift => lhs << (rhs & 63),
+            Op::BitRShift => (ul >> (rhs & 63)) as i64,
+            Op::ArithRShift => lhs >> (rhs & 63),
+            Op::EQ => (lhs == rhs) as i64,
+            Op::NEQ => (lhs != rhs) as i64,
+            Op::SGt => (lhs > rhs) as i64,
+            Op::UGt => (ul > ur) as i64,
+            Op::SGteq => (lhs >= rhs) as i64,
+            Op::UGteq => (ul >= ur) as i64,
+            Op::SLt => (lhs < rhs) as i64,
+            Op::ULt => (ul < ur) as i64,
+            Op::SLteq => (lhs <= rhs) as i64,
+            Op::ULteq => (ul <= ur) as i64,
+            op => Err(GenError {
+                message: format!("{:?} is not a binary operator", op),
+                span: None,
+            })?,
+        };
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label(pub String);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JumpEntry {
+    pub value: i64,
+    pub label: Label,
+}
 
 #[derive(Debug)]
 pub struct LabelGenerator {
     counter: u32,
 }
 
+impl Default for LabelGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LabelGenerator {
     pub fn new() -> Self {
         Self { counter: 0 }
@@ -134,7 +384,7 @@ impl LabelGenerator {
 
     pub fn new_label(&mut self) -> Label {
         self.counter += 1;
-        Label(format!(".L{}", self.counter.to_string()))
+        Label(format!(".L{}", self.counter))
     }
 }
 
@@ -143,6 +393,12 @@ pub struct TmpVarGenerator {
     counter: u32,
 }
 
+impl Default for TmpVarGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TmpVarGenerator {
     pub fn new() -> Self {
         Self { counter: 0 }
@@ -151,6 +407,7 @@ impl TmpVarGenerator {
     pub fn new_tmpvar(&mut self, scope: &Rc<Scope>) -> String {
         loop {
             let name = format!("__tmp{}", self.counter);
+            self.counter += 1;
             if get_ref(scope, &name).is_none() {
                 return name;
             }
@@ -165,6 +422,16 @@ pub struct IRInfo {
     pub continue_stack: Vec<Label>,
     pub label_gen: LabelGenerator,
     pub tmpvargen: TmpVarGenerator,
+    pub locals: Vec<(String, TypeNode)>,
+    /// Variables declared in each enclosing block of the function, from their names in the
+    /// source to their names in the IR.
+    pub blocks: Vec<HashMap<String, String>>,
+}
+
+impl Default for IRInfo {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl IRInfo {
@@ -174,6 +441,8 @@ impl IRInfo {
         let continue_stack = vec![];
         let label_gen = LabelGenerator::new();
         let tmpvargen = TmpVarGenerator::new();
+        let locals = vec![];
+        let blocks = vec![];
 
         IRInfo {
             scope_stack,
@@ -181,12 +450,14 @@ impl IRInfo {
             continue_stack,
             label_gen,
             tmpvargen,
+            locals,
+            blocks,
         }
     }
 
     pub fn new_label(&mut self) -> Label {
         self.label_gen.counter += 1;
-        Label(format!(".L{}", self.label_gen.counter.to_string()))
+        Label(format!(".L{}", self.label_gen.counter))
     }
 
     pub fn push_continue(&mut self, label: &Label) {
@@ -205,6 +476,22 @@ impl IRInfo {
         self.break_stack.pop().expect("break stack is empty")
     }
 
+    /// Returns the label that `continue` jumps to in the innermost loop.
+    pub fn continue_label(&self) -> Result<Label, GenError> {
+        self.continue_stack.last().cloned().ok_or_else(|| GenError {
+            message: "continue statement not within a loop".into(),
+            span: None,
+        })
+    }
+
+    /// Returns the label that `break` jumps to in the innermost loop or switch.
+    pub fn break_label(&self) -> Result<Label, GenError> {
+        self.break_stack.last().cloned().ok_or_else(|| GenError {
+            message: "break statement not within a loop or switch".into(),
+            span: None,
+        })
+    }
+
     pub fn push_scope(&mut self, scope: Rc<Scope>) {
         self.scope_stack.push(scope);
     }
@@ -217,33 +504,103 @@ impl IRInfo {
         self.scope_stack.pop();
     }
 
+    pub fn add_local(&mut self, name: &str, _type: &TypeNode) {
+        self.locals.push((name.to_string(), _type.clone()));
+    }
+
+    pub fn push_block(&mut self) {
+        self.blocks.push(HashMap::new());
+    }
+
+    pub fn pop_block(&mut self) {
+        self.blocks.pop();
+    }
+
+    /// Declare a local in the innermost block and return its name in the IR. The name is unique
+    /// within the function, so a declaration that shadows another variable gets its own slot.
+    pub fn declare_local(&mut self, name: &str, _type: &TypeNode) -> String {
+        let scope = self.current_scope();
+        let global = scope.parent.borrow().upgrade();
+        let is_taken = |ir_name: &str| {
+            self.locals.iter().any(|(n, _)| n == ir_name)
+                || self.blocks.iter().any(|b| b.values().any(|n| n == ir_name))
+                || global
+                    .as_ref()
+                    .is_some_and(|g| get_ref(g, ir_name).is_some())
+        };
+        let mut ir_name = name.to_string();
+        let mut suffix = 0;
+        while is_taken(&ir_name) || (suffix > 0 && get_ref(&scope, &ir_name).is_some()) {
+            suffix += 1;
+            ir_name = format!("{}__{}", name, suffix);
+        }
+        self.blocks
+            .last_mut()
+            .expect("no block to declare a local in")
+            .insert(name.to_string(), ir_name.clone());
+        self.add_local(&ir_name, _type);
+        ir_name
+    }
+
+    /// Returns the name in the IR of the variable `name` refers to in the current block.
+    pub fn variable_name(&self, name: &str) -> String {
+        self.blocks
+            .iter()
+            .rev()
+            .find_map(|b| b.get(name))
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+
     pub fn get_tmpvar(&mut self, scope: Rc<Scope>, base: TypeBaseNode) -> Expr {
-        Expr::Var(
-            self.tmpvargen.new_tmpvar(&scope),
-            Entity::Variable {
-                _type: TypeNode {
-                    base,
-                    suffixs: vec![],
-                },
-                is_static: false,
-                init: None,
-            },
-        )
+        let _type = TypeNode {
+            base,
+            suffixs: vec![],
+            span: Span::default(),
+        };
+        self.get_tmpvar_of(scope, _type)
+    }
+
+    pub fn get_tmpvar_of(&mut self, scope: Rc<Scope>, _type: TypeNode) -> Expr {
+        let name = self.tmpvargen.new_tmpvar(&scope);
+        self.add_local(&name, &_type);
+        Expr::Var(Type::of(&_type), name)
     }
 }
 
-pub fn gen_ir(nodes: Vec<Node>) -> Result<IR, GenError> {
+/// Generate the IR of the program, along with warnings about functions that are valid but
+/// probably wrong, such as ones that can end without returning a value.
+pub fn gen_ir(nodes: Vec<Node>, scope: &Rc<Scope>) -> Result<(IR, Vec<GenError>), GenError> {
     let mut ir = IR {
         fun: vec![],
         var: vec![],
     };
+    let mut warns = vec![];
     let mut info = IRInfo::new();
 
     for node in nodes {
         match node {
             Node::Def(def) => match def.as_ref() {
-                DefNode::Vars(def_var) => ir.var.extend(gen_def_var(def_var)?),
-                DefNode::Fun(fun) => ir.fun.push(gen_def_fun(fun, &mut info)?),
+                DefNode::Vars(def_var) => ir
+                    .var
+                    .extend(gen_def_var(def_var, scope).map_err(|e| e.or_span(def_var.span))?),
+                DefNode::Fun(fun) => {
+                    let defined = gen_def_fun(fun, &mut info).map_err(|e| e.or_span(fun.span))?;
+                    // Labels in the source are already checked by the resolver, so a problem
+                    // here is a bug in the IR generation.
+                    if cfg!(debug_assertions) {
+                        check_fun(&defined).map_err(|e| e.or_span(fun.span))?;
+                    }
+                    warns.extend(warnings(&defined).into_iter().map(|problem| GenError {
+                        message: format!("{} in {}", problem, defined.name),
+                        span: Some(fun.span),
+                    }));
+                    ir.fun.push(defined);
+                }
+                DefNode::Enum { .. }
+                | DefNode::Struct { .. }
+                | DefNode::Union { .. }
+                | DefNode::Type { .. } => {}
                 _ => todo!(),
             },
             Node::Import(_) => {}
@@ -251,7 +608,7 @@ pub fn gen_ir(nodes: Vec<Node>) -> Result<IR, GenError> {
         }
     }
 
-    Ok(ir)
+    Ok((ir, warns))
 }
 
 #[test]
@@ -279,9 +636,9 @@ fn test_gen_ir() {
     let scope =
         gen_scope_toplevel(&mut nodes, Rc::new(Scope::default()), Weak::new(), false).unwrap();
 
-    gen_scope_toplevel(&mut nodes, scope, Weak::new(), true).unwrap();
+    let scope = gen_scope_toplevel(&mut nodes, scope, Weak::new(), true).unwrap();
 
-    let ir = gen_ir(nodes);
+    let ir = gen_ir(nodes, &scope);
     assert!(ir.is_ok());
 
     let mut nodes = crate::node::parse(
@@ -300,9 +657,9 @@ fn test_gen_ir() {
     let scope =
         gen_scope_toplevel(&mut nodes, Rc::new(Scope::default()), Weak::new(), false).unwrap();
 
-    gen_scope_toplevel(&mut nodes, scope, Weak::new(), true).unwrap();
+    let scope = gen_scope_toplevel(&mut nodes, scope, Weak::new(), true).unwrap();
 
-    let ir = gen_ir(nodes);
+    let ir = gen_ir(nodes, &scope);
     assert!(ir.is_ok());
 
     let mut nodes = crate::node::parse(
@@ -325,9 +682,9 @@ fn test_gen_ir() {
     let scope =
         gen_scope_toplevel(&mut nodes, Rc::new(Scope::default()), Weak::new(), false).unwrap();
 
-    gen_scope_toplevel(&mut nodes, scope, Weak::new(), true).unwrap();
+    let scope = gen_scope_toplevel(&mut nodes, scope, Weak::new(), true).unwrap();
 
-    let ir = gen_ir(nodes);
+    let ir = gen_ir(nodes, &scope);
     assert!(ir.is_ok());
 
     let mut nodes = crate::node::parse(
@@ -364,8 +721,8 @@ fn test_gen_ir() {
     let scope =
         gen_scope_toplevel(&mut nodes, Rc::new(Scope::default()), Weak::new(), false).unwrap();
 
-    gen_scope_toplevel(&mut nodes, scope, Weak::new(), true).unwrap();
+    let scope = gen_scope_toplevel(&mut nodes, scope, Weak::new(), true).unwrap();
 
-    let ir = gen_ir(nodes);
+    let ir = gen_ir(nodes, &scope);
     assert!(ir.is_ok());
 }
@@ -0,0 +1,316 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use super::dce::DeadCode;
+use super::fold::ConstFold;
+use super::verify::check_fun;
+use super::{GenError, IR};
+
+/// A transformation of the IR.
+pub trait Pass {
+    /// Name used to disable the pass and to label its timing and dumps.
+    fn name(&self) -> &'static str;
+
+    /// Returns whether the IR has changed.
+    fn run(&mut self, ir: &mut IR) -> Result<bool, GenError>;
+}
+
+/// Optimization level, selecting a fixed pipeline of passes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptLevel {
+    #[default]
+    O0,
+    O1,
+    O2,
+}
+
+/// Returns the passes that run at `level`, in order.
+fn pipeline(level: OptLevel) -> Vec<Box<dyn Pass>> {
+    match level {
+        OptLevel::O0 => vec![],
+        OptLevel::O1 | OptLevel::O2 => vec![Box::new(ConstFold), Box::new(DeadCode)],
+    }
+}
+
+/// Callback receiving the name of a pass and the IR after it has run.
+type Dump = Box<dyn FnMut(&str, &IR)>;
+
+/// Runs a sequence of passes over the IR.
+///
+/// Passes can be disabled by name, and `limit` stops after a number of passes so that a
+/// miscompilation can be bisected to the pass that introduced it. In debug builds the control
+/// flow of every function is verified after each pass.
+///
+/// With `repeat`, the whole sequence runs again until no pass changes the IR, as removing dead
+/// code can leave locals that are assigned once, whose constants can then be propagated.
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    repeat: bool,
+    disabled: HashSet<String>,
+    limit: Option<usize>,
+    dump: Option<Dump>,
+    timings: Vec<(&'static str, Duration)>,
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A pass manager with the pipeline of `level`.
+    pub fn with_level(level: OptLevel) -> Self {
+        Self {
+            passes: pipeline(level),
+            repeat: level == OptLevel::O2,
+            ..Self::default()
+        }
+    }
+
+    pub fn add(&mut self, pass: impl Pass + 'static) -> &mut Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    /// Returns the names of the passes in the order they run, including disabled ones.
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// Skip every pass named `name`.
+    pub fn disable(&mut self, name: &str) -> &mut Self {
+        self.disabled.insert(name.to_string());
+        self
+    }
+
+    /// Run the passes again until none of them changes the IR.
+    pub fn repeat(&mut self) -> &mut Self {
+        self.repeat = true;
+        self
+    }
+
+    /// Run at most `limit` passes, counting every run when repeating. Disabled passes are not
+    /// counted.
+    pub fn limit(&mut self, limit: usize) -> &mut Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Call `dump` with the name of each pass and the IR after it has run.
+    pub fn dump_after(&mut self, dump: impl FnMut(&str, &IR) + 'static) -> &mut Self {
+        self.dump = Some(Box::new(dump));
+        self
+    }
+
+    /// Returns the time taken by each run of a pass, in order.
+    pub fn timings(&self) -> &[(&'static str, Duration)] {
+        &self.timings
+    }
+
+    pub fn run(&mut self, ir: &mut IR) -> Result<(), GenError> {
+        let mut remaining = self.limit.unwrap_or(usize::MAX);
+        loop {
+            let mut changed = false;
+            let passes = self
+                .passes
+                .iter_mut()
+                .filter(|pass| !self.disabled.contains(pass.name()));
+            for pass in passes {
+                if remaining == 0 {
+                    return Ok(());
+                }
+                remaining -= 1;
+
+                let start = Instant::now();
+                changed |= pass.run(ir)?;
+                self.timings.push((pass.name(), start.elapsed()));
+
+                if cfg!(debug_assertions) {
+                    for fun in ir.fun.iter() {
+                        check_fun(fun).map_err(|e| GenError {
+                            message: format!("{} after {}", e.message, pass.name()),
+                            span: None,
+                        })?;
+                    }
+                }
+                if let Some(dump) = self.dump.as_mut() {
+                    dump(pass.name(), ir);
+                }
+            }
+            if !self.repeat || !changed {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+struct Rename(&'static str, &'static str);
+
+#[cfg(test)]
+impl Pass for Rename {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn run(&mut self, ir: &mut IR) -> Result<bool, GenError> {
+        let mut changed = false;
+        for fun in ir.fun.iter_mut().filter(|fun| !fun.name.ends_with(self.1)) {
+            fun.name.push_str(self.1);
+            changed = true;
+        }
+        Ok(changed)
+    }
+}
+
+#[test]
+fn test_pass_manager() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let source = "fun f() {\n    return\n}\n";
+    let dumps = Rc::new(RefCell::new(vec![]));
+
+    let mut passes = PassManager::new();
+    passes
+        .add(Rename("a", "_a"))
+        .add(Rename("b", "_b"))
+        .add(Rename("c", "_c"))
+        .add(Rename("d", "_d"))
+        .disable("b")
+        .limit(2);
+    let sink = dumps.clone();
+    passes.dump_after(move |name, ir| sink.borrow_mut().push(format!("{}: {}", name, ir)));
+
+    let mut ir = super::text::parse(source).unwrap();
+    passes.run(&mut ir).unwrap();
+
+    assert_eq!(passes.pass_names(), vec!["a", "b", "c", "d"]);
+    assert_eq!(ir.fun[0].name, "f_a_c");
+    let names = passes
+        .timings()
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["a", "c"]);
+    assert_eq!(
+        *dumps.borrow(),
+        vec![
+            "a: fun f_a() {\n    return\n}\n",
+            "c: fun f_a_c() {\n    return\n}\n"
+        ]
+    );
+}
+
+#[test]
+fn test_pass_breaking_ir() {
+    struct DropLabels;
+
+    impl Pass for DropLabels {
+        fn name(&self) -> &'static str {
+            "drop-labels"
+        }
+
+        fn run(&mut self, ir: &mut IR) -> Result<bool, GenError> {
+            for fun in ir.fun.iter_mut() {
+                fun.body
+                    .retain(|stmt| !matches!(stmt, super::Stmt::Label(_)));
+            }
+            Ok(true)
+        }
+    }
+
+    let mut ir = super::text::parse("fun f() {\n.L1:\n    jump .L1\n}\n").unwrap();
+    let mut passes = PassManager::new();
+    passes.add(DropLabels);
+    let result = passes.run(&mut ir);
+    if cfg!(debug_assertions) {
+        assert_eq!(
+            result.unwrap_err().message,
+            "jump to undefined label .L1 in f after drop-labels"
+        );
+    }
+}
+
+#[test]
+fn test_compile_with_passes() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        let mut passes = PassManager::with_level(level);
+        passes.add(Rename("rename", "_renamed"));
+        let object = crate::gen::compile_source_with(
+            "<source>",
+            "int main(void) { return 1 + 2 * 3; }",
+            &mut passes,
+        )
+        .unwrap();
+
+        let mut engine = jit::Engine::new(symbol_resolver::none);
+        engine.add_object(&object).unwrap();
+        assert!(engine.get("main").is_none());
+        let main = engine
+            .get("main_renamed")
+            .expect("main_renamed not defined");
+        let main = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(main) };
+        assert_eq!(main(), 7);
+    }
+}
+
+#[test]
+fn test_opt_levels() {
+    let source = r#"
+        long f(void) {
+            long x = 1;
+            if (0) {
+                x = 2;
+            }
+            return x * 3;
+        }
+    "#;
+    let optimize = |passes: &mut PassManager| {
+        let mut ir = crate::gen::ir_of_source("<source>", source).unwrap();
+        passes.run(&mut ir).unwrap();
+        let returns_three = matches!(
+            ir.fun[0].body.last(),
+            Some(super::Stmt::Return(Some(super::Expr::Const(
+                _,
+                super::Const::Int(3)
+            ))))
+        );
+        let runs: Vec<_> = passes.timings().iter().map(|(name, _)| *name).collect();
+        (returns_three, runs)
+    };
+
+    // The store of 2 is only removed as dead code after the first round of folding.
+    let (folded, runs) = optimize(&mut PassManager::with_level(OptLevel::O1));
+    assert!(!folded);
+    assert_eq!(runs, vec!["const-fold", "dce"]);
+
+    let (folded, runs) = optimize(&mut PassManager::with_level(OptLevel::O2));
+    assert!(folded);
+    assert_eq!(
+        runs,
+        vec![
+            "const-fold",
+            "dce",
+            "const-fold",
+            "dce",
+            "const-fold",
+            "dce"
+        ]
+    );
+    assert_eq!(
+        PassManager::with_level(OptLevel::O2).pass_names(),
+        vec!["const-fold", "dce"]
+    );
+
+    // Disabled passes and the limit apply to the repeated rounds as well.
+    let (folded, runs) = optimize(PassManager::with_level(OptLevel::O2).disable("const-fold"));
+    assert!(!folded);
+    assert_eq!(runs, vec!["dce", "dce"]);
+    let (folded, runs) = optimize(PassManager::with_level(OptLevel::O2).limit(3));
+    assert!(folded);
+    assert_eq!(runs, vec!["const-fold", "dce", "const-fold"]);
+}
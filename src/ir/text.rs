@@ -0,0 +1,676 @@
+//! Textual form of the IR.
+//!
+//! The `Display` impls print an `IR` in a line-oriented format that `parse` reads back into an
+//! equal `IR`, so that IR can be compared against expected text or written by hand.
+//!
+//! ```text
+//! global count size 4 align 4 = 0
+//! private global message size 8 align 8 = "hello\n"
+//!
+//! fun add(i32 a, i32 b) -> i32 {
+//!     local __tmp0 size 4 align 4
+//!     store i32 (addr u64 __tmp0), (add i32 (var i32 a) (var i32 b))
+//!     cjump (var i32 __tmp0), .L1, .L2
+//! .L1:
+//!     eval (call variadic i32 printf (const u64 "%d\n") (var i32 __tmp0))
+//! .L2:
+//!     return (var i32 __tmp0)
+//! }
+//! ```
+//!
+//! Every expression is parenthesized and starts with its operator and machine type.
+
+use std::fmt::{self, Display, Formatter};
+
+use pest::iterators::Pair;
+use pest::Parser;
+
+use crate::diagnostic::Span;
+
+use super::layout::Layout;
+use super::{
+    Const, DefinedFun, DefinedVar, Expr, GenError, JumpEntry, Label, Local, Op, Stmt, Type, IR,
+};
+
+#[derive(Parser)]
+#[grammar = "ir/text.pest"]
+struct IRScanner;
+
+impl Display for IR {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for var in self.var.iter() {
+            writeln!(f, "{}", var)?;
+        }
+        for (i, fun) in self.fun.iter().enumerate() {
+            if i > 0 || !self.var.is_empty() {
+                writeln!(f)?;
+            }
+            write!(f, "{}", fun)?;
+        }
+        Ok(())
+    }
+}
+
+fn private(is_private: bool) -> &'static str {
+    if is_private {
+        "private "
+    } else {
+        ""
+    }
+}
+
+impl Display for Layout {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "size {} align {}", self.size, self.align)
+    }
+}
+
+impl Display for DefinedVar {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}global {} {}",
+            private(self.is_private),
+            self.name,
+            self.layout
+        )?;
+        if let Some(init) = &self.init {
+            write!(f, " = {}", init)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for DefinedFun {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let params = self
+            .params
+            .iter()
+            .map(|(name, _type)| format!("{} {}", _type, name))
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "{}fun {}({})",
+            private(self.is_private),
+            self.name,
+            params.join(", ")
+        )?;
+        if let Some(ret) = self.ret {
+            write!(f, " -> {}", ret)?;
+        }
+        writeln!(f, " {{")?;
+        for Local { name, layout } in self.locals.iter() {
+            writeln!(f, "    local {} {}", name, layout)?;
+        }
+        for stmt in self.body.iter() {
+            match stmt {
+                Stmt::Label(_) => writeln!(f, "{}", stmt)?,
+                _ => writeln!(f, "    {}", stmt)?,
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl Display for Stmt {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Stmt::Return(Some(expr)) => write!(f, "return {}", expr),
+            Stmt::Return(None) => write!(f, "return"),
+            Stmt::Jump { label } => write!(f, "jump {}", label),
+            Stmt::CJump {
+                cond,
+                then_label,
+                else_label,
+            } => write!(f, "cjump {}, {}, {}", cond, then_label, else_label),
+            Stmt::Switch {
+                cond,
+                cases,
+                default_label,
+            } => {
+                let cases = cases
+                    .iter()
+                    .map(|JumpEntry { value, label }| format!("{}: {}", value, label))
+                    .collect::<Vec<_>>();
+                write!(
+                    f,
+                    "switch {} [{}] default {}",
+                    cond,
+                    cases.join(", "),
+                    default_label
+                )
+            }
+            Stmt::Label(label) => write!(f, "{}:", label),
+            Stmt::ExprStmt(expr) => write!(f, "eval {}", expr),
+            Stmt::Assign(_type, dst, src) => write!(f, "store {} {}, {}", _type, dst, src),
+            Stmt::Copy { dst, src, size } => write!(f, "copy {}, {}, {}", dst, src, size),
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Expr::Uni(_type, op, expr) => write!(f, "({} {} {})", op, _type, expr),
+            Expr::Bin(_type, op, lhs, rhs) => write!(f, "({} {} {} {})", op, _type, lhs, rhs),
+            Expr::Call(_type, name, args, variadic) => {
+                write!(f, "(call ")?;
+                if *variadic {
+                    write!(f, "variadic ")?;
+                }
+                write!(f, "{} {}", _type, name)?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Addr(_type, name) => write!(f, "(addr {} {})", _type, name),
+            Expr::Mem(_type, expr) => write!(f, "(mem {} {})", _type, expr),
+            Expr::Var(_type, name) => write!(f, "(var {} {})", _type, name),
+            Expr::Const(_type, value) => write!(f, "(const {} {})", _type, value),
+        }
+    }
+}
+
+impl Display for Const {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Const::Int(value) => write!(f, "{}", value),
+            Const::Str(s) => {
+                write!(f, "\"")?;
+                for &b in s.iter() {
+                    match b {
+                        b'"' => write!(f, "\\\"")?,
+                        b'\\' => write!(f, "\\\\")?,
+                        b'\n' => write!(f, "\\n")?,
+                        b'\t' => write!(f, "\\t")?,
+                        b'\r' => write!(f, "\\r")?,
+                        b if b.is_ascii_graphic() || b == b' ' => write!(f, "{}", b as char)?,
+                        b => write!(f, "\\x{:02x}", b)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+        }
+    }
+}
+
+impl Display for Label {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+const TYPES: [(Type, &str); 8] = [
+    (Type::I8, "i8"),
+    (Type::I16, "i16"),
+    (Type::I32, "i32"),
+    (Type::I64, "i64"),
+    (Type::U8, "u8"),
+    (Type::U16, "u16"),
+    (Type::U32, "u32"),
+    (Type::U64, "u64"),
+];
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let (_, name) = TYPES.iter().find(|(_type, _)| _type == self).unwrap();
+        write!(f, "{}", name)
+    }
+}
+
+const OPS: [(Op, &str); 28] = [
+    (Op::Add, "add"),
+    (Op::Sub, "sub"),
+    (Op::Mul, "mul"),
+    (Op::SDiv, "sdiv"),
+    (Op::UDiv, "udiv"),
+    (Op::SMod, "smod"),
+    (Op::UMod, "umod"),
+    (Op::Not, "not"),
+    (Op::BitAnd, "band"),
+    (Op::BitOr, "bor"),
+    (Op::BitXor, "bxor"),
+    (Op::BitNot, "bnot"),
+    (Op::BitLShift, "shl"),
+    (Op::BitRShift, "shr"),
+    (Op::ArithRShift, "sar"),
+    (Op::EQ, "eq"),
+    (Op::NEQ, "ne"),
+    (Op::SGt, "sgt"),
+    (Op::UGt, "ugt"),
+    (Op::SGteq, "sge"),
+    (Op::UGteq, "uge"),
+    (Op::SLt, "slt"),
+    (Op::ULt, "ult"),
+    (Op::SLteq, "sle"),
+    (Op::ULteq, "ule"),
+    (Op::UMinus, "neg"),
+    (Op::SCast, "scast"),
+    (Op::UCast, "ucast"),
+];
+
+impl Display for Op {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let (_, name) = OPS.iter().find(|(op, _)| op == self).unwrap();
+        write!(f, "{}", name)
+    }
+}
+
+/// Parse the textual form of the IR.
+pub fn parse(source: &str) -> Result<IR, GenError> {
+    let pair = IRScanner::parse(Rule::IR, source)
+        .map_err(|e| {
+            let span = match e.location {
+                pest::error::InputLocation::Pos(pos) => Span::new(pos, pos),
+                pest::error::InputLocation::Span((start, end)) => Span::new(start, end),
+            };
+            GenError {
+                message: format!("failed to parse IR: {}", e.variant.message()),
+                span: Some(span),
+            }
+        })?
+        .next()
+        .unwrap();
+
+    let mut ir = IR {
+        var: vec![],
+        fun: vec![],
+    };
+    for pair in pair.into_inner() {
+        match pair.as_rule() {
+            Rule::GLOBAL => ir.var.push(parse_global(pair)?),
+            Rule::FUN => ir.fun.push(parse_fun(pair)?),
+            Rule::EOI => break,
+            e => panic!("{:?}", e),
+        }
+    }
+    Ok(ir)
+}
+
+fn parse_global(pair: Pair<Rule>) -> Result<DefinedVar, GenError> {
+    let mut pairs = pair.into_inner().peekable();
+    let is_private = pairs.next_if(|p| p.as_rule() == Rule::PRIVATE).is_some();
+    let name = pairs.next().unwrap().as_str().to_string();
+    let layout = parse_layout(pairs.next().unwrap())?;
+    let init = pairs.next().map(parse_const).transpose()?;
+    Ok(DefinedVar {
+        name,
+        layout,
+        is_private,
+        init,
+    })
+}
+
+fn parse_fun(pair: Pair<Rule>) -> Result<DefinedFun, GenError> {
+    let mut pairs = pair.into_inner().peekable();
+    let is_private = pairs.next_if(|p| p.as_rule() == Rule::PRIVATE).is_some();
+    let name = pairs.next().unwrap().as_str().to_string();
+
+    let mut params = vec![];
+    let mut ret = None;
+    let mut locals = vec![];
+    let mut body = vec![];
+    for pair in pairs {
+        match pair.as_rule() {
+            Rule::PARAM => {
+                let mut pairs = pair.into_inner();
+                let _type = parse_type(pairs.next().unwrap());
+                params.push((pairs.next().unwrap().as_str().to_string(), _type));
+            }
+            Rule::TYPE => ret = Some(parse_type(pair)),
+            Rule::LOCAL => {
+                let mut pairs = pair.into_inner();
+                let name = pairs.next().unwrap().as_str().to_string();
+                let layout = parse_layout(pairs.next().unwrap())?;
+                locals.push(Local { name, layout });
+            }
+            _ => body.push(parse_stmt(pair)?),
+        }
+    }
+
+    Ok(DefinedFun {
+        name,
+        params,
+        ret,
+        is_private,
+        locals,
+        body,
+    })
+}
+
+fn parse_layout(pair: Pair<Rule>) -> Result<Layout, GenError> {
+    let span = pair.as_span().into();
+    let mut pairs = pair.into_inner();
+    let size = parse_int(pairs.next().unwrap())?;
+    let align = parse_int(pairs.next().unwrap())?;
+    if align <= 0 {
+        return Err(GenError {
+            message: format!("alignment must be positive, but got {}", align),
+            span: Some(span),
+        });
+    }
+    Ok(Layout { size, align })
+}
+
+fn parse_stmt(pair: Pair<Rule>) -> Result<Stmt, GenError> {
+    let rule = pair.as_rule();
+    let mut pairs = pair.into_inner();
+    let stmt = match rule {
+        Rule::LABEL_STMT => Stmt::Label(parse_label(pairs.next().unwrap())),
+        Rule::RETURN => Stmt::Return(pairs.next().map(parse_expr).transpose()?),
+        Rule::JUMP => Stmt::Jump {
+            label: parse_label(pairs.next().unwrap()),
+        },
+        Rule::CJUMP => Stmt::CJump {
+            cond: parse_expr(pairs.next().unwrap())?,
+            then_label: parse_label(pairs.next().unwrap()),
+            else_label: parse_label(pairs.next().unwrap()),
+        },
+        Rule::SWITCH => {
+            let cond = parse_expr(pairs.next().unwrap())?;
+            let mut cases = vec![];
+            let mut default_label = None;
+            for pair in pairs {
+                match pair.as_rule() {
+                    Rule::CASE => {
+                        let mut pairs = pair.into_inner();
+                        cases.push(JumpEntry {
+                            value: parse_int(pairs.next().unwrap())?,
+                            label: parse_label(pairs.next().unwrap()),
+                        });
+                    }
+                    _ => default_label = Some(parse_label(pair)),
+                }
+            }
+            Stmt::Switch {
+                cond,
+                cases,
+                default_label: default_label.unwrap(),
+            }
+        }
+        Rule::EVAL => Stmt::ExprStmt(parse_expr(pairs.next().unwrap())?),
+        Rule::STORE => Stmt::Assign(
+            parse_type(pairs.next().unwrap()),
+            parse_expr(pairs.next().unwrap())?,
+            parse_expr(pairs.next().unwrap())?,
+        ),
+        Rule::COPY => Stmt::Copy {
+            dst: parse_expr(pairs.next().unwrap())?,
+            src: parse_expr(pairs.next().unwrap())?,
+            size: parse_int(pairs.next().unwrap())?,
+        },
+        e => panic!("{:?}", e),
+    };
+    Ok(stmt)
+}
+
+fn parse_expr(pair: Pair<Rule>) -> Result<Expr, GenError> {
+    let rule = pair.as_rule();
+    let mut pairs = pair.into_inner().peekable();
+    let variadic = pairs.next_if(|p| p.as_rule() == Rule::VARIADIC).is_some();
+    let op = pairs
+        .next_if(|p| matches!(p.as_rule(), Rule::UNI_OP | Rule::BIN_OP))
+        .map(|p| {
+            OPS.iter()
+                .find(|(_, name)| *name == p.as_str())
+                .unwrap()
+                .0
+                .clone()
+        });
+    let _type = parse_type(pairs.next().unwrap());
+    let mut next = || pairs.next().unwrap();
+
+    let expr = match rule {
+        Rule::CONST => Expr::Const(_type, parse_const(next())?),
+        Rule::VAR => Expr::Var(_type, next().as_str().to_string()),
+        Rule::ADDR => Expr::Addr(_type, next().as_str().to_string()),
+        Rule::MEM => Expr::Mem(_type, Box::new(parse_expr(next())?)),
+        Rule::CALL => {
+            let name = next().as_str().to_string();
+            let args = pairs.map(parse_expr).collect::<Result<_, _>>()?;
+            Expr::Call(_type, name, args, variadic)
+        }
+        Rule::UNI => Expr::Uni(_type, op.unwrap(), Box::new(parse_expr(next())?)),
+        Rule::BIN => {
+            let lhs = parse_expr(next())?;
+            let rhs = parse_expr(next())?;
+            Expr::Bin(_type, op.unwrap(), Box::new(lhs), Box::new(rhs))
+        }
+        e => panic!("{:?}", e),
+    };
+    Ok(expr)
+}
+
+fn parse_type(pair: Pair<Rule>) -> Type {
+    let (_type, _) = TYPES
+        .iter()
+        .find(|(_, name)| *name == pair.as_str())
+        .unwrap();
+    *_type
+}
+
+fn parse_label(pair: Pair<Rule>) -> Label {
+    Label(pair.as_str().to_string())
+}
+
+fn parse_int(pair: Pair<Rule>) -> Result<i64, GenError> {
+    pair.as_str().parse().map_err(|_| GenError {
+        message: format!("{} is out of range for a 64-bit integer", pair.as_str()),
+        span: Some(pair.as_span().into()),
+    })
+}
+
+fn parse_const(pair: Pair<Rule>) -> Result<Const, GenError> {
+    match pair.as_rule() {
+        Rule::INT => Ok(Const::Int(parse_int(pair)?)),
+        Rule::STRING => {
+            let inner = pair.into_inner().next().unwrap();
+            Ok(Const::Str(unescape(inner)?))
+        }
+        e => panic!("{:?}", e),
+    }
+}
+
+fn unescape(pair: Pair<Rule>) -> Result<Vec<u8>, GenError> {
+    let error = || GenError {
+        message: format!("invalid escape sequence in \"{}\"", pair.as_str()),
+        span: Some(pair.as_span().into()),
+    };
+
+    let mut s = vec![];
+    let mut chars = pair.as_str().chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            s.extend(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('"') => s.push(b'"'),
+            Some('\\') => s.push(b'\\'),
+            Some('n') => s.push(b'\n'),
+            Some('t') => s.push(b'\t'),
+            Some('r') => s.push(b'\r'),
+            Some('x') => {
+                let hex = chars.by_ref().take(2).collect::<String>();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(b) if hex.len() == 2 => s.push(b),
+                    _ => return Err(error()),
+                }
+            }
+            _ => return Err(error()),
+        }
+    }
+    Ok(s)
+}
+
+#[test]
+fn test_print_gen_ir() {
+    let ir = crate::gen::ir_of_source(
+        "<source>",
+        r#"
+        int count = 0;
+        static char *message = "hi\n";
+        extern int printf(char *fmt, ...);
+
+        int add(int a, int b) {
+            return a + b;
+        }
+
+        int main(void) {
+            long l = add(1, 2);
+            if (l) {
+                printf(message, l);
+            }
+            return -count;
+        }
+           "#,
+    )
+    .unwrap();
+
+    let expected = r#"global count size 4 align 4 = 0
+private global message size 8 align 8 = "hi\n"
+
+fun add(i32 a, i32 b) -> i32 {
+    return (add i32 (var i32 a) (var i32 b))
+}
+
+fun main() -> i32 {
+    local l size 8 align 8
+    store i64 (addr u64 l), (scast i64 (call i32 add (const i32 1) (const i32 2)))
+    cjump (var i64 l), .L1, .L3
+.L1:
+    eval (call variadic i32 printf (var u64 message) (var i64 l))
+.L3:
+    return (neg i32 (var i32 count))
+}
+"#;
+    assert_eq!(ir.to_string(), expected);
+}
+
+#[test]
+fn test_round_trip() {
+    let sources = [
+        r#"
+        struct Point {
+            char tag;
+            long x;
+        }
+        unsigned char[16] buf;
+        char *text = "quote \" backslash \\ tab \t bell \a end";
+
+        long copy(struct Point *p, long n) {
+            struct Point q;
+            q = *p;
+            buf[n] = ~q.tag;
+            return q.x + (unsigned char)n;
+        }
+
+        int classify(int n) {
+            switch (n) {
+                case (-1): return 1;
+                case 7: break;
+                default: goto done;
+            }
+        done:
+            return !n;
+        }
+           "#,
+        r#"
+        import stdio;
+
+        static int counter;
+
+        int main(int argc, char **argv) {
+            unsigned long u = 10;
+            int i;
+            for (i = 0; i < argc; i++) {
+                counter += u >> i;
+            }
+            printf("%d %s\n", counter, argv[0]);
+            return counter % 3;
+        }
+           "#,
+    ];
+
+    for source in sources {
+        let ir = crate::gen::ir_of_source("<source>", source).unwrap();
+        let text = ir.to_string();
+        let parsed = parse(&text).unwrap_or_else(|e| panic!("{}\n{}", e.message, text));
+        assert_eq!(parsed, ir);
+        assert_eq!(parsed.to_string(), text);
+    }
+}
+
+#[test]
+fn test_compile_parsed_ir() {
+    use xten::jit;
+    use xten::jit::symbol_resolver;
+
+    let ir = parse(
+        r#"
+        // sum(n) = 1 + 2 + ... + n, computed with a loop.
+        global total size 8 align 8
+
+        fun sum(i32 n) -> i64 {
+            local i size 4 align 4
+            store i64 (addr u64 total), (const i64 0)
+            store i32 (addr u64 i), (const i32 1)
+        .L1:
+            cjump (sle i32 (var i32 i) (var i32 n)), .L2, .L3
+        .L2:
+            store i64 (addr u64 total), (add i64 (var i64 total) (scast i64 (var i32 i)))
+            store i32 (addr u64 i), (add i32 (var i32 i) (const i32 1))
+            jump .L1
+        .L3:
+            return (var i64 total)
+        }
+
+        private fun pick(i32 n) -> i32 {
+            switch (var i32 n) [1: one, -2: minus_two] default other
+        one:
+            return (const i32 10)
+        minus_two:
+            return (const i32 20)
+        other:
+            return (call i32 sum (var i32 n))
+        }
+
+        fun main() -> i32 {
+            return (add i32 (call i32 pick (const i32 1)) (call i32 pick (const i32 4)))
+        }
+           "#,
+    )
+    .unwrap();
+
+    let mut engine = jit::Engine::new(symbol_resolver::none);
+    let object = crate::gen::compile(ir).unwrap();
+    engine.add_object(&object).unwrap();
+
+    let get = |name: &str| engine.get(name).expect("not defined");
+    let sum = unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32) -> i64>(get("sum")) };
+    let main = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(get("main")) };
+    assert_eq!(sum(100), 5050);
+    assert_eq!(main(), 10 + 10);
+}
+
+#[test]
+fn test_parse_error() {
+    let source = "fun main() -> i32 {\n    return (add i32 (const i32 1))\n}\n";
+    let e = parse(source).unwrap_err();
+    assert!(e.message.starts_with("failed to parse IR"), "{}", e.message);
+    assert_eq!(
+        crate::diagnostic::line_col(source, e.span.unwrap().start).0,
+        2
+    );
+
+    let e = parse("global g size 8 align 8 = 99999999999999999999").unwrap_err();
+    assert_eq!(
+        e.message,
+        "99999999999999999999 is out of range for a 64-bit integer"
+    );
+
+    let e = parse(r#"global g size 8 align 8 = "\q""#).unwrap_err();
+    assert_eq!(e.message, r#"invalid escape sequence in "\q""#);
+}
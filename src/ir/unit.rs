@@ -1,3 +1,4 @@
+use crate::diagnostic::Diagnostic;
 use crate::ir::Const;
 use crate::ir::GenError;
 use crate::ir::IRInfo;
@@ -5,38 +6,136 @@ use crate::node::def::def_var::DefVars;
 use crate::node::def::def_var::Var;
 use crate::node::expr::AssignOp;
 use crate::node::expr::BinaryOp;
+use crate::node::param::ParamsNode;
 use crate::node::primary::PrimaryNode;
 use crate::node::term::TermNode;
 use crate::node::type_::TypeBaseNode;
+use crate::node::type_::TypeNode;
 use crate::node::unary::SuffixOp;
 use crate::node::unary::UnaryNode;
+use crate::resolve::type_check::{
+    expand, integer_promotion, is_array, is_integer, is_pointer, is_struct_or_union, is_void,
+    pointee, pointer_to, type_name, type_of_expr, type_of_primary, type_of_term, type_of_unary,
+    usual_arithmetic_conversion,
+};
+use crate::resolve::variable_scope::{eval_const_primary, Entity};
 
 use crate::node::{expr::ExprNode, stmt::StmtNode};
 
+use super::layout::{member_offset, size_of};
 use super::Op;
-use super::{Expr, Label, Stmt};
+use super::{Expr, JumpEntry, Label, Stmt, Type};
 
 pub fn address_of(expr: Expr) -> Expr {
     match expr {
-        Expr::Var(name, entity) => Expr::Addr(name, entity),
-        Expr::Mem(expr) => *expr,
+        Expr::Var(_, name) => Expr::Addr(Type::U64, name),
+        Expr::Mem(_, expr) => *expr,
         e => panic!("{:?} is not have a address", e),
     }
 }
 
+/// An integer constant of the given type.
+pub fn int_const(_type: Type, value: i64) -> Expr {
+    Expr::Const(_type, Const::Int(_type.normalize(value)))
+}
+
+/// Convert `expr` to `_type`. Constants are converted in place.
+pub fn convert(expr: Expr, _type: Type) -> Expr {
+    let op = match &expr {
+        e if e._type() == _type => return expr,
+        Expr::Const(_, Const::Int(value)) => return int_const(_type, *value),
+        e if e._type().is_signed() => Op::SCast,
+        _ => Op::UCast,
+    };
+    Expr::Uni(_type, op, Box::new(expr))
+}
+
+/// Arrays are converted to the address of their first element when they are used as a value.
+fn decay_value(expr: Expr, _type: &TypeNode) -> Expr {
+    if is_array(_type) {
+        address_of(expr)
+    } else {
+        expr
+    }
+}
+
+/// Multiply the integer operand of pointer arithmetic by the size of the pointee.
+fn scale(expr: Expr, element: &TypeNode, info: &mut IRInfo) -> Result<Expr, GenError> {
+    let size = size_of(element, &info.current_scope())?;
+    let expr = convert(expr, Type::I64);
+    if size == 1 {
+        Ok(expr)
+    } else {
+        Ok(Expr::Bin(
+            Type::I64,
+            Op::Mul,
+            Box::new(expr),
+            Box::new(int_const(Type::I64, size)),
+        ))
+    }
+}
+
 pub fn transform_expr(expr: &ExprNode, info: &mut IRInfo) -> Result<(Vec<Stmt>, Expr), GenError> {
     let ret = match expr {
-        ExprNode::Term(term) => transform_term(term, info),
-        ExprNode::BinaryOp { op, lhs, rhs } => {
+        ExprNode::Term(term, _) => transform_term(term, info),
+        ExprNode::BinaryOp { op, lhs, rhs, .. } => {
             transform_binaryop(op, lhs.as_ref(), rhs.as_ref(), info)
         }
-        ExprNode::Assign { term, expr } => transform_assign(term, &expr, info),
-        ExprNode::AssignOp { op, term, expr } => transform_assignop(op, term, expr, info),
-        _ => Err(GenError {
-            message: format!("{:?} is not a constant value", expr),
-        }),
+        ExprNode::Assign { term, expr, .. } => transform_assign(term, expr, info),
+        ExprNode::AssignOp { op, term, expr, .. } => transform_assignop(op, term, expr, info),
+        ExprNode::TernaryOp { .. } => transform_ternaryop(expr, info),
     };
-    ret
+    ret.map_err(|e| e.or_span(expr.span()))
+}
+
+/// Store the address of a memory operand in a temporary so that the operand can be read and
+/// written without evaluating the address twice.
+///
+/// a[f()] += 1
+/// =>
+/// tmp = &a[f()];
+/// *tmp = *tmp + 1;
+fn address_once(target: Expr, _type: &TypeNode, stmts: &mut Vec<Stmt>, info: &mut IRInfo) -> Expr {
+    match target {
+        Expr::Mem(mem_type, addr) => {
+            let scope = info.current_scope();
+            let tmp = info.get_tmpvar_of(scope, pointer_to(_type));
+            stmts.push(Stmt::Assign(Type::U64, address_of(tmp.clone()), *addr));
+            Expr::Mem(mem_type, Box::new(tmp))
+        }
+        target => target,
+    }
+}
+
+/// Store `value` into the lvalue `target`. Structs and unions are copied byte by byte, so
+/// `value` must be an lvalue as well in that case.
+fn store(target: Expr, value: Expr, _type: &TypeNode, info: &mut IRInfo) -> Result<Stmt, GenError> {
+    if !is_struct_or_union(_type) {
+        let _type = Type::of(_type);
+        return Ok(Stmt::Assign(
+            _type,
+            address_of(target),
+            convert(value, _type),
+        ));
+    }
+    match value {
+        Expr::Var(..) | Expr::Mem(..) => Ok(Stmt::Copy {
+            dst: address_of(target),
+            src: address_of(value),
+            size: size_of(_type, &info.current_scope())?,
+        }),
+        _ => rvalue_aggregate(_type),
+    }
+}
+
+fn rvalue_aggregate<T>(_type: &TypeNode) -> Result<T, GenError> {
+    Err(GenError {
+        message: format!(
+            "{} values returned from functions are not supported yet",
+            type_name(_type)
+        ),
+        span: None,
+    })
 }
 
 pub fn transform_assign(
@@ -44,13 +143,16 @@ pub fn transform_assign(
     expr: &ExprNode,
     info: &mut IRInfo,
 ) -> Result<(Vec<Stmt>, Expr), GenError> {
+    let lhs_type = type_of_term(term, &info.current_scope())?;
+
     let mut stmts = vec![];
     let (s, t) = transform_term(term, info)?;
     stmts.extend(s);
+    let target = address_once(t, &lhs_type, &mut stmts, info);
     let (s, e) = transform_expr(expr, info)?;
     stmts.extend(s);
-    stmts.push(Stmt::Assign(address_of(t), e.clone()));
-    Ok((stmts, e))
+    stmts.push(store(target.clone(), e, &lhs_type, info)?);
+    Ok((stmts, target))
 }
 
 pub fn transform_assignop(
@@ -59,80 +161,231 @@ pub fn transform_assignop(
     expr: &ExprNode,
     info: &mut IRInfo,
 ) -> Result<(Vec<Stmt>, Expr), GenError> {
+    let scope = info.current_scope();
+    let lhs_type = type_of_term(term, &scope)?;
+    let rhs_type = type_of_expr(expr, &scope)?;
+
     let mut stmts = vec![];
     let (s, t) = transform_term(term, info)?;
     stmts.extend(s);
+    let target = address_once(t, &lhs_type, &mut stmts, info);
+
     let (s, e) = transform_expr(expr, info)?;
     stmts.extend(s);
 
-    match op {
-        AssignOp::Add => stmts.push(Stmt::Assign(
-            address_of(t.clone()),
-            Expr::Bin(Op::Add, Box::new(t), Box::new(e.clone())),
-        )),
-        e => panic!("not yet implemented {:?}", e),
-    }
-    Ok((stmts, e))
+    let op = match op {
+        AssignOp::Add => BinaryOp::Add,
+        AssignOp::Sub => BinaryOp::Sub,
+        AssignOp::Mul => BinaryOp::Mul,
+        AssignOp::Div => BinaryOp::Div,
+        AssignOp::Mod => BinaryOp::Mod,
+        AssignOp::And => BinaryOp::BitAnd,
+        AssignOp::Or => BinaryOp::BitOr,
+        AssignOp::Exor => BinaryOp::BitExOr,
+        AssignOp::Shl => BinaryOp::Shl,
+        AssignOp::Shr => BinaryOp::Shr,
+    };
+    let value = binary(&op, (target.clone(), &lhs_type), (e, &rhs_type), info)?;
+    stmts.push(Stmt::Assign(
+        Type::of(&lhs_type),
+        address_of(target.clone()),
+        value,
+    ));
+    Ok((stmts, target))
 }
 
-pub fn transform_binaryop(
+/// Lower `&&` and `||` so that `rhs` is only evaluated when it decides the result.
+fn transform_logicalop(
     op: &BinaryOp,
     lhs: &ExprNode,
     rhs: &ExprNode,
     info: &mut IRInfo,
 ) -> Result<(Vec<Stmt>, Expr), GenError> {
+    // lhs && rhs              lhs || rhs
+    // =>                      =>
+    // int tmp = 0;            int tmp = 1;
+    // if (lhs) {              if (!lhs) {
+    //  tmp = rhs != 0;         tmp = rhs != 0;
+    // }                       }
+    // tmp                     tmp
     let mut stmts = vec![];
+    let scope = info.current_scope();
+    let var = info.get_tmpvar(scope, TypeBaseNode::Int);
+    let rhs_label = info.new_label();
+    let end_label = info.new_label();
 
-    match op {
-        BinaryOp::And => {
-            // lhs && rhs
-            // =>
-            // int tmp = 0;
-            // if (lhs) {
-            //  tmp = rhs;
-            // }
-            // tmp
-            //
-
-            let scope = info.current_scope();
-            let var = info.get_tmpvar(scope, TypeBaseNode::Int);
-            stmts.push(Stmt::Assign(
-                address_of(var.clone()),
-                Expr::Const(Const::Int(0)),
-            ));
-
-            let (_stmts, expr) = transform_expr(rhs, info)?;
-            stmts.extend(_stmts);
-            let then_node = Stmt::Assign(address_of(var.clone()), expr);
-            let _stmts = gen_if_stmt_(&lhs, &then_node, &None, info)?;
-            stmts.extend(_stmts);
-
-            return Ok((stmts, var));
-        }
-        _ => {}
+    let (init, cond) = match op {
+        BinaryOp::And => (0, cjump(lhs, &rhs_label, &end_label, info)?),
+        _ => (1, cjump(lhs, &end_label, &rhs_label, info)?),
     };
+    stmts.push(Stmt::Assign(
+        Type::I32,
+        address_of(var.clone()),
+        int_const(Type::I32, init),
+    ));
+    stmts.extend(cond);
+    stmts.push(label(&rhs_label));
+    let (s, rhs) = transform_expr(rhs, info)?;
+    stmts.extend(s);
+    let zero = int_const(rhs._type(), 0);
+    stmts.push(Stmt::Assign(
+        Type::I32,
+        address_of(var.clone()),
+        Expr::Bin(Type::I32, Op::NEQ, Box::new(rhs), Box::new(zero)),
+    ));
+    stmts.push(label(&end_label));
+
+    Ok((stmts, var))
+}
+
+pub fn transform_binaryop(
+    op: &BinaryOp,
+    lhs: &ExprNode,
+    rhs: &ExprNode,
+    info: &mut IRInfo,
+) -> Result<(Vec<Stmt>, Expr), GenError> {
+    if let BinaryOp::And | BinaryOp::Or = op {
+        return transform_logicalop(op, lhs, rhs, info);
+    }
 
+    let scope = info.current_scope();
+    let (lhs_type, rhs_type) = (type_of_expr(lhs, &scope)?, type_of_expr(rhs, &scope)?);
+
+    let mut stmts = vec![];
     let (s, rhs) = transform_expr(rhs, info)?;
     stmts.extend(s);
     let (s, lhs) = transform_expr(lhs, info)?;
     stmts.extend(s);
 
-    match op {
-        BinaryOp::Add => Ok((stmts, Expr::Bin(Op::Add, Box::new(lhs), Box::new(rhs)))),
-        BinaryOp::Sub => Ok((stmts, Expr::Bin(Op::Sub, Box::new(lhs), Box::new(rhs)))),
-        BinaryOp::Mod => Ok((stmts, Expr::Bin(Op::SMod, Box::new(lhs), Box::new(rhs)))),
-        BinaryOp::Eq => Ok((stmts, Expr::Bin(Op::EQ, Box::new(lhs), Box::new(rhs)))),
-        BinaryOp::Le => Ok((stmts, Expr::Bin(Op::SLteq, Box::new(lhs), Box::new(rhs)))),
-        BinaryOp::Lt => Ok((stmts, Expr::Bin(Op::SLt, Box::new(lhs), Box::new(rhs)))),
-        e => panic!("not yet implemented {:?}", e),
+    let expr = binary(op, (lhs, &lhs_type), (rhs, &rhs_type), info)?;
+    Ok((stmts, expr))
+}
+
+/// Build `lhs op rhs` from operands of the given (decayed) types.
+///
+/// Integer operands are converted to their common type, and the integer operand of pointer
+/// arithmetic counts in elements of the pointee. Pointers compare as unsigned values.
+fn binary(
+    op: &BinaryOp,
+    (lhs, lhs_type): (Expr, &TypeNode),
+    (rhs, rhs_type): (Expr, &TypeNode),
+    info: &mut IRInfo,
+) -> Result<Expr, GenError> {
+    let bin = |_type, op, lhs, rhs| Expr::Bin(_type, op, Box::new(lhs), Box::new(rhs));
+
+    match (op, pointee(lhs_type), pointee(rhs_type)) {
+        (BinaryOp::Add | BinaryOp::Sub, Some(element), None) => {
+            let op = match op {
+                BinaryOp::Add => Op::Add,
+                _ => Op::Sub,
+            };
+            let rhs = scale(rhs, &element, info)?;
+            return Ok(bin(Type::U64, op, lhs, rhs));
+        }
+        (BinaryOp::Add, None, Some(element)) => {
+            let lhs = scale(lhs, &element, info)?;
+            return Ok(bin(Type::U64, Op::Add, lhs, rhs));
+        }
+        (BinaryOp::Sub, Some(element), Some(_)) => {
+            let size = size_of(&element, &info.current_scope())?;
+            let diff = bin(Type::I64, Op::Sub, lhs, rhs);
+            return Ok(bin(Type::I64, Op::SDiv, diff, int_const(Type::I64, size)));
+        }
+        _ => {}
+    }
+
+    // The result of a shift has the type of the promoted left operand.
+    if let BinaryOp::Shl | BinaryOp::Shr = op {
+        let _type = Type::of(&integer_promotion(lhs_type));
+        let op = match op {
+            BinaryOp::Shl => Op::BitLShift,
+            _ if _type.is_signed() => Op::ArithRShift,
+            _ => Op::BitRShift,
+        };
+        let rhs = convert(rhs, Type::of(&integer_promotion(rhs_type)));
+        return Ok(bin(_type, op, convert(lhs, _type), rhs));
+    }
+
+    let operand = if is_integer(lhs_type) && is_integer(rhs_type) {
+        Type::of(&usual_arithmetic_conversion(lhs_type, rhs_type))
+    } else {
+        Type::U64
+    };
+    let pick = |s: Op, u: Op| if operand.is_signed() { s } else { u };
+    let (op, _type) = match op {
+        BinaryOp::Mul => (Op::Mul, operand),
+        BinaryOp::Div => (pick(Op::SDiv, Op::UDiv), operand),
+        BinaryOp::Mod => (pick(Op::SMod, Op::UMod), operand),
+        BinaryOp::Add => (Op::Add, operand),
+        BinaryOp::Sub => (Op::Sub, operand),
+        BinaryOp::BitAnd => (Op::BitAnd, operand),
+        BinaryOp::BitOr => (Op::BitOr, operand),
+        BinaryOp::BitExOr => (Op::BitXor, operand),
+        BinaryOp::Ge => (pick(Op::SGteq, Op::UGteq), Type::I32),
+        BinaryOp::Le => (pick(Op::SLteq, Op::ULteq), Type::I32),
+        BinaryOp::Gt => (pick(Op::SGt, Op::UGt), Type::I32),
+        BinaryOp::Lt => (pick(Op::SLt, Op::ULt), Type::I32),
+        BinaryOp::Eq => (Op::EQ, Type::I32),
+        BinaryOp::Ne => (Op::NEQ, Type::I32),
+        BinaryOp::Shl | BinaryOp::Shr | BinaryOp::And | BinaryOp::Or => unreachable!(),
+    };
+    Ok(bin(_type, op, convert(lhs, operand), convert(rhs, operand)))
+}
+
+pub fn transform_ternaryop(
+    expr: &ExprNode,
+    info: &mut IRInfo,
+) -> Result<(Vec<Stmt>, Expr), GenError> {
+    // cond ? lhs : rhs
+    // =>
+    // if (cond) {
+    //  tmp = lhs;
+    // } else {
+    //  tmp = rhs;
+    // }
+    // tmp
+    let (cond, mhs, rhs) = match expr {
+        ExprNode::TernaryOp { lhs, mhs, rhs, .. } => (lhs, mhs, rhs),
+        _ => unreachable!(),
+    };
+    let scope = info.current_scope();
+    let _type = type_of_expr(expr, &scope)?;
+    let var = if is_void(&_type) {
+        None
+    } else {
+        Some(info.get_tmpvar_of(scope, _type.clone()))
+    };
+    let then_label = info.new_label();
+    let else_label = info.new_label();
+    let end_label = info.new_label();
+
+    let mut stmts = cjump(cond, &then_label, &else_label, info)?;
+    for (branch_label, branch) in [(&then_label, mhs), (&else_label, rhs)] {
+        stmts.push(label(branch_label));
+        let (s, value) = transform_expr(branch, info)?;
+        stmts.extend(s);
+        stmts.push(match &var {
+            Some(var) => store(var.clone(), value, &_type, info)?,
+            None => Stmt::ExprStmt(value),
+        });
+        stmts.push(jump(&end_label));
     }
+    stmts.push(label(&end_label));
+
+    Ok((stmts, var.unwrap_or(int_const(Type::I32, 0))))
 }
 
 pub fn transform_term(term: &TermNode, info: &mut IRInfo) -> Result<(Vec<Stmt>, Expr), GenError> {
     match term {
-        TermNode::Cast(_, _) => Err(GenError {
-            message: format!("{:?} is not a constant value", term),
-        })?,
+        TermNode::Cast(_type, operand) => {
+            let _type = expand(_type, &info.current_scope())?;
+            let (stmts, expr) = transform_term(operand, info)?;
+            if is_void(&_type) {
+                Ok((stmts, expr))
+            } else {
+                Ok((stmts, convert(expr, Type::of(&_type))))
+            }
+        }
         TermNode::Unary(unary) => transform_unary(unary, info),
     }
 }
@@ -141,17 +394,107 @@ pub fn transform_unary(
     unary: &UnaryNode,
     info: &mut IRInfo,
 ) -> Result<(Vec<Stmt>, Expr), GenError> {
+    // The operand is promoted to the type of the result.
+    let uni = |op: Op, term: &TermNode, info: &mut IRInfo| {
+        let _type = Type::of(&type_of_unary(unary, &info.current_scope())?);
+        let (stmts, expr) = transform_term(term, info)?;
+        let expr = match op {
+            Op::Not => expr,
+            _ => convert(expr, _type),
+        };
+        Ok((stmts, Expr::Uni(_type, op, Box::new(expr))))
+    };
     match unary {
-        UnaryNode::Primary(primary) => Ok((vec![], transform_primary(primary)?)),
-        UnaryNode::Plus(term) => transform_term(term, info),
-        UnaryNode::Minus(term) => {
+        UnaryNode::Primary(primary) => transform_primary(primary, info),
+        UnaryNode::Plus(term) => {
+            let _type = Type::of(&type_of_unary(unary, &info.current_scope())?);
             let (stmts, expr) = transform_term(term, info)?;
-            Ok((stmts, Expr::Uni(Op::UMinus, Box::new(expr))))
+            Ok((stmts, convert(expr, _type)))
         }
-        UnaryNode::Suffix(primary, suffix) => transform_suffix(primary, &suffix, info),
-        _ => Err(GenError {
-            message: format!("unary: {:?} is not a constant value", unary),
-        }),
+        UnaryNode::Minus(term) => uni(Op::UMinus, term, info),
+        UnaryNode::Tilde(term) => uni(Op::BitNot, term, info),
+        UnaryNode::Not(term) => uni(Op::Not, term, info),
+        UnaryNode::Star(term) => {
+            let _type = type_of_unary(unary, &info.current_scope())?;
+            let (stmts, expr) = transform_term(term, info)?;
+            let mem = Expr::Mem(Type::of(&_type), Box::new(expr));
+            Ok((stmts, decay_value(mem, &_type)))
+        }
+        UnaryNode::And(term) => {
+            let (stmts, expr) = transform_term(term, info)?;
+            // An array has already been converted to its address.
+            match term {
+                TermNode::Unary(operand)
+                    if is_array(&type_of_unary(operand, &info.current_scope())?) =>
+                {
+                    Ok((stmts, expr))
+                }
+                _ => Ok((stmts, address_of(expr))),
+            }
+        }
+        UnaryNode::Increment(operand) | UnaryNode::Decrement(operand) => {
+            let op = match unary {
+                UnaryNode::Increment(_) => Op::Add,
+                _ => Op::Sub,
+            };
+            let _type = type_of_unary(operand, &info.current_scope())?;
+            let (mut stmts, expr) = transform_unary(operand, info)?;
+            let expr = transform_increment(op, true, expr, &_type, &mut stmts, info)?;
+            Ok((stmts, expr))
+        }
+        UnaryNode::SizeofUnary(operand) => {
+            let scope = info.current_scope();
+            let size = size_of(&type_of_unary(operand, &scope)?, &scope)?;
+            Ok((vec![], int_const(Type::U64, size)))
+        }
+        UnaryNode::SizeofType(_type) => {
+            let size = size_of(_type, &info.current_scope())?;
+            Ok((vec![], int_const(Type::U64, size)))
+        }
+        UnaryNode::Suffix(primary, suffix) => transform_suffix(primary, suffix, info),
+    }
+}
+
+/// Lower `++x`, `--x`, `x++` and `x--`. Pointers step by the size of the pointee.
+fn transform_increment(
+    op: Op,
+    prefix: bool,
+    target: Expr,
+    _type: &TypeNode,
+    stmts: &mut Vec<Stmt>,
+    info: &mut IRInfo,
+) -> Result<Expr, GenError> {
+    let step = match pointee(_type) {
+        Some(pointee) => size_of(&pointee, &info.current_scope())?,
+        None => 1,
+    };
+    let ir_type = Type::of(_type);
+    let target = address_once(target, _type, stmts, info);
+    let update = Stmt::Assign(
+        ir_type,
+        address_of(target.clone()),
+        Expr::Bin(
+            ir_type,
+            op,
+            Box::new(target.clone()),
+            Box::new(int_const(ir_type, step)),
+        ),
+    );
+
+    if prefix {
+        stmts.push(update);
+        Ok(target)
+    } else {
+        // x++
+        // =>
+        // tmp = x;
+        // x = x + 1;
+        // tmp
+        let scope = info.current_scope();
+        let tmp = info.get_tmpvar_of(scope, _type.clone());
+        stmts.push(Stmt::Assign(ir_type, address_of(tmp.clone()), target));
+        stmts.push(update);
+        Ok(tmp)
     }
 }
 
@@ -160,91 +503,252 @@ pub fn transform_suffix(
     suffix: &SuffixOp,
     info: &mut IRInfo,
 ) -> Result<(Vec<Stmt>, Expr), GenError> {
-    match suffix {
-        SuffixOp::SuffixNone => Ok((vec![], transform_primary(primary)?)),
-        SuffixOp::CallFu(args, _, entity) => {
-            if let PrimaryNode::Identifier(name, _) = primary {
-                if let Some(entity) = entity {
-                    let mut stmts = vec![];
-                    let mut nargs = vec![];
-                    for arg in args {
-                        let (s, a) = transform_expr(arg, info)?;
-                        nargs.push(a);
-                        stmts.extend(s);
-                    }
-                    Ok((stmts, Expr::Call(name.clone(), nargs, entity.clone())))
-                } else {
-                    panic!("{name} transform_suffix is not have a function entity",);
+    let mut _type = type_of_primary(primary, &info.current_scope())?;
+    let (mut stmts, mut expr) = match suffix {
+        SuffixOp::CallFu(args, _, entity) => match (primary, entity) {
+            (PrimaryNode::Identifier(name, _), Some(entity)) => {
+                let mut stmts = vec![];
+                let mut nargs = vec![];
+                for arg in args {
+                    let (s, a) = transform_expr(arg, info)?;
+                    nargs.push(a);
+                    stmts.extend(s);
                 }
-            } else {
-                panic!("transform_suffix failed");
+                let return_type = Type::of(&call_return_type(&_type));
+                let variadic = matches!(
+                    entity,
+                    Entity::Function {
+                        params: ParamsNode::Some { variable: true, .. },
+                        ..
+                    }
+                );
+                let call = Expr::Call(return_type, name.clone(), nargs, variadic);
+                (stmts, call)
             }
+            (PrimaryNode::Identifier(name, _), None) => Err(GenError {
+                message: format!("{} is not defined", name),
+                span: None,
+            })?,
+            _ => Err(GenError {
+                message: "only named functions can be called".into(),
+                span: None,
+            })?,
+        },
+        _ => transform_primary(primary, info)?,
+    };
+
+    // The call has already been applied to the primary above.
+    let mut suffix = match suffix {
+        SuffixOp::CallFu(_, next, _) => {
+            _type = call_return_type(&_type);
+            next.as_ref()
         }
-        _ => todo!(),
+        suffix => suffix,
+    };
+
+    loop {
+        suffix = match suffix {
+            SuffixOp::SuffixNone => break,
+            SuffixOp::Increment(next) | SuffixOp::Decrement(next) => {
+                let op = match suffix {
+                    SuffixOp::Increment(_) => Op::Add,
+                    _ => Op::Sub,
+                };
+                expr = transform_increment(op, false, expr, &_type, &mut stmts, info)?;
+                next
+            }
+            SuffixOp::CallFu(..) => Err(GenError {
+                message: "only named functions can be called".into(),
+                span: None,
+            })?,
+            SuffixOp::Dot(name, next) => {
+                let base = match expr {
+                    Expr::Var(..) | Expr::Mem(..) => address_of(expr),
+                    _ => rvalue_aggregate(&_type)?,
+                };
+                (expr, _type) = member(base, &_type, name, info)?;
+                expr = decay_value(expr, &_type);
+                next
+            }
+            SuffixOp::Arrow(name, next) => {
+                let pointee = pointee(&_type).unwrap();
+                (expr, _type) = member(expr, &pointee, name, info)?;
+                expr = decay_value(expr, &_type);
+                next
+            }
+            SuffixOp::Array(idx, next) => {
+                // a[i] is *(a + i), and either operand may be the pointer.
+                let index_type = type_of_expr(idx, &info.current_scope())?;
+                let (s, index) = transform_expr(idx, info)?;
+                stmts.extend(s);
+                let (base, offset, element) = match pointee(&_type) {
+                    Some(element) => (expr, index, element),
+                    None => (index, expr, pointee(&index_type).unwrap()),
+                };
+                let offset = scale(offset, &element, info)?;
+                let addr = Expr::Bin(Type::U64, Op::Add, Box::new(base), Box::new(offset));
+                let mem = Expr::Mem(Type::of(&element), Box::new(addr));
+                expr = decay_value(mem, &element);
+                _type = element;
+                next
+            }
+        };
+    }
+
+    Ok((stmts, expr))
+}
+
+/// Access the member `name` of the struct or union of type `_type` located at `base`.
+fn member(
+    base: Expr,
+    _type: &TypeNode,
+    name: &str,
+    info: &mut IRInfo,
+) -> Result<(Expr, TypeNode), GenError> {
+    let (offset, member_type) = member_offset(_type, name, &info.current_scope())?;
+    let addr = if offset == 0 {
+        base
+    } else {
+        Expr::Bin(
+            Type::U64,
+            Op::Add,
+            Box::new(base),
+            Box::new(int_const(Type::I64, offset)),
+        )
+    };
+    Ok((
+        Expr::Mem(Type::of(&member_type), Box::new(addr)),
+        member_type,
+    ))
+}
+
+/// Returns the return type of a function or function pointer type.
+fn call_return_type(_type: &TypeNode) -> TypeNode {
+    let mut _type = _type.clone();
+    if is_pointer(&_type) {
+        _type.suffixs.pop();
     }
+    _type.suffixs.pop();
+    _type
 }
 
-pub fn transform_primary(primary: &PrimaryNode) -> Result<Expr, GenError> {
+pub fn transform_primary(
+    primary: &PrimaryNode,
+    info: &mut IRInfo,
+) -> Result<(Vec<Stmt>, Expr), GenError> {
     match primary {
-        PrimaryNode::Char(c) => Ok(Expr::Const(Const::Int(*c as i32))),
-        PrimaryNode::String(s) => Ok(Expr::Const(Const::Str(s.clone()))),
-        PrimaryNode::Integer(i) => Ok(Expr::Const(Const::Int(*i as i32))),
+        PrimaryNode::String(s) => Ok((vec![], Expr::Const(Type::U64, Const::Str(s.clone())))),
+        PrimaryNode::Char(_)
+        | PrimaryNode::Integer(_)
+        | PrimaryNode::Identifier(_, Some(Entity::Constant { .. })) => {
+            let _type = Type::of(&type_of_primary(primary, &info.current_scope())?);
+            let value = eval_const_primary(primary).unwrap();
+            Ok((vec![], int_const(_type, value)))
+        }
         PrimaryNode::Identifier(name, entity) => {
             if let Some(entity) = entity {
-                Ok(Expr::Var(name.clone(), entity.clone()))
+                match entity {
+                    Entity::Variable { _type, .. } => {
+                        let _type = expand(_type, &info.current_scope())?;
+                        let var = Expr::Var(Type::of(&_type), info.variable_name(name));
+                        Ok((vec![], decay_value(var, &_type)))
+                    }
+                    _ => Ok((vec![], Expr::Var(Type::U64, name.clone()))),
+                }
             } else {
                 Err(GenError {
                     message: format!("not found {}, this may be a compiler bug", name),
+                    span: None,
                 })
             }
         }
+        PrimaryNode::Expr(expr) => transform_expr(expr, info),
     }
 }
 
 pub fn transform_stmt(stmt: &StmtNode, info: &mut IRInfo) -> Result<Vec<Stmt>, GenError> {
+    let stmts = transform_stmt_(stmt, info);
+    match stmt.span() {
+        Some(span) => stmts.map_err(|e| e.or_span(span)),
+        None => stmts,
+    }
+}
+
+fn transform_stmt_(stmt: &StmtNode, info: &mut IRInfo) -> Result<Vec<Stmt>, GenError> {
     let stmts = match stmt {
-        StmtNode::If { cond, then, _else } => gen_if_stmt(cond, then, _else, info)?,
+        StmtNode::If {
+            cond, then, _else, ..
+        } => gen_if_stmt(cond, then, _else, info)?,
         StmtNode::Expr(expr) => {
             let (mut stmts, expr) = transform_expr(expr, info)?;
             stmts.push(Stmt::ExprStmt(expr));
             stmts
         }
-        StmtNode::Return { expr } => _return(expr, info)?,
-        StmtNode::Block { stmts } => {
+        StmtNode::Return { expr, .. } => _return(expr, info)?,
+        StmtNode::Block { stmts, .. } => {
+            info.push_block();
             let mut ret = vec![];
             for stmt in stmts {
                 ret.extend(transform_stmt(stmt, info)?);
             }
+            info.pop_block();
             ret
         }
-        StmtNode::While { cond, stmt } => gen_while_stmt(cond, stmt.as_ref(), info)?,
+        StmtNode::While { cond, stmt, .. } => gen_while_stmt(cond, stmt.as_ref(), info)?,
+        StmtNode::DoWhile { cond, stmt, .. } => gen_dowhile_stmt(cond, stmt.as_ref(), info)?,
+        StmtNode::For {
+            init,
+            cond,
+            term,
+            stmt,
+            ..
+        } => gen_for_stmt(init, cond, term, stmt.as_ref(), info)?,
+        StmtNode::Switch {
+            cond,
+            cases,
+            default,
+            ..
+        } => gen_switch_stmt(cond, cases, default, info)?,
+        StmtNode::Break { .. } => vec![jump(&info.break_label()?)],
+        StmtNode::Continue { .. } => vec![jump(&info.continue_label()?)],
         StmtNode::DefVars(defvars) => gen_defvars_stmt(defvars, info)?,
-        e => panic!("transform_stmt: {:?}", e),
+        StmtNode::Label {
+            label: name, stmt, ..
+        } => {
+            let mut stmts = vec![label(&Label(name.clone()))];
+            stmts.extend(transform_stmt(stmt, info)?);
+            stmts
+        }
+        StmtNode::Goto { label, .. } => vec![jump(&Label(label.clone()))],
+        StmtNode::None => vec![],
     };
 
     Ok(stmts)
 }
 
+/// Transform a statement that has a scope of its own, such as the body of a loop.
+fn transform_scoped_stmt(stmt: &StmtNode, info: &mut IRInfo) -> Result<Vec<Stmt>, GenError> {
+    info.push_block();
+    let stmts = transform_stmt(stmt, info);
+    info.pop_block();
+    stmts
+}
+
 pub fn gen_defvars_stmt(defvars: &DefVars, info: &mut IRInfo) -> Result<Vec<Stmt>, GenError> {
     let mut stmts = vec![];
 
     for var in defvars.vars.iter() {
         match var {
-            Var::Uninit { .. } => {}
+            Var::Uninit { name } => {
+                info.declare_local(name, &defvars._type);
+            }
             Var::Init { name, expr } => {
+                // The initializer still sees the variables the declaration shadows.
                 let (mut _stmts, expr) = transform_expr(expr, info)?;
-                _stmts.push(Stmt::Assign(
-                    address_of(Expr::Var(
-                        name.clone(),
-                        info.current_scope()
-                            .entities
-                            .borrow()
-                            .get(name)
-                            .unwrap()
-                            .clone(),
-                    )),
-                    expr,
-                ));
+                let name = info.declare_local(name, &defvars._type);
+                let _type = expand(&defvars._type, &info.current_scope())?;
+                let var = Expr::Var(Type::of(&_type), name);
+                _stmts.push(store(var, expr, &_type, info)?);
                 stmts.extend(_stmts);
             }
         }
@@ -304,72 +808,166 @@ pub fn gen_if_stmt(
     if let StmtNode::None = else_node {
         ir.extend(cjump(cond, &then_label, &end_label, info)?);
         ir.push(label(&then_label));
-        ir.extend(transform_stmt(then_node, info)?);
+        ir.extend(transform_scoped_stmt(then_node, info)?);
         ir.push(label(&end_label));
     } else {
         ir.extend(cjump(cond, &then_label, &else_label, info)?);
         ir.push(label(&then_label));
-        ir.extend(transform_stmt(then_node, info)?);
+        ir.extend(transform_scoped_stmt(then_node, info)?);
         ir.push(jump(&end_label));
         ir.push(label(&else_label));
-        ir.extend(transform_stmt(else_node, info)?);
+        ir.extend(transform_scoped_stmt(else_node, info)?);
         ir.push(label(&end_label));
     }
 
     Ok(ir)
 }
 
-pub fn gen_if_stmt_(
+pub fn gen_while_stmt(
     cond: &ExprNode,
-    then_node: &Stmt,
-    else_node: &Option<Stmt>,
+    stmt: &StmtNode,
     info: &mut IRInfo,
 ) -> Result<Vec<Stmt>, GenError> {
     let mut ir = vec![];
-    let then_label = info.new_label();
-    let else_label = info.new_label();
+    let beg_label = info.new_label();
+    let body_label = info.new_label();
     let end_label = info.new_label();
 
-    if let Some(else_node) = else_node {
-        ir.extend(cjump(cond, &then_label, &else_label, info)?);
-        ir.push(label(&then_label));
-        ir.push(then_node.clone());
-        ir.push(jump(&end_label));
-        ir.push(label(&else_label));
-        ir.push(else_node.clone());
-        ir.push(label(&end_label));
-    } else {
-        ir.extend(cjump(cond, &then_label, &end_label, info)?);
-        ir.push(label(&then_label));
-        ir.push(then_node.clone());
-        ir.push(label(&end_label));
-    }
+    ir.push(label(&beg_label));
+    ir.extend(cjump(cond, &body_label, &end_label, info)?);
+    ir.push(label(&body_label));
+    info.push_continue(&beg_label);
+    info.push_break(&end_label);
+
+    ir.extend(transform_scoped_stmt(stmt, info)?);
+
+    info.pop_continue();
+    info.pop_break();
+    ir.push(jump(&beg_label));
+    ir.push(label(&end_label));
 
     Ok(ir)
 }
 
-pub fn gen_while_stmt(
+pub fn gen_dowhile_stmt(
+    cond: &ExprNode,
+    stmt: &StmtNode,
+    info: &mut IRInfo,
+) -> Result<Vec<Stmt>, GenError> {
+    let mut ir = vec![];
+    let beg_label = info.new_label();
+    let cont_label = info.new_label();
+    let end_label = info.new_label();
+
+    ir.push(label(&beg_label));
+    info.push_continue(&cont_label);
+    info.push_break(&end_label);
+
+    ir.extend(transform_scoped_stmt(stmt, info)?);
+
+    info.pop_continue();
+    info.pop_break();
+    ir.push(label(&cont_label));
+    ir.extend(cjump(cond, &beg_label, &end_label, info)?);
+    ir.push(label(&end_label));
+
+    Ok(ir)
+}
+
+pub fn gen_for_stmt(
+    init: &ExprNode,
     cond: &ExprNode,
+    term: &ExprNode,
     stmt: &StmtNode,
     info: &mut IRInfo,
 ) -> Result<Vec<Stmt>, GenError> {
     let mut ir = vec![];
     let beg_label = info.new_label();
     let body_label = info.new_label();
+    let cont_label = info.new_label();
     let end_label = info.new_label();
 
+    let (stmts, init) = transform_expr(init, info)?;
+    ir.extend(stmts);
+    ir.push(Stmt::ExprStmt(init));
     ir.push(label(&beg_label));
     ir.extend(cjump(cond, &body_label, &end_label, info)?);
     ir.push(label(&body_label));
-    info.push_continue(&beg_label);
+    info.push_continue(&cont_label);
     info.push_break(&end_label);
 
-    ir.extend(transform_stmt(stmt, info)?);
+    ir.extend(transform_scoped_stmt(stmt, info)?);
 
     info.pop_continue();
     info.pop_break();
+    ir.push(label(&cont_label));
+    let (stmts, term) = transform_expr(term, info)?;
+    ir.extend(stmts);
+    ir.push(Stmt::ExprStmt(term));
     ir.push(jump(&beg_label));
     ir.push(label(&end_label));
 
     Ok(ir)
 }
+
+pub fn gen_switch_stmt(
+    cond: &ExprNode,
+    cases: &[(Vec<PrimaryNode>, Vec<StmtNode>)],
+    default: &Option<Vec<StmtNode>>,
+    info: &mut IRInfo,
+) -> Result<Vec<Stmt>, GenError> {
+    // Case values are compared with the promoted condition as it is held in a register.
+    let scope = info.current_scope();
+    let _type = Type::of(&integer_promotion(&type_of_expr(cond, &scope)?));
+
+    let mut ir = vec![];
+    let end_label = info.new_label();
+    let (stmts, cond) = transform_expr(cond, info)?;
+    let cond = convert(cond, _type);
+    ir.extend(stmts);
+
+    let mut entries = vec![];
+    let mut bodies = vec![];
+    for (values, stmts) in cases {
+        let body_label = info.new_label();
+        for value in values {
+            let value = eval_const_primary(value).ok_or_else(|| GenError {
+                message: "case label does not reduce to an integer constant".into(),
+                span: None,
+            })?;
+            entries.push(JumpEntry {
+                value: _type.normalize(value),
+                label: body_label.clone(),
+            });
+        }
+        bodies.push((body_label, stmts));
+    }
+    let default_label = match default {
+        Some(stmts) => {
+            let default_label = info.new_label();
+            bodies.push((default_label.clone(), stmts));
+            default_label
+        }
+        None => end_label.clone(),
+    };
+
+    ir.push(Stmt::Switch {
+        cond,
+        cases: entries,
+        default_label,
+    });
+    info.push_break(&end_label);
+    // All clauses share the scope of the switch body.
+    info.push_block();
+    for (body_label, stmts) in bodies {
+        ir.push(label(&body_label));
+        for stmt in stmts {
+            ir.extend(transform_stmt(stmt, info)?);
+        }
+    }
+    info.pop_block();
+    info.pop_break();
+    ir.push(label(&end_label));
+
+    Ok(ir)
+}
@@ -6,21 +6,27 @@ use crate::node::{
     unary::UnaryNode,
 };
 
+use crate::diagnostic::Diagnostic;
+use crate::resolve::variable_scope::{Entity, Scope};
+use std::rc::Rc;
+
+use super::layout::layout_of;
 use super::{Const, DefinedVar, GenError};
 
-pub fn gen_def_var(var: &DefVars) -> Result<Vec<DefinedVar>, GenError> {
+pub fn gen_def_var(var: &DefVars, scope: &Rc<Scope>) -> Result<Vec<DefinedVar>, GenError> {
+    let layout = layout_of(&var._type, scope)?;
     let mut dvars = vec![];
     for v in var.vars.iter() {
         match v {
             Var::Init { name, expr } => dvars.push(DefinedVar {
                 name: name.clone(),
-                _type: var._type.clone(),
+                layout,
                 is_private: var.is_static,
                 init: Some(get_const_expr(expr)?),
             }),
             Var::Uninit { name } => dvars.push(DefinedVar {
                 name: name.clone(),
-                _type: var._type.clone(),
+                layout,
                 is_private: var.is_static,
                 init: None,
             }),
@@ -32,17 +38,20 @@ pub fn gen_def_var(var: &DefVars) -> Result<Vec<DefinedVar>, GenError> {
 
 pub fn get_const_expr(expr: &ExprNode) -> Result<Const, GenError> {
     match expr {
-        ExprNode::Term(term) => get_const_term(term),
+        ExprNode::Term(term, _) => get_const_term(term),
         _ => Err(GenError {
-            message: format!("{:?} is not a constant value", expr),
+            message: "not a constant value".into(),
+            span: None,
         }),
     }
+    .map_err(|e| e.or_span(expr.span()))
 }
 
 pub fn get_const_term(term: &TermNode) -> Result<Const, GenError> {
     match term {
         TermNode::Cast(_, _) => Err(GenError {
-            message: format!("{:?} is not a constant value", term),
+            message: "not a constant value".into(),
+            span: None,
         }),
         TermNode::Unary(unary) => get_const_unary(unary),
     }
@@ -51,19 +60,38 @@ pub fn get_const_term(term: &TermNode) -> Result<Const, GenError> {
 pub fn get_const_unary(unary: &UnaryNode) -> Result<Const, GenError> {
     match unary {
         UnaryNode::Primary(primary) => get_const_primary(primary),
+        UnaryNode::Plus(term) => get_const_term(term),
+        UnaryNode::Minus(term) => match get_const_term(term)? {
+            Const::Int(i) => Ok(Const::Int(i.wrapping_neg())),
+            c => Err(GenError {
+                message: format!("{:?} cannot be negated", c),
+                span: None,
+            }),
+        },
+        UnaryNode::Tilde(term) => match get_const_term(term)? {
+            Const::Int(i) => Ok(Const::Int(!i)),
+            c => Err(GenError {
+                message: format!("{:?} cannot be inverted", c),
+                span: None,
+            }),
+        },
         _ => Err(GenError {
-            message: format!("{:?} is not a constant value", unary),
+            message: "not a constant value".into(),
+            span: None,
         }),
     }
 }
 
 pub fn get_const_primary(primary: &PrimaryNode) -> Result<Const, GenError> {
     match primary {
-        PrimaryNode::Char(c) => Ok(Const::Int(*c as i32)),
+        PrimaryNode::Char(c) => Ok(Const::Int(*c as i64)),
         PrimaryNode::String(s) => Ok(Const::Str(s.clone())),
-        PrimaryNode::Integer(i) => Ok(Const::Int(*i as i32)),
+        PrimaryNode::Integer(i) => Ok(Const::Int(*i)),
+        PrimaryNode::Expr(expr) => get_const_expr(expr),
+        PrimaryNode::Identifier(_, Some(Entity::Constant { value, .. })) => Ok(Const::Int(*value)),
         PrimaryNode::Identifier(_, _) => Err(GenError {
-            message: format!("{:?} is not a constant value", primary),
+            message: "not a constant value".into(),
+            span: None,
         }),
     }
 }
@@ -0,0 +1,247 @@
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+
+use super::cfg::{jump_targets, Cfg};
+use super::{DefinedFun, GenError, Label, Stmt, IR};
+
+/// Problem in the control flow of a function.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    UndefinedLabel(Label),
+    DuplicateLabel(Label),
+    /// The block with the given index in the CFG cannot be reached from the entry.
+    UnreachableBlock(usize),
+    /// Control can fall off the end of a function that returns a value.
+    MissingReturn,
+}
+
+impl Problem {
+    /// Returns whether the function cannot be compiled. Unreachable blocks and missing returns
+    /// are left by valid programs, for example after a `return` inside a loop.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            Problem::UndefinedLabel(_) | Problem::DuplicateLabel(_)
+        )
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Problem::UndefinedLabel(label) => write!(f, "jump to undefined label {}", label),
+            Problem::DuplicateLabel(label) => {
+                write!(f, "label {} is defined more than once", label)
+            }
+            Problem::UnreachableBlock(i) => write!(f, "block {} is unreachable", i),
+            Problem::MissingReturn => write!(f, "control reaches the end of a non-void function"),
+        }
+    }
+}
+
+/// Returns the problems in the control flow of `fun`.
+pub fn verify_fun(fun: &DefinedFun) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    let mut labels = HashSet::new();
+    for stmt in fun.body.iter() {
+        if let Stmt::Label(label) = stmt {
+            if !labels.insert(&label.0) {
+                problems.push(Problem::DuplicateLabel(label.clone()));
+            }
+        }
+    }
+    for stmt in fun.body.iter() {
+        for label in jump_targets(stmt) {
+            if !labels.contains(&label.0) {
+                problems.push(Problem::UndefinedLabel(label.clone()));
+            }
+        }
+    }
+
+    let cfg = Cfg::new(&fun.body);
+    let reachable = cfg.reachable();
+    for (i, reachable) in reachable.iter().enumerate() {
+        if !reachable {
+            problems.push(Problem::UnreachableBlock(i));
+        }
+    }
+    let last = cfg.blocks.len() - 1;
+    if fun.ret.is_some() && reachable[last] && cfg.blocks[last].falls_through() {
+        problems.push(Problem::MissingReturn);
+    }
+
+    problems
+}
+
+/// Returns the problems in `fun` that do not prevent it from being compiled but are worth
+/// reporting. Unreachable blocks holding only labels and jumps are left by the IR generation
+/// itself, for example after an `if` whose branches both return, and are not reported.
+pub fn warnings(fun: &DefinedFun) -> Vec<Problem> {
+    let cfg = Cfg::new(&fun.body);
+    verify_fun(fun)
+        .into_iter()
+        .filter(|problem| match problem {
+            Problem::UnreachableBlock(i) => cfg.blocks[*i]
+                .stmts
+                .iter()
+                .any(|stmt| !matches!(stmt, Stmt::Label(_) | Stmt::Jump { .. })),
+            problem => !problem.is_error(),
+        })
+        .collect()
+}
+
+/// Returns the problems in the control flow of every function in `ir`, with the name of the
+/// function.
+pub fn verify(ir: &IR) -> Vec<(String, Problem)> {
+    ir.fun
+        .iter()
+        .flat_map(|fun| {
+            verify_fun(fun)
+                .into_iter()
+                .map(|problem| (fun.name.clone(), problem))
+        })
+        .collect()
+}
+
+/// Fail with the first problem in `fun` that prevents it from being compiled.
+pub fn check_fun(fun: &DefinedFun) -> Result<(), GenError> {
+    match verify_fun(fun).into_iter().find(Problem::is_error) {
+        Some(problem) => Err(GenError {
+            message: format!("{} in {}", problem, fun.name),
+            span: None,
+        }),
+        None => Ok(()),
+    }
+}
+
+#[test]
+fn test_verify() {
+    let ir = super::text::parse(
+        r#"
+        fun ok(i32 n) -> i32 {
+            cjump (var i32 n), .L1, .L2
+        .L1:
+            return (const i32 1)
+        .L2:
+            return (const i32 2)
+        }
+
+        fun void_end(i32 n) {
+            eval (var i32 n)
+        }
+
+        fun broken(i32 n) -> i32 {
+            jump .L1
+            eval (var i32 n)
+        .L1:
+            cjump (var i32 n), .L1, nowhere
+        .L1:
+            switch (var i32 n) [1: .L2] default .L3
+        .L3:
+            eval (var i32 n)
+        }
+
+        fun loop_forever() -> i32 {
+        top:
+            jump top
+        }
+
+        fun falls_off(i32 n) -> i32 {
+            cjump (var i32 n), .L1, .L2
+        .L1:
+            return (var i32 n)
+        .L2:
+        }
+        "#,
+    )
+    .unwrap();
+
+    let problems = verify(&ir);
+    let problems = problems
+        .iter()
+        .map(|(fun, problem)| (fun.as_str(), problem.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        problems,
+        vec![
+            ("broken", "label .L1 is defined more than once".to_string()),
+            ("broken", "jump to undefined label nowhere".to_string()),
+            ("broken", "jump to undefined label .L2".to_string()),
+            ("broken", "block 1 is unreachable".to_string()),
+            ("broken", "block 3 is unreachable".to_string()),
+            ("broken", "block 4 is unreachable".to_string()),
+            (
+                "falls_off",
+                "control reaches the end of a non-void function".to_string()
+            ),
+        ]
+    );
+
+    assert!(check_fun(&ir.fun[0]).is_ok());
+    assert!(check_fun(&ir.fun[4]).is_ok());
+    assert_eq!(
+        check_fun(&ir.fun[2]).unwrap_err().message,
+        "label .L1 is defined more than once in broken"
+    );
+}
+
+#[test]
+fn test_verify_gen_ir() {
+    let ir = crate::gen::ir_of_source(
+        "<source>",
+        r#"
+        int sign(int n) {
+            if (n < 0) {
+                return -1;
+            } else {
+                return 1;
+            }
+        }
+
+        int count(int n) {
+            while (1) {
+                if (n > 10) {
+                    return n;
+                }
+                n++;
+            }
+        }
+
+        void jump(int n) {
+        again:
+            switch (n) {
+                case 1: n--; goto again;
+                default: break;
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    assert!(verify(&ir)
+        .iter()
+        .all(|(_, problem)| matches!(problem, Problem::UnreachableBlock(_))));
+}
+
+#[test]
+fn test_gen_ir_warnings() {
+    let source = "long missing(long n) {\n    if (n) {\n        return 1;\n    }\n}\n\
+                  long sign(long n) {\n    if (n < 0) {\n        return -1;\n    } else {\n        return 1;\n    }\n}\n\
+                  long dead(long n) {\n    return n;\n    n = n + 1;\n    return n;\n}\n";
+    let (_, warnings) = crate::gen::ir_of_source_with_warnings("main.cb", source).unwrap();
+    assert_eq!(
+        warnings,
+        vec![
+            "warning: control reaches the end of a non-void function in missing\n \
+             --> main.cb:1:1\n  \
+             |\n\
+             1 | long missing(long n) {\n  \
+             | ^^^^^^^^^^^^^^^^^^^^^^\n",
+            "warning: block 1 is unreachable in dead\n  \
+             --> main.cb:13:1\n   \
+             |\n\
+             13 | long dead(long n) {\n   \
+             | ^^^^^^^^^^^^^^^^^^^\n",
+        ]
+    );
+}
@@ -6,7 +6,8 @@ extern crate pest_derive;
 #[grammar = "scanner.pest"]
 pub struct CBCScanner;
 
+pub mod diagnostic;
+pub mod gen;
 pub mod ir;
 pub mod node;
 pub mod resolve;
-pub mod gen;
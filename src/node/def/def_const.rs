@@ -6,6 +6,7 @@ use crate::Rule;
 use pest::iterators::Pair;
 
 pub fn def_const(pair: Pair<Rule>) -> Result<DefNode, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner();
 
     pairs.next().unwrap(); // const
@@ -14,7 +15,12 @@ pub fn def_const(pair: Pair<Rule>) -> Result<DefNode, NodeError> {
     pairs.next().unwrap(); // =
     let expr = parse_expr_node(pairs.next().unwrap())?;
 
-    Ok(DefNode::Const { _type, name, expr })
+    Ok(DefNode::Const {
+        _type,
+        name,
+        expr,
+        span,
+    })
 }
 
 #[test]
@@ -0,0 +1,59 @@
+use super::DefNode;
+use crate::node::expr::parse_expr_node;
+use crate::node::NodeError;
+use crate::Rule;
+use pest::iterators::Pair;
+
+pub fn def_enum(pair: Pair<Rule>) -> Result<DefNode, NodeError> {
+    let span = pair.as_span().into();
+    let mut pairs = pair.into_inner();
+
+    pairs.next().unwrap(); // enum
+    let name = pairs.next().unwrap().as_str().into();
+    let mut enumerators = vec![];
+    for pair in pairs {
+        let mut pairs = pair.into_inner();
+        let ident = pairs.next().unwrap().as_str().into();
+        let value = match pairs.nth(1) {
+            Some(expr) => Some(parse_expr_node(expr)?),
+            None => None,
+        };
+        enumerators.push((ident, value));
+    }
+
+    Ok(DefNode::Enum {
+        name,
+        enumerators,
+        span,
+    })
+}
+
+#[test]
+fn test_enum() {
+    use crate::node::def::parse_topdef_node;
+    use crate::node::Node;
+    use crate::CBCScanner;
+    use pest::Parser;
+
+    let node = parse_topdef_node(
+        CBCScanner::parse(Rule::TOP_DEF, "enum Color { Red, Green = 5, Blue, }")
+            .unwrap()
+            .next()
+            .unwrap(),
+    )
+    .unwrap();
+    match node {
+        Node::Def(def) => match *def {
+            DefNode::Enum {
+                name, enumerators, ..
+            } => {
+                assert_eq!(name, "Color");
+                assert_eq!(enumerators.len(), 3);
+                assert!(enumerators[0].1.is_none());
+                assert!(enumerators[1].1.is_some());
+            }
+            _ => panic!("not an enum"),
+        },
+        _ => panic!("not a definition"),
+    }
+}
@@ -1,4 +1,5 @@
 use super::DefNode;
+use crate::diagnostic::Span;
 use crate::node::param::parse_params_node;
 use crate::node::param::ParamsNode;
 use crate::node::stmt::block::parse_block_stmts;
@@ -19,9 +20,11 @@ pub struct DefFun {
     pub params: ParamsNode,
     pub block: Vec<StmtNode>,
     pub scope: Option<Rc<Scope>>,
+    pub span: Span,
 }
 
 pub fn def_fun(pair: Pair<Rule>) -> Result<DefNode, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner();
 
     let is_static = pairs.next().unwrap().into_inner().count() > 0;
@@ -37,6 +40,7 @@ pub fn def_fun(pair: Pair<Rule>) -> Result<DefNode, NodeError> {
         params,
         block,
         scope: None,
+        span,
     }))
 }
 
@@ -4,13 +4,18 @@ use crate::Rule;
 use pest::iterators::Pair;
 
 pub fn def_struct(pair: Pair<Rule>) -> Result<DefNode, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner();
 
     pairs.next().unwrap(); // struct
     let name = pairs.next().unwrap().as_str().into();
     let member_list = parse_member_list(pairs.next().unwrap())?;
 
-    Ok(DefNode::Struct { name, member_list })
+    Ok(DefNode::Struct {
+        name,
+        member_list,
+        span,
+    })
 }
 
 #[test]
@@ -5,13 +5,14 @@ use crate::Rule;
 use pest::iterators::Pair;
 
 pub fn def_type(pair: Pair<Rule>) -> Result<DefNode, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner();
 
     pairs.next().unwrap(); // typedef
     let _type = parse_type_node(pairs.next().unwrap())?;
     let ident = pairs.next().unwrap().as_str().into();
 
-    Ok(DefNode::Type { _type, ident })
+    Ok(DefNode::Type { _type, ident, span })
 }
 
 #[test]
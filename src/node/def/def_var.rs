@@ -8,6 +8,7 @@ pub struct DefVars {
     pub _type: TypeNode,
     pub is_static: bool,
     pub vars: Vec<Var>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -17,6 +18,7 @@ pub enum Var {
 }
 
 pub fn parse_def_vars(pair: Pair<Rule>) -> Result<DefVars, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner().peekable();
     let is_static = pairs.next().unwrap().into_inner().next().is_some();
     let _type = parse_type_node(pairs.next().unwrap())?;
@@ -32,6 +34,7 @@ pub fn parse_def_vars(pair: Pair<Rule>) -> Result<DefVars, NodeError> {
         _type,
         is_static,
         vars,
+        span,
     })
 }
 
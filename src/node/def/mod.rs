@@ -1,5 +1,6 @@
 use self::{
     def_const::def_const,
+    def_enum::def_enum,
     def_fun::{def_fun, DefFun},
     def_struct::def_struct,
     def_type::def_type,
@@ -11,10 +12,12 @@ use super::{
     type_::{parse_type_node, TypeNode},
     Node, NodeError,
 };
+use crate::diagnostic::Span;
 use crate::Rule;
 use pest::iterators::Pair;
 
 pub mod def_const;
+pub mod def_enum;
 pub mod def_fun;
 pub mod def_struct;
 pub mod def_type;
@@ -29,19 +32,42 @@ pub enum DefNode {
         _type: TypeNode,
         name: String,
         expr: ExprNode,
+        span: Span,
     },
     Type {
         _type: TypeNode,
         ident: String,
+        span: Span,
     },
     Struct {
         name: String,
         member_list: Vec<Member>,
+        span: Span,
     },
     Union {
         name: String,
         member_list: Vec<Member>,
+        span: Span,
     },
+    Enum {
+        name: String,
+        enumerators: Vec<(String, Option<ExprNode>)>,
+        span: Span,
+    },
+}
+
+impl DefNode {
+    pub fn span(&self) -> Span {
+        match self {
+            DefNode::Vars(vars) => vars.span,
+            DefNode::Fun(fun) => fun.span,
+            DefNode::Const { span, .. }
+            | DefNode::Type { span, .. }
+            | DefNode::Struct { span, .. }
+            | DefNode::Union { span, .. }
+            | DefNode::Enum { span, .. } => *span,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -51,10 +77,10 @@ pub struct Member {
 }
 
 pub fn parse_member_list(pair: Pair<Rule>) -> Result<Vec<Member>, NodeError> {
-    let mut pairs = pair.into_inner();
+    let pairs = pair.into_inner();
     let mut member_list = vec![];
 
-    while let Some(pair) = pairs.next() {
+    for pair in pairs {
         let mut pairs = pair.into_inner();
         let _type = parse_type_node(pairs.next().unwrap())?;
         let name = pairs.next().unwrap().as_str().into();
@@ -91,6 +117,7 @@ pub fn parse_topdef_node(pair: Pair<Rule>) -> Result<Node, NodeError> {
         Rule::DEF_TYPE => def_type(pairs.next().unwrap())?,
         Rule::DEF_STRUCT => def_struct(pairs.next().unwrap())?,
         Rule::DEF_UNION => def_union(pairs.next().unwrap())?,
+        Rule::DEF_ENUM => def_enum(pairs.next().unwrap())?,
         e => panic!("{:?}", e),
     };
 
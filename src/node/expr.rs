@@ -45,29 +45,54 @@ pub enum AssignOp {
 
 #[derive(Debug, Clone)]
 pub enum ExprNode {
-    Term(TermNode),
+    Term(TermNode, Span),
     Assign {
         term: TermNode,
         expr: Box<ExprNode>,
+        span: Span,
     },
     AssignOp {
         op: AssignOp,
         term: TermNode,
         expr: Box<ExprNode>,
+        span: Span,
     },
     BinaryOp {
         op: BinaryOp,
         lhs: Box<ExprNode>,
         rhs: Box<ExprNode>,
+        span: Span,
     },
     TernaryOp {
         op: TernaryOp,
         lhs: Box<ExprNode>,
         mhs: Box<ExprNode>,
         rhs: Box<ExprNode>,
+        span: Span,
     },
 }
 
+impl ExprNode {
+    pub fn span(&self) -> Span {
+        match self {
+            ExprNode::Term(_, span)
+            | ExprNode::Assign { span, .. }
+            | ExprNode::AssignOp { span, .. }
+            | ExprNode::BinaryOp { span, .. }
+            | ExprNode::TernaryOp { span, .. } => *span,
+        }
+    }
+
+    pub fn binary_op(op: BinaryOp, lhs: ExprNode, rhs: ExprNode) -> ExprNode {
+        ExprNode::BinaryOp {
+            op,
+            span: lhs.span().to(rhs.span()),
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        }
+    }
+}
+
 pub fn parse_expr_node(pair: Pair<Rule>) -> Result<ExprNode, NodeError> {
     debug_assert_eq!(pair.as_rule(), Rule::EXPR);
     let mut pairs = pair.into_inner().peekable();
@@ -80,12 +105,15 @@ pub fn parse_expr_node(pair: Pair<Rule>) -> Result<ExprNode, NodeError> {
 }
 
 pub fn assign_op(mut pairs: Peekable<Pairs<Rule>>) -> Result<ExprNode, NodeError> {
-    let term = parse_term_node(pairs.next().unwrap())?;
+    let pair = pairs.next().unwrap();
+    let start: Span = pair.as_span().into();
+    let term = parse_term_node(pair)?;
 
     if pairs.peek().unwrap().as_rule() == Rule::EQ {
         pairs.next();
         let expr = Box::new(expr10(&mut pairs.next().unwrap().into_inner())?);
-        return Ok(ExprNode::Assign { term, expr });
+        let span = start.to(expr.span());
+        return Ok(ExprNode::Assign { term, expr, span });
     }
 
     let op = match pairs.next().unwrap().as_str() {
@@ -102,8 +130,14 @@ pub fn assign_op(mut pairs: Peekable<Pairs<Rule>>) -> Result<ExprNode, NodeError
         _ => todo!(),
     };
     let expr = Box::new(expr10(&mut pairs.next().unwrap().into_inner())?);
-
-    Ok(ExprNode::AssignOp { op, term, expr })
+    let span = start.to(expr.span());
+
+    Ok(ExprNode::AssignOp {
+        op,
+        term,
+        expr,
+        span,
+    })
 }
 
 pub fn expr10(pairs: &mut Pairs<Rule>) -> Result<ExprNode, NodeError> {
@@ -114,15 +148,18 @@ pub fn expr10(pairs: &mut Pairs<Rule>) -> Result<ExprNode, NodeError> {
             Err(NodeError {
                 _type: NodeErrorType::Expr,
                 message: "expected '?'".into(),
+                span: Some(pair.as_span().into()),
             })?
         }
 
         let _then = expr10(&mut pairs.next().unwrap().into_inner())?;
 
-        if pairs.next().unwrap().as_rule() != Rule::COLON {
+        let pair = pairs.next().unwrap();
+        if pair.as_rule() != Rule::COLON {
             Err(NodeError {
                 _type: NodeErrorType::Expr,
                 message: "expected ':'".into(),
+                span: Some(pair.as_span().into()),
             })?
         }
 
@@ -130,6 +167,7 @@ pub fn expr10(pairs: &mut Pairs<Rule>) -> Result<ExprNode, NodeError> {
 
         Ok(ExprNode::TernaryOp {
             op: TernaryOp::If,
+            span: expr.span().to(_else.span()),
             lhs: Box::new(expr),
             mhs: Box::new(_then),
             rhs: Box::new(_else),
@@ -139,20 +177,17 @@ pub fn expr10(pairs: &mut Pairs<Rule>) -> Result<ExprNode, NodeError> {
     }
 }
 
+// Binary operators are left-associative: `a - b + c` is `(a - b) + c`.
 pub fn expr9(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
     let mut expr = expr8(pairs.next().unwrap().into_inner())?;
 
-    if let Some(pair) = pairs.next() {
-        match pair.as_rule() {
-            Rule::OOR => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::Or,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr9(pairs)?),
-                };
-            }
+    while let Some(pair) = pairs.next() {
+        let op = match pair.as_rule() {
+            Rule::OOR => BinaryOp::Or,
             _ => todo!(),
-        }
+        };
+        let rhs = expr8(pairs.next().unwrap().into_inner())?;
+        expr = ExprNode::binary_op(op, expr, rhs);
     }
 
     Ok(expr)
@@ -161,17 +196,13 @@ pub fn expr9(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
 pub fn expr8(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
     let mut expr = expr7(pairs.next().unwrap().into_inner())?;
 
-    if let Some(pair) = pairs.next() {
-        match pair.as_rule() {
-            Rule::AAND => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::And,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr8(pairs)?),
-                };
-            }
+    while let Some(pair) = pairs.next() {
+        let op = match pair.as_rule() {
+            Rule::AAND => BinaryOp::And,
             _ => todo!(),
-        }
+        };
+        let rhs = expr7(pairs.next().unwrap().into_inner())?;
+        expr = ExprNode::binary_op(op, expr, rhs);
     }
 
     Ok(expr)
@@ -180,52 +211,18 @@ pub fn expr8(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
 pub fn expr7(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
     let mut expr = expr6(pairs.next().unwrap().into_inner())?;
 
-    if let Some(pair) = pairs.next() {
-        match pair.as_rule() {
-            Rule::GE => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::Ge,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr7(pairs)?),
-                };
-            }
-            Rule::LE => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::Le,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr7(pairs)?),
-                };
-            }
-            Rule::GT => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::Gt,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr7(pairs)?),
-                };
-            }
-            Rule::LT => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::Lt,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr7(pairs)?),
-                };
-            }
-            Rule::EEQ => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::Eq,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr7(pairs)?),
-                };
-            }
-            Rule::NE => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::Ne,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr7(pairs)?),
-                };
-            }
+    while let Some(pair) = pairs.next() {
+        let op = match pair.as_rule() {
+            Rule::GE => BinaryOp::Ge,
+            Rule::LE => BinaryOp::Le,
+            Rule::GT => BinaryOp::Gt,
+            Rule::LT => BinaryOp::Lt,
+            Rule::EEQ => BinaryOp::Eq,
+            Rule::NE => BinaryOp::Ne,
             _ => todo!(),
-        }
+        };
+        let rhs = expr6(pairs.next().unwrap().into_inner())?;
+        expr = ExprNode::binary_op(op, expr, rhs);
     }
 
     Ok(expr)
@@ -234,17 +231,13 @@ pub fn expr7(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
 pub fn expr6(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
     let mut expr = expr5(pairs.next().unwrap().into_inner())?;
 
-    if let Some(pair) = pairs.next() {
-        match pair.as_rule() {
-            Rule::OR => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::BitOr,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr6(pairs)?),
-                };
-            }
+    while let Some(pair) = pairs.next() {
+        let op = match pair.as_rule() {
+            Rule::OR => BinaryOp::BitOr,
             _ => todo!(),
-        }
+        };
+        let rhs = expr5(pairs.next().unwrap().into_inner())?;
+        expr = ExprNode::binary_op(op, expr, rhs);
     }
 
     Ok(expr)
@@ -253,17 +246,13 @@ pub fn expr6(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
 pub fn expr5(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
     let mut expr = expr4(pairs.next().unwrap().into_inner())?;
 
-    if let Some(pair) = pairs.next() {
-        match pair.as_rule() {
-            Rule::CARET => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::BitExOr,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr5(pairs)?),
-                };
-            }
+    while let Some(pair) = pairs.next() {
+        let op = match pair.as_rule() {
+            Rule::CARET => BinaryOp::BitExOr,
             _ => todo!(),
-        }
+        };
+        let rhs = expr4(pairs.next().unwrap().into_inner())?;
+        expr = ExprNode::binary_op(op, expr, rhs);
     }
 
     Ok(expr)
@@ -272,17 +261,13 @@ pub fn expr5(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
 pub fn expr4(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
     let mut expr = expr3(pairs.next().unwrap().into_inner())?;
 
-    if let Some(pair) = pairs.next() {
-        match pair.as_rule() {
-            Rule::AND => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::BitAnd,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr4(pairs)?),
-                };
-            }
+    while let Some(pair) = pairs.next() {
+        let op = match pair.as_rule() {
+            Rule::AND => BinaryOp::BitAnd,
             _ => todo!(),
-        }
+        };
+        let rhs = expr3(pairs.next().unwrap().into_inner())?;
+        expr = ExprNode::binary_op(op, expr, rhs);
     }
 
     Ok(expr)
@@ -291,24 +276,14 @@ pub fn expr4(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
 pub fn expr3(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
     let mut expr = expr2(pairs.next().unwrap().into_inner())?;
 
-    if let Some(pair) = pairs.next() {
-        match pair.as_rule() {
-            Rule::SHL => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::Shl,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr3(pairs)?),
-                };
-            }
-            Rule::SHR => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::Shr,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr3(pairs)?),
-                };
-            }
+    while let Some(pair) = pairs.next() {
+        let op = match pair.as_rule() {
+            Rule::SHL => BinaryOp::Shl,
+            Rule::SHR => BinaryOp::Shr,
             _ => todo!(),
-        }
+        };
+        let rhs = expr2(pairs.next().unwrap().into_inner())?;
+        expr = ExprNode::binary_op(op, expr, rhs);
     }
 
     Ok(expr)
@@ -317,57 +292,35 @@ pub fn expr3(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
 pub fn expr2(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
     let mut expr = expr1(pairs.next().unwrap().into_inner())?;
 
-    if let Some(pair) = pairs.next() {
-        match pair.as_rule() {
-            Rule::PLUS => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::Add,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr2(pairs)?),
-                };
-            }
-            Rule::MINUS => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::Sub,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr2(pairs)?),
-                };
-            }
+    while let Some(pair) = pairs.next() {
+        let op = match pair.as_rule() {
+            Rule::PLUS => BinaryOp::Add,
+            Rule::MINUS => BinaryOp::Sub,
             _ => todo!(),
-        }
+        };
+        let rhs = expr1(pairs.next().unwrap().into_inner())?;
+        expr = ExprNode::binary_op(op, expr, rhs);
     }
 
     Ok(expr)
 }
 
 pub fn expr1(mut pairs: Pairs<Rule>) -> Result<ExprNode, NodeError> {
-    let mut expr = ExprNode::Term(parse_term_node(pairs.next().unwrap())?);
-
-    if let Some(pair) = pairs.next() {
-        match pair.as_rule() {
-            Rule::STAR => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::Mul,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr1(pairs)?),
-                };
-            }
-            Rule::SLASH => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::Div,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr1(pairs)?),
-                };
-            }
-            Rule::PERCENT => {
-                expr = ExprNode::BinaryOp {
-                    op: BinaryOp::Mod,
-                    lhs: Box::new(expr),
-                    rhs: Box::new(expr1(pairs)?),
-                };
-            }
+    let pair = pairs.next().unwrap();
+    let span = pair.as_span().into();
+    let mut expr = ExprNode::Term(parse_term_node(pair)?, span);
+
+    while let Some(pair) = pairs.next() {
+        let op = match pair.as_rule() {
+            Rule::STAR => BinaryOp::Mul,
+            Rule::SLASH => BinaryOp::Div,
+            Rule::PERCENT => BinaryOp::Mod,
             _ => todo!(),
-        }
+        };
+        let pair = pairs.next().unwrap();
+        let span = pair.as_span().into();
+        let rhs = ExprNode::Term(parse_term_node(pair)?, span);
+        expr = ExprNode::binary_op(op, expr, rhs);
     }
 
     Ok(expr)
@@ -480,3 +433,25 @@ fn test_expr() {
         )
     );
 }
+
+#[test]
+fn test_left_associative() {
+    use crate::resolve::variable_scope::eval_const_expr;
+
+    let eval = |source: &str| {
+        eval_const_expr(
+            &parse_expr_node(
+                CBCScanner::parse(Rule::EXPR, source)
+                    .unwrap()
+                    .next()
+                    .unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    };
+    assert_eq!(eval("10 - 4 + 3"), 9);
+    assert_eq!(eval("100 / 10 / 5"), 2);
+    assert_eq!(eval("1 << 4 >> 2"), 4);
+    assert_eq!(eval("2 * 3 % 4"), 2);
+}
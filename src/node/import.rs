@@ -1,24 +1,65 @@
-use super::{Node, NodeError};
+use super::{Node, NodeError, NodeErrorType};
+use crate::diagnostic::Span;
 use crate::Rule;
 use pest::iterators::Pair;
 
 #[derive(Debug, Clone)]
 pub struct ImportNode {
     names: Vec<String>,
+    span: Span,
+}
+
+impl ImportNode {
+    pub fn library(&self) -> String {
+        self.names.join(".")
+    }
+}
+
+/// Returns the source of a bundled library header.
+pub fn library_source(name: &str) -> Option<&'static str> {
+    match name {
+        "stdio" => Some(include_str!("../../import/stdio.hb")),
+        "stdlib" => Some(include_str!("../../import/stdlib.hb")),
+        _ => None,
+    }
+}
+
+/// Parses the headers of all imported libraries.
+pub fn load_imports(nodes: &[Node]) -> Result<Vec<Node>, NodeError> {
+    let mut loaded = vec![];
+    for node in nodes {
+        if let Node::Import(import) = node {
+            let name = import.library();
+            let source = library_source(&name).ok_or_else(|| NodeError {
+                _type: NodeErrorType::Import,
+                message: format!("library {} is not found", name),
+                span: Some(import.span),
+            })?;
+            // Errors in a library header are reported at the import statement.
+            let nodes = super::parse(source).map_err(|e| NodeError {
+                _type: NodeErrorType::Import,
+                message: format!("in library {}: {}", name, e.message),
+                span: Some(import.span),
+            })?;
+            loaded.extend(nodes);
+        }
+    }
+    Ok(loaded)
 }
 
 pub fn parse_import_node(pair: Pair<Rule>) -> Result<Node, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner();
 
     pairs.next().unwrap(); // import
     let mut names = vec![];
     names.push(pairs.next().unwrap().as_str().into());
 
-    while let Some(pair) = pairs.next() {
+    for pair in pairs {
         names.push(pair.as_str().into());
     }
 
-    Ok(Node::Import(Box::new(ImportNode { names })))
+    Ok(Node::Import(Box::new(ImportNode { names, span })))
 }
 
 #[test]
@@ -17,6 +17,7 @@ use self::def::DefNode;
 use self::expr::*;
 use self::extern_::parse_prototypefun;
 use self::extern_::PrototypeFun;
+use self::import::load_imports;
 use self::import::parse_import_node;
 use self::import::ImportNode;
 use self::primary::*;
@@ -26,6 +27,7 @@ use self::type_::*;
 use self::unary::*;
 
 use self::term::parse_term_node;
+use crate::diagnostic::{Diagnostic, Span};
 use pest::Parser;
 
 use crate::{CBCScanner, Rule};
@@ -78,6 +80,21 @@ pub enum Node {
 pub struct NodeError {
     _type: NodeErrorType,
     message: String,
+    span: Option<Span>,
+}
+
+impl Diagnostic for NodeError {
+    fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    fn set_span(&mut self, span: Span) {
+        self.span = Some(span);
+    }
 }
 
 #[derive(Debug)]
@@ -97,18 +114,23 @@ pub enum NodeErrorType {
 
 pub fn parse(src: &str) -> Result<Vec<Node>, NodeError> {
     let mut nodes = vec![];
-    let mut pairs = CBCScanner::parse(Rule::FILE, src)
-        .or_else(|e| {
-            Err(NodeError {
+    let pairs = CBCScanner::parse(Rule::FILE, src)
+        .map_err(|e| {
+            let span = match e.location {
+                pest::error::InputLocation::Pos(pos) => Span::new(pos, pos),
+                pest::error::InputLocation::Span((start, end)) => Span::new(start, end),
+            };
+            NodeError {
                 _type: NodeErrorType::Token,
-                message: format!("failed to scan {:?}", e),
-            })
+                message: format!("failed to scan: {}", e.variant.message()),
+                span: Some(span),
+            }
         })?
         .next()
         .unwrap()
         .into_inner();
 
-    while let Some(pair) = pairs.next() {
+    for pair in pairs {
         match pair.as_rule() {
             Rule::IMPORT_STMT => nodes.push(parse_import_node(pair)?),
             Rule::TOP_DEF => nodes.push(parse_topdef_node(pair)?),
@@ -118,7 +140,10 @@ pub fn parse(src: &str) -> Result<Vec<Node>, NodeError> {
         }
     }
 
-    Ok(nodes)
+    let mut imported = load_imports(&nodes)?;
+    imported.append(&mut nodes);
+
+    Ok(imported)
 }
 
 #[test]
@@ -32,10 +32,11 @@ pub fn parse_params_node(pair: Pair<Rule>) -> Result<ParamsNode, NodeError> {
         return Ok(ParamsNode::Void);
     }
 
-    let mut pairs = pairs.next().unwrap().into_inner();
+    let fixed_params = pairs.next().unwrap().into_inner();
     let mut fixed = vec![];
 
-    while let Some(pair) = pairs.next() {
+    // VAR_PARAMS follows FIXED_PARAMS rather than being nested in it.
+    for pair in fixed_params.chain(pairs) {
         match pair.as_rule() {
             Rule::PARAM => fixed.push(parse_param(pair)?),
             Rule::VAR_PARAMS => {
@@ -81,4 +82,13 @@ fn test_params() {
             .unwrap()
     )
     .is_ok());
+    assert!(matches!(
+        parse_params_node(
+            CBCScanner::parse(Rule::PARAMS, "char* fmt, ...")
+                .unwrap()
+                .next()
+                .unwrap()
+        ),
+        Ok(ParamsNode::Some { variable: true, .. })
+    ));
 }
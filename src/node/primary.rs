@@ -7,33 +7,155 @@ use pest::iterators::Pair;
 #[derive(Debug, Clone)]
 pub enum PrimaryNode {
     Integer(i64),
-    String(String),
+    String(Vec<u8>),
     Char(char),
     Identifier(String, Option<Entity>),
+    Expr(Box<ExprNode>),
 }
 
 pub fn parse_primary_node(pair: Pair<Rule>) -> Result<PrimaryNode, NodeError> {
     debug_assert_eq!(pair.as_rule(), Rule::PRIMARY);
     let pair = pair.into_inner().next().unwrap();
+    let span: Span = pair.as_span().into();
     let node = match pair.as_rule() {
         Rule::INTEGER => {
             let n = pair.as_str().parse().unwrap();
             PrimaryNode::Integer(n)
         }
         Rule::STRING => {
-            let s = pair.as_str().into();
-            PrimaryNode::String(s)
+            // Adjacent string literals are concatenated.
+            let mut bytes = vec![];
+            for literal in pair.into_inner() {
+                let s = literal.as_str();
+                bytes.extend(
+                    unescape(&s[1..s.len() - 1])
+                        .map_err(|e| e.or_span(literal.as_span().into()))?,
+                );
+            }
+            PrimaryNode::String(bytes)
         }
         Rule::CHARACTER => {
-            let s = pair.as_str().to_string().chars().collect::<Vec<_>>();
-            PrimaryNode::Char(s[1])
+            let s = pair.as_str();
+            match unescape(&s[1..s.len() - 1]).map_err(|e| e.or_span(span))?[..] {
+                [c] => PrimaryNode::Char(c as char),
+                _ => Err(NodeError {
+                    _type: NodeErrorType::Primary,
+                    message: format!("character literal {} must be a single byte", s),
+                    span: Some(span),
+                })?,
+            }
         }
         Rule::IDENTIFIER => {
             let s = pair.as_str().into();
             PrimaryNode::Identifier(s, None)
         }
+        Rule::EXPR => PrimaryNode::Expr(Box::new(parse_expr_node(pair)?)),
         _ => panic!("not primary, found {:?}", pair.as_rule()),
     };
 
     Ok(node)
 }
+
+/// Decode the C escape sequences in the body of a string or character literal.
+pub fn unescape(s: &str) -> Result<Vec<u8>, NodeError> {
+    let mut bytes = vec![];
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+
+        let error = |message: String| NodeError {
+            _type: NodeErrorType::Primary,
+            message,
+            span: None,
+        };
+        let c = chars
+            .next()
+            .ok_or_else(|| error(format!("incomplete escape sequence in {:?}", s)))?;
+        let byte = match c {
+            'n' => b'\n',
+            't' => b'\t',
+            'r' => b'\r',
+            'a' => 0x07,
+            'b' => 0x08,
+            'f' => 0x0c,
+            'v' => 0x0b,
+            '\\' | '\'' | '"' | '?' => c as u8,
+            '0'..='7' => {
+                let mut value = c.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(d) => {
+                            value = value * 8 + d;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                u8::try_from(value)
+                    .map_err(|_| error(format!("octal escape sequence out of range in {:?}", s)))?
+            }
+            'x' => {
+                let mut value = 0u32;
+                let mut digits = 0;
+                while let Some(d) = chars.peek().and_then(|c| c.to_digit(16)) {
+                    value = value.saturating_mul(16).saturating_add(d);
+                    digits += 1;
+                    chars.next();
+                }
+                if digits == 0 {
+                    Err(error(format!(
+                        "\\x used with no following hex digits in {:?}",
+                        s
+                    )))?
+                }
+                u8::try_from(value)
+                    .map_err(|_| error(format!("hex escape sequence out of range in {:?}", s)))?
+            }
+            c => Err(error(format!(
+                "unknown escape sequence '\\{}' in {:?}",
+                c, s
+            )))?,
+        };
+        bytes.push(byte);
+    }
+
+    Ok(bytes)
+}
+
+#[test]
+fn test_unescape() {
+    assert_eq!(unescape("%d\\n").unwrap(), b"%d\n");
+    assert_eq!(unescape("\\t\\\\\\\"\\'\\0").unwrap(), b"\t\\\"'\0");
+    assert_eq!(unescape("\\101\\0101\\377").unwrap(), b"A\x081\xff");
+    assert_eq!(unescape("\\x41\\x7e!").unwrap(), b"A~!");
+    assert!(unescape("\\q").is_err());
+    assert!(unescape("\\x").is_err());
+    assert!(unescape("\\x100").is_err());
+    assert!(unescape("\\400").is_err());
+}
+
+#[test]
+fn test_literal() {
+    let parse =
+        |s| parse_primary_node(CBCScanner::parse(Rule::PRIMARY, s).unwrap().next().unwrap());
+
+    assert!(matches!(parse("'\\0'"), Ok(PrimaryNode::Char('\0'))));
+    assert!(matches!(parse("'1'"), Ok(PrimaryNode::Char('1'))));
+    assert!(matches!(parse("' '"), Ok(PrimaryNode::Char(' '))));
+    assert!(matches!(parse("'\\''"), Ok(PrimaryNode::Char('\''))));
+    assert!(parse("'ab'").is_err());
+    assert!(parse("'\\z'").is_err());
+
+    match parse("\"say \\\"hi\\\"\" /* comment */ \"\\n\"") {
+        Ok(PrimaryNode::String(s)) => assert_eq!(s, b"say \"hi\"\n"),
+        e => panic!("unexpected {:?}", e),
+    }
+    match parse("\"\\377\\200\" \"\\xff\"") {
+        Ok(PrimaryNode::String(s)) => assert_eq!(s, b"\xff\x80\xff"),
+        e => panic!("unexpected {:?}", e),
+    }
+}
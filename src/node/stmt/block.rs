@@ -1,9 +1,10 @@
 use super::*;
 
 pub fn parse_block(pair: Pair<Rule>) -> Result<StmtNode, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner();
     let stmts = parse_stmts(pairs.next().unwrap())?;
-    Ok(StmtNode::Block { stmts })
+    Ok(StmtNode::Block { stmts, span })
 }
 
 pub fn parse_block_stmts(pair: Pair<Rule>) -> Result<Vec<StmtNode>, NodeError> {
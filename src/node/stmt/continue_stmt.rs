@@ -1,10 +1,11 @@
 use super::*;
 
 pub fn parse_continue_stmt(pair: Pair<Rule>) -> Result<StmtNode, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner();
     pairs.next().unwrap(); // continue
     pairs.next().unwrap(); // semicolon
-    Ok(StmtNode::Continue)
+    Ok(StmtNode::Continue { span })
 }
 
 #[test]
@@ -1,13 +1,14 @@
 use super::*;
 
 pub fn parse_dowhile_stmt(pair: Pair<Rule>) -> Result<StmtNode, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner();
     pairs.next().unwrap(); // do
     let stmt = Box::new(parse_stmt_node(pairs.next().unwrap())?);
     pairs.next().unwrap(); // while
     let cond = parse_expr_node(pairs.next().unwrap())?;
     pairs.next().unwrap(); // semicolon
-    Ok(StmtNode::DoWhile { cond, stmt })
+    Ok(StmtNode::DoWhile { cond, stmt, span })
 }
 
 #[test]
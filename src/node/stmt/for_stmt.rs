@@ -1,6 +1,7 @@
 use super::*;
 
 pub fn parse_for_stmt(pair: Pair<Rule>) -> Result<StmtNode, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner();
     pairs.next().unwrap(); // for
     let init = parse_expr_node(pairs.next().unwrap())?;
@@ -14,6 +15,7 @@ pub fn parse_for_stmt(pair: Pair<Rule>) -> Result<StmtNode, NodeError> {
         cond,
         term,
         stmt,
+        span,
     })
 }
 
@@ -1,11 +1,12 @@
 use super::*;
 
 pub fn parse_goto_stmt(pair: Pair<Rule>) -> Result<StmtNode, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner();
     pairs.next().unwrap(); // goto
     let label = pairs.next().unwrap().as_str().into();
     pairs.next().unwrap(); // semicolon
-    Ok(StmtNode::Goto { label })
+    Ok(StmtNode::Goto { label, span })
 }
 
 #[test]
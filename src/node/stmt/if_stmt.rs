@@ -1,13 +1,21 @@
 use super::*;
 
 pub fn parse_if_stmt(pair: Pair<Rule>) -> Result<StmtNode, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner();
     pairs.next().unwrap(); // if
     let cond = parse_expr_node(pairs.next().unwrap())?;
     let then = Box::new(parse_stmt_node(pairs.next().unwrap())?);
-    pairs.next().unwrap(); // else
-    let _else = Box::new(parse_stmt_node(pairs.next().unwrap())?);
-    Ok(StmtNode::If { cond, then, _else })
+    let _else = match pairs.next() {
+        Some(_) => Box::new(parse_stmt_node(pairs.next().unwrap())?), // else
+        None => Box::new(StmtNode::None),
+    };
+    Ok(StmtNode::If {
+        cond,
+        then,
+        _else,
+        span,
+    })
 }
 
 #[test]
@@ -27,3 +35,17 @@ fn test_if() {
     )
     .is_ok());
 }
+
+#[test]
+fn test_if_without_else() {
+    let node = parse_stmt_node(
+        CBCScanner::parse(Rule::STMT, "if (a == b) a = 1;")
+            .unwrap()
+            .next()
+            .unwrap(),
+    );
+    assert!(matches!(
+        node,
+        Ok(StmtNode::If { _else, .. }) if matches!(*_else, StmtNode::None)
+    ));
+}
@@ -0,0 +1,24 @@
+use super::*;
+
+pub fn parse_label_stmt(pair: Pair<Rule>) -> Result<StmtNode, NodeError> {
+    let span = pair.as_span().into();
+    let mut pairs = pair.into_inner();
+    let label = pairs.next().unwrap().as_str().into();
+    pairs.next().unwrap(); // colon
+    let stmt = Box::new(parse_stmt_node(pairs.next().unwrap())?);
+    Ok(StmtNode::Label { label, stmt, span })
+}
+
+#[test]
+fn test_label() {
+    let node = parse_stmt_node(
+        CBCScanner::parse(Rule::STMT, "retry: a = a + 1;")
+            .unwrap()
+            .next()
+            .unwrap(),
+    );
+    assert!(matches!(
+        node,
+        Ok(StmtNode::Label { label, stmt, .. }) if label == "retry" && matches!(*stmt, StmtNode::Expr(_))
+    ));
+}
@@ -1,7 +1,8 @@
 use self::{
-    block::parse_block, break_stmt::parse_break_stmt, dowhile_stmt::parse_dowhile_stmt,
-    for_stmt::parse_for_stmt, goto_stmt::parse_goto_stmt, if_stmt::parse_if_stmt,
-    return_stmt::parse_return_stmt, switch_stmt::parse_switch_stmt, while_stmt::parse_while_stmt,
+    block::parse_block, break_stmt::parse_break_stmt, continue_stmt::parse_continue_stmt,
+    dowhile_stmt::parse_dowhile_stmt, for_stmt::parse_for_stmt, goto_stmt::parse_goto_stmt,
+    if_stmt::parse_if_stmt, label_stmt::parse_label_stmt, return_stmt::parse_return_stmt,
+    switch_stmt::parse_switch_stmt, while_stmt::parse_while_stmt,
 };
 use super::def::def_var::*;
 use super::*;
@@ -14,6 +15,7 @@ mod dowhile_stmt;
 mod for_stmt;
 mod goto_stmt;
 mod if_stmt;
+mod label_stmt;
 mod return_stmt;
 mod switch_stmt;
 mod while_stmt;
@@ -24,47 +26,86 @@ pub enum StmtNode {
     Expr(ExprNode),
     Block {
         stmts: Vec<StmtNode>,
+        span: Span,
     },
     If {
         cond: ExprNode,
         then: Box<StmtNode>,
         _else: Box<StmtNode>,
+        span: Span,
     },
     While {
         cond: ExprNode,
         stmt: Box<StmtNode>,
+        span: Span,
     },
     DoWhile {
         cond: ExprNode,
         stmt: Box<StmtNode>,
+        span: Span,
     },
     For {
         init: ExprNode,
         cond: ExprNode,
         term: ExprNode,
         stmt: Box<StmtNode>,
+        span: Span,
     },
     Switch {
         cond: ExprNode,
         cases: Vec<(Vec<PrimaryNode>, Vec<StmtNode>)>,
         default: Option<Vec<StmtNode>>,
+        span: Span,
+    },
+    Break {
+        span: Span,
+    },
+    Continue {
+        span: Span,
     },
-    Break,
-    Continue,
     Goto {
         label: String,
+        span: Span,
+    },
+    Label {
+        label: String,
+        stmt: Box<StmtNode>,
+        span: Span,
     },
     Return {
         expr: Option<ExprNode>,
+        span: Span,
     },
     DefVars(DefVars),
 }
 
+impl StmtNode {
+    /// Returns the span of the statement. `None` has no span since it may be absent in the source.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            StmtNode::None => None,
+            StmtNode::Expr(expr) => Some(expr.span()),
+            StmtNode::DefVars(vars) => Some(vars.span),
+            StmtNode::Block { span, .. }
+            | StmtNode::If { span, .. }
+            | StmtNode::While { span, .. }
+            | StmtNode::DoWhile { span, .. }
+            | StmtNode::For { span, .. }
+            | StmtNode::Switch { span, .. }
+            | StmtNode::Break { span }
+            | StmtNode::Continue { span }
+            | StmtNode::Goto { span, .. }
+            | StmtNode::Label { span, .. }
+            | StmtNode::Return { span, .. } => Some(*span),
+        }
+    }
+}
+
 pub fn parse_stmts(pair: Pair<Rule>) -> Result<Vec<StmtNode>, NodeError> {
-    let mut pairs = pair.into_inner();
+    let pairs = pair.into_inner();
 
     let mut stmts = vec![];
-    while let Some(pair) = pairs.next() {
+    for pair in pairs {
         stmts.push(parse_stmt_node(pair)?);
     }
     Ok(stmts)
@@ -78,6 +119,7 @@ pub fn parse_stmt_node(pair: Pair<Rule>) -> Result<StmtNode, NodeError> {
             pairs.next();
             Ok(StmtNode::None)
         }
+        Rule::LABELED_STMT => Ok(parse_label_stmt(pairs.next().unwrap())?),
         Rule::BLOCK => Ok(parse_block(pairs.next().unwrap())?),
         Rule::EXPR => {
             let node = Ok(StmtNode::Expr(parse_expr_node(pairs.next().unwrap())?));
@@ -90,6 +132,7 @@ pub fn parse_stmt_node(pair: Pair<Rule>) -> Result<StmtNode, NodeError> {
         Rule::FOR_STMT => Ok(parse_for_stmt(pairs.next().unwrap())?),
         Rule::SWITCH_STMT => Ok(parse_switch_stmt(pairs.next().unwrap())?),
         Rule::BREAK_STMT => Ok(parse_break_stmt(pairs.next().unwrap())?),
+        Rule::CONTINUE_STMT => Ok(parse_continue_stmt(pairs.next().unwrap())?),
         Rule::GOTO_STMT => Ok(parse_goto_stmt(pairs.next().unwrap())?),
         Rule::RETURN_STMT => Ok(parse_return_stmt(pairs.next().unwrap())?),
         Rule::DEF_VARS => Ok(StmtNode::DefVars(parse_def_vars(pairs.next().unwrap())?)),
@@ -1,13 +1,15 @@
 use super::*;
 
 pub fn parse_return_stmt(pair: Pair<Rule>) -> Result<StmtNode, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner();
     pairs.next().unwrap(); // return
     let pair = pairs.next().unwrap();
     let node = match pair.as_rule() {
-        Rule::SCOLON => StmtNode::Return { expr: None },
+        Rule::SCOLON => StmtNode::Return { expr: None, span },
         Rule::EXPR => StmtNode::Return {
             expr: Some(parse_expr_node(pair)?),
+            span,
         },
         _ => unreachable!(),
     };
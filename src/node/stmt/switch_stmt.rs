@@ -1,6 +1,7 @@
 use super::*;
 
 pub fn parse_switch_stmt(pair: Pair<Rule>) -> Result<StmtNode, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner();
     pairs.next().unwrap(); // swtich
     let cond = parse_expr_node(pairs.next().unwrap())?;
@@ -10,18 +11,16 @@ pub fn parse_switch_stmt(pair: Pair<Rule>) -> Result<StmtNode, NodeError> {
         cond,
         cases,
         default,
+        span,
     })
 }
 
-pub fn case_clauses(
-    pair: Pair<Rule>,
-) -> Result<
-    (
-        Vec<(Vec<PrimaryNode>, Vec<StmtNode>)>,
-        Option<Vec<StmtNode>>,
-    ),
-    NodeError,
-> {
+pub type CaseClauses = (
+    Vec<(Vec<PrimaryNode>, Vec<StmtNode>)>,
+    Option<Vec<StmtNode>>,
+);
+
+pub fn case_clauses(pair: Pair<Rule>) -> Result<CaseClauses, NodeError> {
     let mut pairs = pair.into_inner().peekable();
 
     let mut clist = vec![];
@@ -53,12 +52,10 @@ pub fn default_clause(pair: Pair<Rule>) -> Result<Vec<StmtNode>, NodeError> {
 }
 
 pub fn cases(pair: Pair<Rule>) -> Result<Vec<PrimaryNode>, NodeError> {
-    let mut pairs = pair.into_inner();
-    pairs.next().unwrap(); // case
     let mut plist = vec![];
-    plist.push(parse_primary_node(pairs.next().unwrap())?);
 
-    while let Some(pair) = pairs.next() {
+    // Every value is preceded by its own `case` keyword.
+    for pair in pair.into_inner().filter(|p| p.as_rule() == Rule::PRIMARY) {
         plist.push(parse_primary_node(pair)?);
     }
 
@@ -66,7 +63,7 @@ pub fn cases(pair: Pair<Rule>) -> Result<Vec<PrimaryNode>, NodeError> {
 }
 
 pub fn case_body(pair: Pair<Rule>) -> Result<Vec<StmtNode>, NodeError> {
-    Ok(parse_stmts(pair.into_inner().next().unwrap())?)
+    parse_stmts(pair.into_inner().next().unwrap())
 }
 
 #[test]
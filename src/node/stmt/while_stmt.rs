@@ -1,11 +1,12 @@
 use super::*;
 
 pub fn parse_while_stmt(pair: Pair<Rule>) -> Result<StmtNode, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner();
     pairs.next().unwrap(); // while
     let cond = parse_expr_node(pairs.next().unwrap())?;
     let stmt = Box::new(parse_stmt_node(pairs.next().unwrap())?);
-    Ok(StmtNode::While { cond, stmt })
+    Ok(StmtNode::While { cond, stmt, span })
 }
 
 #[test]
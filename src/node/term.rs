@@ -15,11 +15,11 @@ pub fn parse_term_node(pair: Pair<Rule>) -> Result<TermNode, NodeError> {
             pairs.next().unwrap(); // Skip the left bracket
             let type_node = pairs.next().unwrap();
             pairs.next().unwrap(); // Skip the right bracket
-            let node = TermNode::Cast(
+
+            TermNode::Cast(
                 parse_type_node(type_node)?,
                 Box::new(parse_term_node(pairs.next().unwrap())?),
-            );
-            node
+            )
         }
         Rule::UNARY => TermNode::Unary(Box::new(parse_unary_node(pairs.next().unwrap())?)),
         err => panic!("term error: {:?}", err),
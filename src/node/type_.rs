@@ -19,6 +19,7 @@ pub enum TypeBaseNode {
     UnsignedLong,
     Struct(String, Option<Box<Entity>>),
     Union(String, Option<Box<Entity>>),
+    Enum(String, Option<Box<Entity>>),
     Identifier(String, Option<Box<Entity>>),
 }
 
@@ -26,6 +27,7 @@ pub enum TypeBaseNode {
 pub struct TypeNode {
     pub base: TypeBaseNode,
     pub suffixs: Vec<TypeSuffix>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -37,6 +39,7 @@ pub enum TypeSuffix {
 }
 
 pub fn parse_type_node(pair: Pair<Rule>) -> Result<TypeNode, NodeError> {
+    let span = pair.as_span().into();
     let mut pairs = pair.into_inner();
     let base = parse_typebase_node(pairs.next().unwrap())?;
 
@@ -62,7 +65,11 @@ pub fn parse_type_node(pair: Pair<Rule>) -> Result<TypeNode, NodeError> {
         }
     }
 
-    Ok(TypeNode { base, suffixs })
+    Ok(TypeNode {
+        base,
+        suffixs,
+        span,
+    })
 }
 
 pub fn parse_typebase_node(pair: Pair<Rule>) -> Result<TypeBaseNode, NodeError> {
@@ -101,6 +108,18 @@ pub fn parse_typebase_node(pair: Pair<Rule>) -> Result<TypeBaseNode, NodeError>
 
             Ok(TypeBaseNode::Union(ident, None))
         }
+        Rule::ENUM_IDENT => {
+            let ident = pairs
+                .next()
+                .unwrap()
+                .into_inner()
+                .nth(1)
+                .unwrap()
+                .as_str()
+                .into();
+
+            Ok(TypeBaseNode::Enum(ident, None))
+        }
         Rule::IDENTIFIER => Ok(TypeBaseNode::Identifier(
             pairs.next().unwrap().as_str().into(),
             None,
@@ -97,10 +97,10 @@ pub fn parse_suffix_node(mut pairs: Peekable<Pairs<Rule>>) -> Result<UnaryNode,
 }
 
 pub fn parse_args(pair: Pair<Rule>) -> Result<Vec<ExprNode>, NodeError> {
-    let mut pairs = pair.into_inner();
+    let pairs = pair.into_inner();
     let mut args = vec![];
 
-    while let Some(pair) = pairs.next() {
+    for pair in pairs {
         args.push(parse_expr_node(pair)?);
     }
 
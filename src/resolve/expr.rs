@@ -8,58 +8,47 @@ use super::variable_scope::{Entity, ResolverError};
 
 pub fn dereference_check(nodes: &Vec<Node>) -> Result<(), ResolverError> {
     for node in nodes {
-        match node {
-            Node::Def(def) => match def.as_ref() {
-                DefNode::Fun(DefFun { block, .. }) => {
-                    for stmt in block {
-                        match stmt {
-                            StmtNode::Expr(expr) => {
-                                if !assiment_check(&expr) {
-                                    Err(ResolverError {
-                                        message: format!(
-                                            "invalid expression: LHS cannot be assigned.\n    {:?}",
-                                            expr
-                                        ),
-                                    })?;
-                                }
-                                if !callable_check(&expr) {
-                                    Err(ResolverError {
-                                        message: format!("invalid expression: {:?}", expr),
-                                    })?;
-                                }
+        if let Node::Def(def) = node {
+            if let DefNode::Fun(DefFun { block, .. }) = def.as_ref() {
+                for stmt in block {
+                    match stmt {
+                        StmtNode::Expr(expr) => {
+                            if !assiment_check(expr) {
+                                Err(ResolverError {
+                                    message: "invalid expression: LHS cannot be assigned".into(),
+                                    span: Some(expr.span()),
+                                })?;
+                            }
+                            if !callable_check(expr) {
+                                Err(ResolverError {
+                                    message: "invalid expression".into(),
+                                    span: Some(expr.span()),
+                                })?;
                             }
-                            StmtNode::DefVars(defvar) => {
-                                for var in defvar.vars.iter() {
-                                    match var {
-                                        Var::Init { expr, .. } => {
-                                            if !assiment_check(&expr) {
-                                                Err(ResolverError {
-                                                    message: format!(
-                                            "invalid expression: LHS cannot be assigned.\n    {:?}",
-                                            expr
-                                        ),
-                                                })?;
-                                            }
-                                            if !callable_check(&expr) {
-                                                Err(ResolverError {
-                                                    message: format!(
-                                                        "invalid expression: {:?}",
-                                                        expr
-                                                    ),
-                                                })?;
-                                            }
-                                        }
-                                        _ => {}
+                        }
+                        StmtNode::DefVars(defvar) => {
+                            for var in defvar.vars.iter() {
+                                if let Var::Init { expr, .. } = var {
+                                    if !assiment_check(expr) {
+                                        Err(ResolverError {
+                                            message: "invalid expression: LHS cannot be assigned"
+                                                .into(),
+                                            span: Some(expr.span()),
+                                        })?;
+                                    }
+                                    if !callable_check(expr) {
+                                        Err(ResolverError {
+                                            message: "invalid expression".into(),
+                                            span: Some(expr.span()),
+                                        })?;
                                     }
                                 }
                             }
-                            _ => {}
                         }
+                        _ => {}
                     }
                 }
-                _ => {}
-            },
-            _ => {}
+            }
         }
     }
     Ok(())
@@ -67,20 +56,20 @@ pub fn dereference_check(nodes: &Vec<Node>) -> Result<(), ResolverError> {
 
 pub fn callable_check(expr: &ExprNode) -> bool {
     match expr {
-        ExprNode::Term(term) => callable_term(term),
-        ExprNode::Assign { term, expr } | ExprNode::AssignOp { term, expr, .. } => {
+        ExprNode::Term(term, _) => callable_term(term),
+        ExprNode::Assign { term, expr, .. } | ExprNode::AssignOp { term, expr, .. } => {
             callable_term(term) && callable_check(expr)
         }
-        ExprNode::BinaryOp { lhs, rhs, .. } => callable_check(&lhs) && callable_check(&rhs),
+        ExprNode::BinaryOp { lhs, rhs, .. } => callable_check(lhs) && callable_check(rhs),
         ExprNode::TernaryOp { lhs, mhs, rhs, .. } => {
-            callable_check(&lhs) && callable_check(&mhs) && callable_check(&rhs)
+            callable_check(lhs) && callable_check(mhs) && callable_check(rhs)
         }
     }
 }
 
 pub fn callable_term(term: &TermNode) -> bool {
     match term {
-        TermNode::Unary(unary) => callable_uanry(&unary),
+        TermNode::Unary(unary) => callable_uanry(unary),
         TermNode::Cast(_, term) => callable_term(term.as_ref()),
     }
 }
@@ -104,21 +93,17 @@ pub fn callable_uanry(unary: &UnaryNode) -> bool {
 pub fn callable_entity(entity: &Entity) -> bool {
     match entity {
         Entity::Function { .. } => true,
-        Entity::Variable { _type, init, .. } => {
-            if let Some(expr) = init {
-                callable_check(expr)
-            } else {
-                false
-            }
-        }
+        Entity::Variable {
+            init: Some(expr), ..
+        } => callable_check(expr),
         _ => false,
     }
 }
 
 pub fn assiment_check(expr: &ExprNode) -> bool {
     match expr {
-        ExprNode::Assign { term, expr } | ExprNode::AssignOp { term, expr, .. } => {
-            return is_variable_term(term) && assiment_check(expr);
+        ExprNode::Assign { term, expr, .. } | ExprNode::AssignOp { term, expr, .. } => {
+            is_variable_term(term) && assiment_check(expr)
         }
         _ => true,
     }
@@ -126,7 +111,7 @@ pub fn assiment_check(expr: &ExprNode) -> bool {
 
 pub fn is_variable_term(term: &TermNode) -> bool {
     match term {
-        TermNode::Unary(unary) => is_variable_unary(&unary),
+        TermNode::Unary(unary) => is_variable_unary(unary),
         TermNode::Cast(_, term) => is_variable_term(term.as_ref()),
     }
 }
@@ -141,10 +126,7 @@ pub fn is_variable_unary(unary: &UnaryNode) -> bool {
 }
 
 pub fn is_variable_primary(primary: &PrimaryNode) -> bool {
-    match primary {
-        PrimaryNode::Identifier(_, _) => true,
-        _ => false,
-    }
+    matches!(primary, PrimaryNode::Identifier(_, _))
 }
 
 #[test]
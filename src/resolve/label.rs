@@ -0,0 +1,89 @@
+use crate::diagnostic::Span;
+use crate::node::stmt::StmtNode;
+use std::collections::BTreeSet;
+
+use super::variable_scope::ResolverError;
+
+/// Check that every `goto` in a function refers to a label defined exactly once in the function.
+pub fn check_labels(fun: &str, stmts: &[StmtNode]) -> Result<(), ResolverError> {
+    let mut labels = BTreeSet::new();
+    let mut gotos = vec![];
+    for stmt in stmts {
+        collect_labels(fun, stmt, &mut labels, &mut gotos)?;
+    }
+
+    for (label, span) in gotos {
+        if !labels.contains(label) {
+            Err(ResolverError {
+                message: format!("label {} is not defined in {}", label, fun),
+                span: Some(span),
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn collect_labels<'a>(
+    fun: &str,
+    stmt: &'a StmtNode,
+    labels: &mut BTreeSet<&'a str>,
+    gotos: &mut Vec<(&'a str, Span)>,
+) -> Result<(), ResolverError> {
+    match stmt {
+        StmtNode::Label { label, stmt, span } => {
+            if !labels.insert(label) {
+                Err(ResolverError {
+                    message: format!("label {} is already defined in {}", label, fun),
+                    span: Some(*span),
+                })?;
+            }
+            collect_labels(fun, stmt, labels, gotos)?;
+        }
+        StmtNode::Goto { label, span } => gotos.push((label, *span)),
+        StmtNode::Block { stmts, .. } => {
+            for stmt in stmts {
+                collect_labels(fun, stmt, labels, gotos)?;
+            }
+        }
+        StmtNode::If { then, _else, .. } => {
+            collect_labels(fun, then, labels, gotos)?;
+            collect_labels(fun, _else, labels, gotos)?;
+        }
+        StmtNode::While { stmt, .. }
+        | StmtNode::DoWhile { stmt, .. }
+        | StmtNode::For { stmt, .. } => collect_labels(fun, stmt, labels, gotos)?,
+        StmtNode::Switch { cases, default, .. } => {
+            for stmt in cases
+                .iter()
+                .flat_map(|(_, stmts)| stmts)
+                .chain(default.iter().flatten())
+            {
+                collect_labels(fun, stmt, labels, gotos)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[test]
+fn test_check_labels() {
+    use crate::node::def::DefNode;
+    use crate::node::{parse, Node};
+
+    let check = |src: &str| {
+        let nodes = parse(src).unwrap();
+        match &nodes[0] {
+            Node::Def(def) => match def.as_ref() {
+                DefNode::Fun(fun) => check_labels(&fun.name, &fun.block),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    };
+
+    assert!(check("int f(void) { goto end; end: return 0; }").is_ok());
+    assert!(check("int f(void) { while (1) { again: goto again; } return 0; }").is_ok());
+    assert!(check("int f(void) { goto nowhere; return 0; }").is_err());
+    assert!(check("int f(void) { a: ; if (1) { a: ; } return 0; }").is_err());
+}
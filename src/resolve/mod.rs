@@ -1,4 +1,5 @@
 pub mod expr;
+pub mod label;
 pub mod type_check;
 pub mod type_def;
 pub mod variable_scope;
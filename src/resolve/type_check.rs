@@ -0,0 +1,1082 @@
+use crate::diagnostic::{Diagnostic, Span};
+use crate::node::def::def_var::{DefVars, Var};
+use crate::node::def::{DefNode, Member};
+use crate::node::expr::{AssignOp, BinaryOp, ExprNode};
+use crate::node::param::ParamsNode;
+use crate::node::primary::PrimaryNode;
+use crate::node::stmt::StmtNode;
+use crate::node::term::TermNode;
+use crate::node::type_::{TypeBaseNode, TypeNode, TypeSuffix};
+use crate::node::unary::{SuffixOp, UnaryNode};
+use crate::node::Node;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+use super::variable_scope::{
+    eval_const_expr, eval_const_primary, get_ref, Entity, ResolverError, Scope,
+};
+
+fn error<T>(message: impl Into<String>) -> Result<T, ResolverError> {
+    Err(ResolverError {
+        message: message.into(),
+        span: None,
+    })
+}
+
+/// Check the types of every initializer and function body in a resolved program.
+pub fn type_check(nodes: &[Node], scope: &Rc<Scope>) -> Result<(), ResolverError> {
+    for node in nodes {
+        if let Node::Def(def) = node {
+            let ret = match def.as_ref() {
+                DefNode::Vars(vars) => check_defvars(vars, scope),
+                DefNode::Fun(fun) => {
+                    let scope = fun.scope.as_ref().unwrap_or(scope);
+                    let return_type = expand(&fun._type, scope)?;
+                    check_stmts(&fun.block, &return_type, scope)
+                }
+                _ => Ok(()),
+            };
+            ret.map_err(|e| e.or_span(def.span()))?;
+        }
+    }
+    Ok(())
+}
+
+fn check_defvars(vars: &DefVars, scope: &Rc<Scope>) -> Result<(), ResolverError> {
+    let _type = expand(&vars._type, scope)?;
+    for var in vars.vars.iter() {
+        let name = match var {
+            Var::Init { name, .. } | Var::Uninit { name } => name,
+        };
+        if is_void(&_type) {
+            error(format!("variable {} is declared void", name))?;
+        }
+        if let Var::Init { expr, .. } = var {
+            let src = type_of_expr(expr, scope)?;
+            check_assignment(&_type, &src, expr).map_err(|e| e.or_span(expr.span()))?;
+        }
+    }
+    Ok(())
+}
+
+fn check_stmts(
+    stmts: &[StmtNode],
+    return_type: &TypeNode,
+    scope: &Rc<Scope>,
+) -> Result<(), ResolverError> {
+    for stmt in stmts {
+        check_stmt(stmt, return_type, scope).map_err(|e| match stmt.span() {
+            Some(span) => e.or_span(span),
+            None => e,
+        })?;
+    }
+    Ok(())
+}
+
+fn check_stmt(
+    stmt: &StmtNode,
+    return_type: &TypeNode,
+    scope: &Rc<Scope>,
+) -> Result<(), ResolverError> {
+    match stmt {
+        StmtNode::None
+        | StmtNode::Break { .. }
+        | StmtNode::Continue { .. }
+        | StmtNode::Goto { .. } => {}
+        StmtNode::Expr(expr) => {
+            type_of_expr(expr, scope)?;
+        }
+        StmtNode::DefVars(vars) => check_defvars(vars, scope)?,
+        StmtNode::Block { stmts, .. } => check_stmts(stmts, return_type, scope)?,
+        StmtNode::If {
+            cond, then, _else, ..
+        } => {
+            check_cond(cond, scope)?;
+            check_stmt(then, return_type, scope)?;
+            check_stmt(_else, return_type, scope)?;
+        }
+        StmtNode::While { cond, stmt, .. } | StmtNode::DoWhile { cond, stmt, .. } => {
+            check_cond(cond, scope)?;
+            check_stmt(stmt, return_type, scope)?;
+        }
+        StmtNode::For {
+            init,
+            cond,
+            term,
+            stmt,
+            ..
+        } => {
+            type_of_expr(init, scope)?;
+            check_cond(cond, scope)?;
+            type_of_expr(term, scope)?;
+            check_stmt(stmt, return_type, scope)?;
+        }
+        StmtNode::Switch {
+            cond,
+            cases,
+            default,
+            ..
+        } => {
+            if !is_integer(&type_of_expr(cond, scope)?) {
+                error("switch quantity is not an integer").map_err(|e| e.or_span(cond.span()))?;
+            }
+            let mut seen = BTreeSet::new();
+            for (values, stmts) in cases {
+                for value in values {
+                    match eval_const_primary(value) {
+                        Some(value) if !seen.insert(value) => {
+                            error(format!("duplicate case value {}", value))?
+                        }
+                        Some(_) => {}
+                        None => error("case label does not reduce to an integer constant")?,
+                    }
+                }
+                check_stmts(stmts, return_type, scope)?;
+            }
+            if let Some(stmts) = default {
+                check_stmts(stmts, return_type, scope)?;
+            }
+        }
+        StmtNode::Label { stmt, .. } => check_stmt(stmt, return_type, scope)?,
+        StmtNode::Return { expr, .. } => match expr {
+            Some(expr) => {
+                let src = type_of_expr(expr, scope)?;
+                if is_void(return_type) {
+                    error("void function should not return a value")
+                        .map_err(|e| e.or_span(expr.span()))?;
+                }
+                check_assignment(return_type, &src, expr).map_err(|e| e.or_span(expr.span()))?;
+            }
+            None => {
+                if !is_void(return_type) {
+                    error(format!(
+                        "function returning {} should return a value",
+                        type_name(return_type)
+                    ))?;
+                }
+            }
+        },
+    }
+    Ok(())
+}
+
+fn check_cond(cond: &ExprNode, scope: &Rc<Scope>) -> Result<(), ResolverError> {
+    if !is_scalar(&type_of_expr(cond, scope)?) {
+        error("condition must be a scalar value").map_err(|e| e.or_span(cond.span()))?;
+    }
+    Ok(())
+}
+
+/// Returns the type of an expression after integer promotions and usual arithmetic conversions.
+pub fn type_of_expr(expr: &ExprNode, scope: &Rc<Scope>) -> Result<TypeNode, ResolverError> {
+    check_expr(expr, scope).map(|(_type, _)| decay(&_type))
+}
+
+pub fn type_of_term(term: &TermNode, scope: &Rc<Scope>) -> Result<TypeNode, ResolverError> {
+    check_term(term, scope).map(|(_type, _)| decay(&_type))
+}
+
+/// Returns the type of a unary expression. Arrays and functions are not converted to pointers,
+/// so this is the operand type seen by `sizeof` and `&`.
+pub fn type_of_unary(unary: &UnaryNode, scope: &Rc<Scope>) -> Result<TypeNode, ResolverError> {
+    check_unary(unary, scope).map(|(_type, _)| _type)
+}
+
+/// Returns the type of a primary expression without array decay.
+pub fn type_of_primary(
+    primary: &PrimaryNode,
+    scope: &Rc<Scope>,
+) -> Result<TypeNode, ResolverError> {
+    check_primary(primary, scope).map(|(_type, _)| _type)
+}
+
+/// Returns the type of an expression along with whether it designates an object.
+fn check_expr(expr: &ExprNode, scope: &Rc<Scope>) -> Result<(TypeNode, bool), ResolverError> {
+    let ret = match expr {
+        ExprNode::Term(term, _) => check_term(term, scope),
+        ExprNode::Assign { term, expr, .. } => {
+            let dst = check_modifiable(check_term(term, scope)?, "=")?;
+            let src = type_of_expr(expr, scope)?;
+            check_assignment(&dst, &src, expr)?;
+            Ok((dst, false))
+        }
+        ExprNode::AssignOp { op, term, expr, .. } => {
+            let dst = check_modifiable(check_term(term, scope)?, assign_op_name(op))?;
+            let src = type_of_expr(expr, scope)?;
+            let valid = match op {
+                AssignOp::Add | AssignOp::Sub if is_pointer(&dst) => {
+                    check_pointer_arithmetic(&dst)?;
+                    is_integer(&src)
+                }
+                _ => is_integer(&dst) && is_integer(&src),
+            };
+            if !valid {
+                error(format!(
+                    "invalid operands to {} ({} and {})",
+                    assign_op_name(op),
+                    type_name(&dst),
+                    type_name(&src)
+                ))?;
+            }
+            Ok((dst, false))
+        }
+        ExprNode::BinaryOp { op, lhs, rhs, .. } => {
+            let l = type_of_expr(lhs, scope)?;
+            let r = type_of_expr(rhs, scope)?;
+            binary_type(op, &l, &r, lhs, rhs).map(|_type| (_type, false))
+        }
+        ExprNode::TernaryOp { lhs, mhs, rhs, .. } => {
+            check_cond(lhs, scope)?;
+            let l = type_of_expr(mhs, scope)?;
+            let r = type_of_expr(rhs, scope)?;
+            ternary_type(&l, &r, mhs, rhs).map(|_type| (_type, false))
+        }
+    };
+    ret.map_err(|e| e.or_span(expr.span()))
+}
+
+fn assign_op_name(op: &AssignOp) -> &'static str {
+    match op {
+        AssignOp::Add => "+=",
+        AssignOp::Sub => "-=",
+        AssignOp::Mul => "*=",
+        AssignOp::Div => "/=",
+        AssignOp::Mod => "%=",
+        AssignOp::And => "&=",
+        AssignOp::Or => "|=",
+        AssignOp::Exor => "^=",
+        AssignOp::Shl => "<<=",
+        AssignOp::Shr => ">>=",
+    }
+}
+
+fn binary_op_name(op: &BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Mod => "%",
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Shl => "<<",
+        BinaryOp::Shr => ">>",
+        BinaryOp::And => "&&",
+        BinaryOp::Or => "||",
+        BinaryOp::BitAnd => "&",
+        BinaryOp::BitOr => "|",
+        BinaryOp::BitExOr => "^",
+        BinaryOp::Ge => ">=",
+        BinaryOp::Le => "<=",
+        BinaryOp::Gt => ">",
+        BinaryOp::Lt => "<",
+        BinaryOp::Eq => "==",
+        BinaryOp::Ne => "!=",
+    }
+}
+
+fn binary_type(
+    op: &BinaryOp,
+    l: &TypeNode,
+    r: &TypeNode,
+    lhs: &ExprNode,
+    rhs: &ExprNode,
+) -> Result<TypeNode, ResolverError> {
+    let _type = match op {
+        BinaryOp::Mul
+        | BinaryOp::Div
+        | BinaryOp::Mod
+        | BinaryOp::BitAnd
+        | BinaryOp::BitOr
+        | BinaryOp::BitExOr
+            if is_integer(l) && is_integer(r) =>
+        {
+            usual_arithmetic_conversion(l, r)
+        }
+        BinaryOp::Shl | BinaryOp::Shr if is_integer(l) && is_integer(r) => integer_promotion(l),
+        BinaryOp::Add | BinaryOp::Sub if is_integer(l) && is_integer(r) => {
+            usual_arithmetic_conversion(l, r)
+        }
+        BinaryOp::Add | BinaryOp::Sub if is_pointer(l) && is_integer(r) => {
+            check_pointer_arithmetic(l)?;
+            l.clone()
+        }
+        BinaryOp::Add if is_integer(l) && is_pointer(r) => {
+            check_pointer_arithmetic(r)?;
+            r.clone()
+        }
+        BinaryOp::Sub if is_pointer(l) && is_pointer(r) => {
+            check_pointer_arithmetic(l)?;
+            if !same_type(l, r) {
+                error(format!(
+                    "cannot subtract {} from {}",
+                    type_name(r),
+                    type_name(l)
+                ))?;
+            }
+            long_type(true)
+        }
+        BinaryOp::And | BinaryOp::Or if is_scalar(l) && is_scalar(r) => int_type(),
+        BinaryOp::Ge | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Lt
+            if (is_integer(l) && is_integer(r)) || (is_pointer(l) && same_type(l, r)) =>
+        {
+            int_type()
+        }
+        BinaryOp::Eq | BinaryOp::Ne
+            if (is_integer(l) && is_integer(r))
+                || (is_pointer(l) && is_pointer(r) && pointers_compatible(l, r))
+                || (is_pointer(l) && is_null_pointer_constant(r, rhs))
+                || (is_pointer(r) && is_null_pointer_constant(l, lhs)) =>
+        {
+            int_type()
+        }
+        _ => error(format!(
+            "invalid operands to binary {} ({} and {})",
+            binary_op_name(op),
+            type_name(l),
+            type_name(r)
+        ))?,
+    };
+    Ok(_type)
+}
+
+fn ternary_type(
+    l: &TypeNode,
+    r: &TypeNode,
+    mhs: &ExprNode,
+    rhs: &ExprNode,
+) -> Result<TypeNode, ResolverError> {
+    if is_integer(l) && is_integer(r) {
+        Ok(usual_arithmetic_conversion(l, r))
+    } else if (is_void(l) && is_void(r)) || same_type(l, r) {
+        Ok(l.clone())
+    } else if is_pointer(l) && (is_null_pointer_constant(r, rhs) || is_void_pointer(r)) {
+        Ok(if is_void_pointer(r) {
+            r.clone()
+        } else {
+            l.clone()
+        })
+    } else if is_pointer(r) && (is_null_pointer_constant(l, mhs) || is_void_pointer(l)) {
+        Ok(if is_void_pointer(l) {
+            l.clone()
+        } else {
+            r.clone()
+        })
+    } else {
+        error(format!(
+            "type mismatch in conditional expression ({} and {})",
+            type_name(l),
+            type_name(r)
+        ))
+    }
+}
+
+fn check_pointer_arithmetic(_type: &TypeNode) -> Result<(), ResolverError> {
+    let pointee = pointee(_type).unwrap();
+    if is_function(&pointee) || is_void(&pointee) {
+        error(format!("arithmetic on {}", type_name(_type)))?;
+    }
+    Ok(())
+}
+
+/// Checks that a value of type `src` can be stored into an object of type `dst`.
+fn check_assignment(dst: &TypeNode, src: &TypeNode, expr: &ExprNode) -> Result<(), ResolverError> {
+    let valid = if is_integer(dst) {
+        is_integer(src)
+    } else if is_pointer(dst) {
+        (is_pointer(src) && pointers_compatible(dst, src)) || is_null_pointer_constant(src, expr)
+    } else {
+        !is_array(dst) && !is_function(dst) && same_type(dst, src)
+    };
+    if !valid {
+        error(format!(
+            "incompatible types: cannot assign {} to {}",
+            type_name(src),
+            type_name(dst)
+        ))?;
+    }
+    Ok(())
+}
+
+fn check_modifiable(
+    (_type, lvalue): (TypeNode, bool),
+    op: &str,
+) -> Result<TypeNode, ResolverError> {
+    if !lvalue || is_array(&_type) || is_function(&_type) {
+        error(format!(
+            "invalid expression: operand of {} cannot be assigned",
+            op
+        ))?;
+    }
+    Ok(_type)
+}
+
+fn check_term(term: &TermNode, scope: &Rc<Scope>) -> Result<(TypeNode, bool), ResolverError> {
+    match term {
+        TermNode::Cast(_type, term) => {
+            let dst = expand(_type, scope)?;
+            let src = decay(&check_term(term, scope)?.0);
+            let valid = is_void(&dst) || (is_scalar(&dst) && is_scalar(&src));
+            if !valid {
+                error(format!(
+                    "invalid cast from {} to {}",
+                    type_name(&src),
+                    type_name(&dst)
+                ))?;
+            }
+            Ok((dst, false))
+        }
+        TermNode::Unary(unary) => check_unary(unary, scope),
+    }
+}
+
+fn check_unary(unary: &UnaryNode, scope: &Rc<Scope>) -> Result<(TypeNode, bool), ResolverError> {
+    match unary {
+        UnaryNode::Increment(operand) => increment_type(check_unary(operand, scope)?, "++"),
+        UnaryNode::Decrement(operand) => increment_type(check_unary(operand, scope)?, "--"),
+        UnaryNode::Plus(term) | UnaryNode::Minus(term) | UnaryNode::Tilde(term) => {
+            let _type = decay(&check_term(term, scope)?.0);
+            if !is_integer(&_type) {
+                let op = match unary {
+                    UnaryNode::Plus(_) => "+",
+                    UnaryNode::Minus(_) => "-",
+                    _ => "~",
+                };
+                error(format!(
+                    "invalid operand to unary {} ({})",
+                    op,
+                    type_name(&_type)
+                ))?;
+            }
+            Ok((integer_promotion(&_type), false))
+        }
+        UnaryNode::Not(term) => {
+            let _type = decay(&check_term(term, scope)?.0);
+            if !is_scalar(&_type) {
+                error(format!("invalid operand to ! ({})", type_name(&_type)))?;
+            }
+            Ok((int_type(), false))
+        }
+        UnaryNode::Star(term) => {
+            let _type = decay(&check_term(term, scope)?.0);
+            match pointee(&_type) {
+                Some(pointee) if is_void(&pointee) => error("cannot dereference a void pointer"),
+                Some(pointee) => Ok((pointee, true)),
+                None => error(format!(
+                    "invalid operand to unary *: {} is not a pointer",
+                    type_name(&_type)
+                )),
+            }
+        }
+        UnaryNode::And(term) => {
+            let (_type, lvalue) = check_term(term, scope)?;
+            if !lvalue && !is_function(&_type) {
+                error("invalid operand to unary &: operand is not assignable")?;
+            }
+            Ok((pointer_to(&_type), false))
+        }
+        UnaryNode::SizeofUnary(unary) => {
+            let (_type, _) = check_unary(unary, scope)?;
+            check_sizeof(&_type)?;
+            Ok((long_type(false), false))
+        }
+        UnaryNode::SizeofType(_type) => {
+            check_sizeof(&expand(_type, scope)?)?;
+            Ok((long_type(false), false))
+        }
+        UnaryNode::Suffix(primary, suffix) => {
+            let value = check_primary(primary, scope)?;
+            check_suffix(value, suffix, scope)
+        }
+        UnaryNode::Primary(primary) => check_primary(primary, scope),
+    }
+}
+
+fn check_sizeof(_type: &TypeNode) -> Result<(), ResolverError> {
+    if is_void(_type) || is_function(_type) {
+        error(format!(
+            "invalid application of sizeof to {}",
+            type_name(_type)
+        ))?;
+    }
+    Ok(())
+}
+
+fn increment_type(value: (TypeNode, bool), op: &str) -> Result<(TypeNode, bool), ResolverError> {
+    let _type = check_modifiable(value, op)?;
+    if is_pointer(&_type) {
+        check_pointer_arithmetic(&_type)?;
+    } else if !is_integer(&_type) {
+        error(format!("invalid operand to {} ({})", op, type_name(&_type)))?;
+    }
+    Ok((_type, false))
+}
+
+fn check_suffix(
+    (_type, lvalue): (TypeNode, bool),
+    suffix: &SuffixOp,
+    scope: &Rc<Scope>,
+) -> Result<(TypeNode, bool), ResolverError> {
+    let (value, next) = match suffix {
+        SuffixOp::SuffixNone => return Ok((_type, lvalue)),
+        SuffixOp::Increment(next) => (increment_type((_type, lvalue), "++")?, next),
+        SuffixOp::Decrement(next) => (increment_type((_type, lvalue), "--")?, next),
+        SuffixOp::Dot(name, next) => {
+            if !is_struct_or_union(&_type) {
+                error(format!(
+                    "request for member {} in {}, which is not a struct or union",
+                    name,
+                    type_name(&_type)
+                ))?;
+            }
+            ((member_type(&_type, name, scope)?, lvalue), next)
+        }
+        SuffixOp::Arrow(name, next) => match pointee(&decay(&_type)) {
+            Some(pointee) if is_struct_or_union(&pointee) => {
+                ((member_type(&pointee, name, scope)?, true), next)
+            }
+            _ => error(format!(
+                "request for member {} in {}, which is not a pointer to a struct or union",
+                name,
+                type_name(&_type)
+            ))?,
+        },
+        SuffixOp::Array(idx, next) => {
+            let base = decay(&_type);
+            let index = type_of_expr(idx, scope)?;
+            let element = match (pointee(&base), pointee(&index)) {
+                (Some(element), None) if is_integer(&index) => element,
+                (None, Some(element)) if is_integer(&base) => element,
+                (Some(_), _) => error(format!(
+                    "array subscript is not an integer ({})",
+                    type_name(&index)
+                ))
+                .map_err(|e| e.or_span(idx.span()))?,
+                _ => error(format!(
+                    "subscripted value is not an array or a pointer ({})",
+                    type_name(&_type)
+                ))?,
+            };
+            if is_void(&element) {
+                error("cannot subscript a void pointer")?;
+            }
+            ((element, true), next)
+        }
+        SuffixOp::CallFu(args, next, _) => ((call_type(&_type, args, scope)?, false), next),
+    };
+    check_suffix(value, next, scope)
+}
+
+fn call_type(
+    _type: &TypeNode,
+    args: &[ExprNode],
+    scope: &Rc<Scope>,
+) -> Result<TypeNode, ResolverError> {
+    let fun = match pointee(&decay(_type)) {
+        Some(fun) if is_function(&fun) => fun,
+        _ => error(format!(
+            "called object of type {} is not a function",
+            type_name(_type)
+        ))?,
+    };
+    let mut return_type = fun.clone();
+    let params = match return_type.suffixs.pop() {
+        Some(TypeSuffix::Params(params)) => params,
+        _ => unreachable!(),
+    };
+
+    let (fixed, variable) = match &params {
+        ParamsNode::Void => (vec![], false),
+        ParamsNode::Some { fixed, variable } => (fixed.clone(), *variable),
+    };
+    if args.len() < fixed.len() || (!variable && args.len() > fixed.len()) {
+        error(format!(
+            "wrong number of arguments: expected {}, found {}",
+            fixed.len(),
+            args.len()
+        ))?;
+    }
+    for (i, arg) in args.iter().enumerate() {
+        let src = type_of_expr(arg, scope)?;
+        if let Some(param) = fixed.get(i) {
+            let dst = decay(&expand(&param._type, scope)?);
+            check_assignment(&dst, &src, arg)
+                .map_err(|e| ResolverError {
+                    message: format!("argument {}: {}", i + 1, e.message),
+                    span: e.span,
+                })
+                .map_err(|e| e.or_span(arg.span()))?;
+        } else if is_void(&src) {
+            error(format!("argument {} has void type", i + 1))
+                .map_err(|e| e.or_span(arg.span()))?;
+        }
+    }
+    Ok(return_type)
+}
+
+fn member_type(_type: &TypeNode, name: &str, scope: &Rc<Scope>) -> Result<TypeNode, ResolverError> {
+    let (kind, tag, entity) = match &_type.base {
+        TypeBaseNode::Struct(tag, entity) => ("struct", tag, entity),
+        TypeBaseNode::Union(tag, entity) => ("union", tag, entity),
+        _ => unreachable!(),
+    };
+    let entity = entity
+        .as_ref()
+        .map(|e| e.as_ref().clone())
+        .or_else(|| get_ref(scope, tag));
+    let member_list = match entity {
+        Some(Entity::Struct { member_list }) | Some(Entity::Union { member_list }) => member_list,
+        _ => error(format!("{} {} is not defined", kind, tag))?,
+    };
+    match member_list.iter().find(|m| m.name == name) {
+        Some(Member { _type, .. }) => expand(_type, scope),
+        None => error(format!("{} {} has no member named {}", kind, tag, name)),
+    }
+}
+
+fn check_primary(
+    primary: &PrimaryNode,
+    scope: &Rc<Scope>,
+) -> Result<(TypeNode, bool), ResolverError> {
+    match primary {
+        PrimaryNode::Integer(i) => {
+            if i32::try_from(*i).is_ok() {
+                Ok((int_type(), false))
+            } else {
+                Ok((long_type(true), false))
+            }
+        }
+        PrimaryNode::Char(_) => Ok((int_type(), false)),
+        PrimaryNode::String(s) => Ok((
+            TypeNode {
+                base: TypeBaseNode::Char,
+                suffixs: vec![TypeSuffix::ArrayWithValue(s.len() as i32 + 1)],
+                span: Span::default(),
+            },
+            false,
+        )),
+        PrimaryNode::Identifier(name, entity) => match entity {
+            Some(Entity::Variable { _type, .. }) => Ok((expand(_type, scope)?, true)),
+            Some(Entity::Constant { _type, .. }) => Ok((expand(_type, scope)?, false)),
+            Some(Entity::Function {
+                return_type,
+                params,
+                ..
+            }) => {
+                let mut _type = expand(return_type, scope)?;
+                _type.suffixs.push(TypeSuffix::Params(params.clone()));
+                Ok((_type, false))
+            }
+            Some(_) => error(format!("{} is not a value", name)),
+            None => error(format!("{} is not defined", name)),
+        },
+        PrimaryNode::Expr(expr) => check_expr(expr, scope),
+    }
+}
+
+/// Replace typedef names with the types they stand for.
+pub fn expand(_type: &TypeNode, scope: &Rc<Scope>) -> Result<TypeNode, ResolverError> {
+    if let TypeBaseNode::Identifier(name, entity) = &_type.base {
+        let entity = entity
+            .as_ref()
+            .map(|e| e.as_ref().clone())
+            .or_else(|| get_ref(scope, name));
+        let mut base = match entity {
+            Some(Entity::TypeDef { _type }) => expand(&_type, scope)?,
+            _ => error(format!("type {} is not defined", name))?,
+        };
+        base.suffixs.extend(_type.suffixs.iter().cloned());
+        base.span = _type.span;
+        Ok(base)
+    } else {
+        Ok(_type.clone())
+    }
+}
+
+/// Convert arrays to pointers to their first element and functions to function pointers.
+pub fn decay(_type: &TypeNode) -> TypeNode {
+    let mut _type = _type.clone();
+    match _type.suffixs.last() {
+        Some(TypeSuffix::Array) | Some(TypeSuffix::ArrayWithValue(_)) => {
+            *_type.suffixs.last_mut().unwrap() = TypeSuffix::Pointer;
+        }
+        Some(TypeSuffix::Params(_)) => _type.suffixs.push(TypeSuffix::Pointer),
+        _ => {}
+    }
+    _type
+}
+
+/// Returns the type pointed to by a pointer or the element type of an array.
+pub fn pointee(_type: &TypeNode) -> Option<TypeNode> {
+    match _type.suffixs.last() {
+        Some(TypeSuffix::Pointer)
+        | Some(TypeSuffix::Array)
+        | Some(TypeSuffix::ArrayWithValue(_)) => {
+            let mut _type = _type.clone();
+            _type.suffixs.pop();
+            Some(_type)
+        }
+        _ => None,
+    }
+}
+
+pub fn pointer_to(_type: &TypeNode) -> TypeNode {
+    let mut _type = _type.clone();
+    _type.suffixs.push(TypeSuffix::Pointer);
+    _type
+}
+
+/// Returns the size in bytes and the signedness of an integer type.
+pub fn integer_kind(_type: &TypeNode) -> Option<(u8, bool)> {
+    if !_type.suffixs.is_empty() {
+        return None;
+    }
+    match &_type.base {
+        TypeBaseNode::Char => Some((1, true)),
+        TypeBaseNode::Short => Some((2, true)),
+        TypeBaseNode::Int | TypeBaseNode::Enum(_, _) => Some((4, true)),
+        TypeBaseNode::Long => Some((8, true)),
+        TypeBaseNode::UnsignedChar => Some((1, false)),
+        TypeBaseNode::UnsignedShort => Some((2, false)),
+        TypeBaseNode::UnsignedInt => Some((4, false)),
+        TypeBaseNode::UnsignedLong => Some((8, false)),
+        _ => None,
+    }
+}
+
+fn integer_type(size: u8, signed: bool) -> TypeNode {
+    let base = match (size, signed) {
+        (1, true) => TypeBaseNode::Char,
+        (2, true) => TypeBaseNode::Short,
+        (4, true) => TypeBaseNode::Int,
+        (8, true) => TypeBaseNode::Long,
+        (1, false) => TypeBaseNode::UnsignedChar,
+        (2, false) => TypeBaseNode::UnsignedShort,
+        (4, false) => TypeBaseNode::UnsignedInt,
+        _ => TypeBaseNode::UnsignedLong,
+    };
+    TypeNode {
+        base,
+        suffixs: vec![],
+        span: Span::default(),
+    }
+}
+
+fn int_type() -> TypeNode {
+    integer_type(4, true)
+}
+
+fn long_type(signed: bool) -> TypeNode {
+    integer_type(8, signed)
+}
+
+/// Types narrower than `int` are promoted to `int`.
+pub fn integer_promotion(_type: &TypeNode) -> TypeNode {
+    match integer_kind(_type) {
+        Some((size, _)) if size < 4 => int_type(),
+        Some((size, signed)) => integer_type(size, signed),
+        None => _type.clone(),
+    }
+}
+
+/// Returns the common type of the operands of an arithmetic operator.
+pub fn usual_arithmetic_conversion(lhs: &TypeNode, rhs: &TypeNode) -> TypeNode {
+    let (lsize, lsigned) = integer_kind(&integer_promotion(lhs)).unwrap();
+    let (rsize, rsigned) = integer_kind(&integer_promotion(rhs)).unwrap();
+    if lsize == rsize {
+        integer_type(lsize, lsigned && rsigned)
+    } else if lsize > rsize {
+        integer_type(lsize, lsigned)
+    } else {
+        integer_type(rsize, rsigned)
+    }
+}
+
+pub fn is_integer(_type: &TypeNode) -> bool {
+    integer_kind(_type).is_some()
+}
+
+pub fn is_signed(_type: &TypeNode) -> bool {
+    matches!(integer_kind(_type), Some((_, true)))
+}
+
+pub fn is_pointer(_type: &TypeNode) -> bool {
+    matches!(_type.suffixs.last(), Some(TypeSuffix::Pointer))
+}
+
+pub fn is_array(_type: &TypeNode) -> bool {
+    matches!(
+        _type.suffixs.last(),
+        Some(TypeSuffix::Array) | Some(TypeSuffix::ArrayWithValue(_))
+    )
+}
+
+pub fn is_function(_type: &TypeNode) -> bool {
+    matches!(_type.suffixs.last(), Some(TypeSuffix::Params(_)))
+}
+
+pub fn is_void(_type: &TypeNode) -> bool {
+    _type.suffixs.is_empty() && matches!(_type.base, TypeBaseNode::Void)
+}
+
+pub fn is_scalar(_type: &TypeNode) -> bool {
+    is_integer(_type) || is_pointer(_type) || is_array(_type) || is_function(_type)
+}
+
+pub fn is_struct_or_union(_type: &TypeNode) -> bool {
+    _type.suffixs.is_empty()
+        && matches!(
+            _type.base,
+            TypeBaseNode::Struct(_, _) | TypeBaseNode::Union(_, _)
+        )
+}
+
+fn is_void_pointer(_type: &TypeNode) -> bool {
+    is_pointer(_type) && pointee(_type).is_some_and(|t| is_void(&t))
+}
+
+fn pointers_compatible(lhs: &TypeNode, rhs: &TypeNode) -> bool {
+    same_type(lhs, rhs) || is_void_pointer(lhs) || is_void_pointer(rhs)
+}
+
+fn is_null_pointer_constant(_type: &TypeNode, expr: &ExprNode) -> bool {
+    is_integer(_type) && matches!(eval_const_expr(expr), Ok(0))
+}
+
+/// Compare two typedef-expanded types.
+pub fn same_type(lhs: &TypeNode, rhs: &TypeNode) -> bool {
+    let same_base = match (&lhs.base, &rhs.base) {
+        (TypeBaseNode::Struct(a, _), TypeBaseNode::Struct(b, _))
+        | (TypeBaseNode::Union(a, _), TypeBaseNode::Union(b, _))
+        | (TypeBaseNode::Enum(a, _), TypeBaseNode::Enum(b, _))
+        | (TypeBaseNode::Identifier(a, _), TypeBaseNode::Identifier(b, _)) => a == b,
+        (a, b) => std::mem::discriminant(a) == std::mem::discriminant(b),
+    };
+    same_base
+        && lhs.suffixs.len() == rhs.suffixs.len()
+        && lhs
+            .suffixs
+            .iter()
+            .zip(rhs.suffixs.iter())
+            .all(|suffix| match suffix {
+                (TypeSuffix::Pointer, TypeSuffix::Pointer) => true,
+                (TypeSuffix::ArrayWithValue(a), TypeSuffix::ArrayWithValue(b)) => a == b,
+                (TypeSuffix::Array, TypeSuffix::Array | TypeSuffix::ArrayWithValue(_))
+                | (TypeSuffix::ArrayWithValue(_), TypeSuffix::Array) => true,
+                (TypeSuffix::Params(a), TypeSuffix::Params(b)) => same_params(a, b),
+                _ => false,
+            })
+}
+
+fn same_params(lhs: &ParamsNode, rhs: &ParamsNode) -> bool {
+    match (lhs, rhs) {
+        (ParamsNode::Void, ParamsNode::Void) => true,
+        (
+            ParamsNode::Some {
+                fixed: a,
+                variable: va,
+            },
+            ParamsNode::Some {
+                fixed: b,
+                variable: vb,
+            },
+        ) => {
+            va == vb
+                && a.len() == b.len()
+                && a.iter()
+                    .zip(b.iter())
+                    .all(|(a, b)| same_type(&a._type, &b._type))
+        }
+        _ => false,
+    }
+}
+
+/// Format a type the way it is written in the source.
+pub fn type_name(_type: &TypeNode) -> String {
+    let mut name = match &_type.base {
+        TypeBaseNode::Void => "void".to_string(),
+        TypeBaseNode::Char => "char".into(),
+        TypeBaseNode::Short => "short".into(),
+        TypeBaseNode::Int => "int".into(),
+        TypeBaseNode::Long => "long".into(),
+        TypeBaseNode::UnsignedChar => "unsigned char".into(),
+        TypeBaseNode::UnsignedShort => "unsigned short".into(),
+        TypeBaseNode::UnsignedInt => "unsigned int".into(),
+        TypeBaseNode::UnsignedLong => "unsigned long".into(),
+        TypeBaseNode::Struct(name, _) => format!("struct {}", name),
+        TypeBaseNode::Union(name, _) => format!("union {}", name),
+        TypeBaseNode::Enum(name, _) => format!("enum {}", name),
+        TypeBaseNode::Identifier(name, _) => name.clone(),
+    };
+    for suffix in _type.suffixs.iter() {
+        match suffix {
+            TypeSuffix::Pointer => name.push('*'),
+            TypeSuffix::Array => name.push_str("[]"),
+            TypeSuffix::ArrayWithValue(n) => name.push_str(&format!("[{}]", n)),
+            TypeSuffix::Params(ParamsNode::Void) => name.push_str("(void)"),
+            TypeSuffix::Params(ParamsNode::Some { fixed, variable }) => {
+                let mut params = fixed
+                    .iter()
+                    .map(|p| type_name(&p._type))
+                    .collect::<Vec<_>>();
+                if *variable {
+                    params.push("...".into());
+                }
+                name.push_str(&format!("({})", params.join(", ")));
+            }
+        }
+    }
+    name
+}
+
+#[cfg(test)]
+fn check_source(source: &str) -> Result<Rc<Scope>, ResolverError> {
+    use super::variable_scope::gen_scope_toplevel;
+    use std::rc::Weak;
+
+    let mut nodes = crate::node::parse(source).unwrap();
+    let scope = gen_scope_toplevel(&mut nodes, Rc::new(Scope::default()), Weak::new(), false)?;
+    let scope = gen_scope_toplevel(&mut nodes, scope, Weak::new(), true)?;
+    type_check(&nodes, &scope)?;
+    Ok(scope)
+}
+
+#[test]
+fn test_type_check() {
+    let ok = r#"
+        struct Point {
+            int x;
+            int y;
+        }
+        typedef struct Point point;
+
+        int sum(int* p, long n) {
+            long i = 0;
+            int s = 0;
+            while (i < n) {
+                s = s + p[i];
+                i = i + 1;
+            }
+            return s;
+        }
+
+        int main(void) {
+            int[4] a;
+            int* p = a;
+            point pt;
+            point* pp = &pt;
+            char* s = "hello";
+            pt.x = 1;
+            pp->y = *p + a[1] + s[0];
+            p = p + 1;
+            p = 0;
+            ++pt.x;
+            pt.y++;
+            return sum(a, p - a) + sizeof(point) + (p == 0);
+        }
+    "#;
+    check_source(ok).unwrap();
+
+    let message = |body: &str| {
+        let source = format!(
+            "struct S {{ int memb; }}\nint main(void) {{\n    struct S s;\n    int* p;\n    {}\n}}",
+            body
+        );
+        check_source(&source).unwrap_err().message
+    };
+    assert_eq!(
+        message("1[0];"),
+        "subscripted value is not an array or a pointer (int)"
+    );
+    assert_eq!(message("p[p];"), "array subscript is not an integer (int*)");
+    assert_eq!(
+        message("1.memb;"),
+        "request for member memb in int, which is not a struct or union"
+    );
+    assert_eq!(
+        message("1->memb;"),
+        "request for member memb in int, which is not a pointer to a struct or union"
+    );
+    assert_eq!(message("s.nope;"), "struct S has no member named nope");
+    assert_eq!(
+        message("*1;"),
+        "invalid operand to unary *: int is not a pointer"
+    );
+    assert_eq!(
+        message("&1;"),
+        "invalid operand to unary &: operand is not assignable"
+    );
+    assert_eq!(
+        message("++1;"),
+        "invalid expression: operand of ++ cannot be assigned"
+    );
+    assert_eq!(
+        message("1 = 2;"),
+        "invalid expression: operand of = cannot be assigned"
+    );
+    assert_eq!(
+        message("p = 1;"),
+        "incompatible types: cannot assign int to int*"
+    );
+    assert_eq!(
+        message("int a = s;"),
+        "incompatible types: cannot assign struct S to int"
+    );
+    assert_eq!(
+        message("p = p * 2;"),
+        "invalid operands to binary * (int* and int)"
+    );
+    assert_eq!(
+        message("return;"),
+        "function returning int should return a value"
+    );
+    assert_eq!(
+        message("main(1);"),
+        "wrong number of arguments: expected 0, found 1"
+    );
+    assert_eq!(message("if (s) {}"), "condition must be a scalar value");
+}
+
+#[test]
+fn test_arithmetic_conversion() {
+    let scope = check_source(
+        r#"
+        int main(void) {
+            char c;
+            unsigned int u;
+            long l;
+            unsigned long ul;
+            int* p;
+            return 0;
+        }
+    "#,
+    )
+    .unwrap();
+    let scope = scope.localscope.borrow()[0].clone();
+
+    let type_of = |source: &str| {
+        let mut expr = crate::node::expr::parse_expr_node(
+            <crate::CBCScanner as pest::Parser<crate::Rule>>::parse(crate::Rule::EXPR, source)
+                .unwrap()
+                .next()
+                .unwrap(),
+        )
+        .unwrap();
+        super::variable_scope::get_variables_expr(&mut expr, &scope).unwrap();
+        type_name(&type_of_expr(&expr, &scope).unwrap())
+    };
+    assert_eq!(type_of("c"), "char");
+    assert_eq!(type_of("c + c"), "int");
+    assert_eq!(type_of("-c"), "int");
+    assert_eq!(type_of("u + 1"), "unsigned int");
+    assert_eq!(type_of("u + l"), "long");
+    assert_eq!(type_of("ul + 1"), "unsigned long");
+    assert_eq!(type_of("c << l"), "int");
+    assert_eq!(type_of("p + 1"), "int*");
+    assert_eq!(type_of("p - p"), "long");
+    assert_eq!(type_of("*p"), "int");
+    assert_eq!(type_of("&p"), "int**");
+    assert_eq!(type_of("p < p"), "int");
+    assert_eq!(type_of("\"abc\""), "char*");
+    assert_eq!(type_of("sizeof c"), "unsigned long");
+    assert_eq!(type_of("sizeof(int[4])"), "unsigned long");
+    assert_eq!(type_of("4294967296"), "long");
+}
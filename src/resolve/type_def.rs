@@ -22,15 +22,15 @@ fn dfs(map: &HashMap<TypeDep, Vec<TypeDep>>, v: TypeDep) -> (bool, Vec<TypeDep>)
     hist.push(v.clone());
 
     while let Some(n) = todo.pop() {
-        *seen.get_mut(&n).unwrap() = true;
+        *seen.get_mut(n).unwrap() = true;
 
-        for next in map[&n].iter() {
-            if seen[&next] {
+        for next in map[n].iter() {
+            if seen[next] {
                 return (true, hist);
             }
 
-            *seen.get_mut(&next).unwrap() = true;
-            todo.push(&next);
+            *seen.get_mut(next).unwrap() = true;
+            todo.push(next);
             hist.push(next.clone());
         }
     }
@@ -43,8 +43,8 @@ pub fn check_recursive_definition(map: &HashMap<TypeDep, Vec<TypeDep>>) -> Optio
         HashMap::from_iter(map.clone().into_keys().map(|k| (k, false)));
 
     for k in map.clone().keys() {
-        if !finished[&k] {
-            let (rec, hist) = dfs(&map, k.clone());
+        if !finished[k] {
+            let (rec, hist) = dfs(map, k.clone());
             if rec {
                 return Some(hist);
             }
@@ -68,9 +68,8 @@ pub fn scope_to_typedep(toplevel_scope: &Scope) -> HashMap<TypeDep, Vec<TypeDep>
                 for member in member_list {
                     let mut is_pointer = false;
                     for suf in member._type.suffixs.iter() {
-                        match suf {
-                            TypeSuffix::Pointer => is_pointer = true,
-                            _ => {}
+                        if let TypeSuffix::Pointer = suf {
+                            is_pointer = true
                         }
                     }
                     if is_pointer {
@@ -1,7 +1,8 @@
 #![allow(dead_code)]
+use crate::diagnostic::{Diagnostic, Span};
 use crate::node::def::def_var::{DefVars, Var};
 use crate::node::def::{DefNode, Member};
-use crate::node::expr::ExprNode;
+use crate::node::expr::{BinaryOp, ExprNode};
 use crate::node::param::ParamsNode;
 use crate::node::primary::PrimaryNode;
 use crate::node::stmt::StmtNode;
@@ -9,6 +10,8 @@ use crate::node::term::TermNode;
 use crate::node::type_::{TypeBaseNode, TypeNode};
 use crate::node::unary::{SuffixOp, UnaryNode};
 use crate::node::Node;
+use crate::resolve::label::check_labels;
+use crate::resolve::type_check::decay;
 use std::cell::RefCell;
 use std::collections::BTreeMap;
 use std::rc::{Rc, Weak};
@@ -16,6 +19,21 @@ use std::rc::{Rc, Weak};
 #[derive(Debug)]
 pub struct ResolverError {
     pub message: String,
+    pub span: Option<Span>,
+}
+
+impl Diagnostic for ResolverError {
+    fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    fn set_span(&mut self, span: Span) {
+        self.span = Some(span);
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -30,7 +48,7 @@ pub enum Entity {
     Variable {
         _type: TypeNode,
         is_static: bool,
-        init: Option<ExprNode>,
+        init: Option<Box<ExprNode>>,
     },
     Function {
         return_type: TypeNode,
@@ -46,6 +64,13 @@ pub enum Entity {
     TypeDef {
         _type: TypeNode,
     },
+    Enum {
+        enumerators: Vec<(String, i64)>,
+    },
+    Constant {
+        _type: TypeNode,
+        value: i64,
+    },
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -55,6 +80,8 @@ pub enum EntityType {
     Struct,
     Union,
     TypeDef,
+    Enum,
+    Constant,
 }
 
 impl Entity {
@@ -65,6 +92,8 @@ impl Entity {
             Entity::Struct { .. } => EntityType::Struct,
             Entity::Union { .. } => EntityType::Union,
             Entity::TypeDef { .. } => EntityType::TypeDef,
+            Entity::Enum { .. } => EntityType::Enum,
+            Entity::Constant { .. } => EntityType::Constant,
         }
     }
 }
@@ -73,6 +102,7 @@ pub fn contain(scope: &Rc<Scope>, name: &str) -> Result<(), ResolverError> {
     if scope.entities.borrow().contains_key(name) {
         Err(ResolverError {
             message: format!("{} is already defined", name),
+            span: None,
         })
     } else {
         Ok(())
@@ -91,122 +121,180 @@ pub fn gen_scope_toplevel(
 
     for node in nodes {
         match node {
-            Node::Def(def_node) => match def_node.as_mut() {
-                DefNode::Vars(vars) => apply_vars(vars, &scope)?,
-                DefNode::Fun(fun) => {
-                    if recursive {
-                        let local = Rc::new(Scope::default());
-
-                        match &fun.params {
-                            ParamsNode::Void => {}
-                            ParamsNode::Some { fixed, .. } => {
-                                for param in fixed.iter() {
-                                    local.entities.borrow_mut().insert(
-                                        param.name.clone(),
-                                        Entity::Variable {
-                                            _type: param._type.clone(),
-                                            is_static: false,
-                                            init: None,
-                                        },
-                                    );
-                                }
-                            }
-                        }
-
-                        let local = gen_scope_stmts(&mut fun.block, local, Rc::downgrade(&scope))?;
-
-                        scope.localscope.borrow_mut().push(local.clone());
-                        fun.scope = Some(local);
-                    } else {
-                        contain(&scope, &fun.name)?;
-
+            Node::Def(def_node) => {
+                let span = def_node.span();
+                gen_scope_def(def_node, &scope, recursive).map_err(|e| e.or_span(span))?;
+            }
+            Node::Extern(proto) => {
+                if !recursive {
+                    let exists = matches!(
+                        scope.entities.borrow().get(&proto.name),
+                        Some(Entity::Function { .. })
+                    );
+                    if !exists {
+                        contain(&scope, &proto.name)?;
                         scope.entities.borrow_mut().insert(
-                            fun.name.clone(),
+                            proto.name.clone(),
                             Entity::Function {
-                                return_type: fun._type.clone(),
-                                is_static: fun.is_static,
-                                params: fun.params.clone(),
+                                return_type: proto.return_type.clone(),
+                                is_static: false,
+                                params: proto.params.clone(),
                             },
                         );
                     }
                 }
-                DefNode::Struct { name, member_list } => {
-                    if !recursive {
-                        contain(&scope, name)?;
-                        scope.entities.borrow_mut().insert(
-                            name.clone(),
-                            Entity::Struct {
-                                member_list: member_list.clone(),
-                            },
-                        );
-                    } else {
-                        for Member { _type, name: _ } in member_list.iter_mut() {
-                            get_type_ref(&scope, _type)?;
+            }
+            Node::Import(_) => {}
+        }
+    }
+    Ok(scope)
+}
+
+pub fn gen_scope_def(
+    def_node: &mut DefNode,
+    scope: &Rc<Scope>,
+    recursive: bool,
+) -> Result<(), ResolverError> {
+    match def_node {
+        DefNode::Vars(vars) => apply_vars(vars, scope)?,
+        DefNode::Fun(fun) => {
+            if recursive {
+                check_labels(&fun.name, &fun.block)?;
+                let local = Rc::new(Scope::default());
+
+                match &fun.params {
+                    ParamsNode::Void => {}
+                    ParamsNode::Some { fixed, .. } => {
+                        for param in fixed.iter() {
+                            // Parameters declared as arrays or functions are pointers.
+                            local.entities.borrow_mut().insert(
+                                param.name.clone(),
+                                Entity::Variable {
+                                    _type: decay(&param._type),
+                                    is_static: false,
+                                    init: None,
+                                },
+                            );
                         }
                     }
                 }
-                DefNode::Union { name, member_list } => {
-                    if !recursive {
-                        contain(&scope, name)?;
-                        scope.entities.borrow_mut().insert(
-                            name.clone(),
-                            Entity::Union {
-                                member_list: member_list.clone(),
-                            },
-                        );
-                    } else {
-                        for Member { _type, name: _ } in member_list.iter_mut() {
-                            get_type_ref(&scope, _type)?;
-                        }
-                    }
+
+                let local = gen_scope_stmts(&mut fun.block, local, Rc::downgrade(scope))?;
+
+                scope.localscope.borrow_mut().push(local.clone());
+                fun.scope = Some(local);
+            } else {
+                contain(scope, &fun.name)?;
+
+                scope.entities.borrow_mut().insert(
+                    fun.name.clone(),
+                    Entity::Function {
+                        return_type: fun._type.clone(),
+                        is_static: fun.is_static,
+                        params: fun.params.clone(),
+                    },
+                );
+            }
+        }
+        DefNode::Struct {
+            name, member_list, ..
+        } => {
+            if !recursive {
+                contain(scope, name)?;
+                scope.entities.borrow_mut().insert(
+                    name.clone(),
+                    Entity::Struct {
+                        member_list: member_list.clone(),
+                    },
+                );
+            } else {
+                for Member { _type, name: _ } in member_list.iter_mut() {
+                    get_type_ref(scope, _type)?;
                 }
-                DefNode::Type { _type, ident } => {
-                    if !recursive {
-                        contain(&scope, &ident)?;
-                        scope.entities.borrow_mut().insert(
-                            ident.clone(),
-                            Entity::TypeDef {
-                                _type: _type.clone(),
-                            },
-                        );
-                    } else {
-                        get_type_ref(&scope, _type)?;
-                    }
+            }
+        }
+        DefNode::Union {
+            name, member_list, ..
+        } => {
+            if !recursive {
+                contain(scope, name)?;
+                scope.entities.borrow_mut().insert(
+                    name.clone(),
+                    Entity::Union {
+                        member_list: member_list.clone(),
+                    },
+                );
+            } else {
+                for Member { _type, name: _ } in member_list.iter_mut() {
+                    get_type_ref(scope, _type)?;
                 }
-                _ => todo!(),
-            },
-            _ => {}
+            }
+        }
+        DefNode::Type { _type, ident, .. } => {
+            if !recursive {
+                contain(scope, ident)?;
+                scope.entities.borrow_mut().insert(
+                    ident.clone(),
+                    Entity::TypeDef {
+                        _type: _type.clone(),
+                    },
+                );
+            } else {
+                get_type_ref(scope, _type)?;
+            }
+        }
+        DefNode::Enum {
+            name, enumerators, ..
+        } => {
+            if !recursive {
+                apply_enum(name, enumerators, scope)?;
+            }
         }
+        _ => todo!(),
     }
-    Ok(scope)
+    Ok(())
 }
 
 pub fn get_type_ref(scope: &Rc<Scope>, type_node: &mut TypeNode) -> Result<(), ResolverError> {
+    let span = type_node.span;
     match &mut type_node.base {
         TypeBaseNode::Struct(name, entity) => {
-            if let Some(e) = get_ref(&scope, &name) {
+            if let Some(e) = get_ref(scope, name) {
                 *entity = Some(Box::new(e));
             } else {
                 Err(ResolverError {
                     message: format!("struct {} is not defined", name),
+                    span: Some(span),
                 })?;
             }
         }
         TypeBaseNode::Union(name, entity) => {
-            if let Some(e) = get_ref(&scope, &name) {
+            if let Some(e) = get_ref(scope, name) {
                 *entity = Some(Box::new(e));
             } else {
                 Err(ResolverError {
                     message: format!("union {} is not defined", name),
+                    span: Some(span),
+                })?;
+            }
+        }
+        TypeBaseNode::Enum(name, entity) => {
+            if let Some(e) = get_ref(scope, name) {
+                *entity = Some(Box::new(e));
+            } else {
+                Err(ResolverError {
+                    message: format!("enum {} is not defined", name),
+                    span: Some(span),
                 })?;
             }
         }
         TypeBaseNode::Identifier(name, entity) => {
-            if let Some(e) = get_ref(&scope, &name) {
+            if let Some(e) = get_ref(scope, name) {
                 *entity = Some(Box::new(e));
             } else {
                 Err(ResolverError {
                     message: format!("type {} is not defined", name),
+                    span: Some(span),
                 })?;
             }
         }
@@ -215,6 +303,109 @@ pub fn get_type_ref(scope: &Rc<Scope>, type_node: &mut TypeNode) -> Result<(), R
     Ok(())
 }
 
+/// Register an enum type and its enumerators, which are integer constants.
+pub fn apply_enum(
+    name: &str,
+    enumerators: &mut [(String, Option<ExprNode>)],
+    scope: &Rc<Scope>,
+) -> Result<(), ResolverError> {
+    contain(scope, name)?;
+
+    let mut values = vec![];
+    let mut next = 0;
+    for (ident, expr) in enumerators.iter_mut() {
+        if let Some(expr) = expr {
+            get_variables_expr(expr, scope)?;
+            next = eval_const_expr(expr)?;
+        }
+        contain(scope, ident)?;
+        scope.entities.borrow_mut().insert(
+            ident.clone(),
+            Entity::Constant {
+                _type: TypeNode {
+                    base: TypeBaseNode::Int,
+                    suffixs: vec![],
+                    span: Span::default(),
+                },
+                value: next,
+            },
+        );
+        values.push((ident.clone(), next));
+        next += 1;
+    }
+
+    scope.entities.borrow_mut().insert(
+        name.into(),
+        Entity::Enum {
+            enumerators: values,
+        },
+    );
+    Ok(())
+}
+
+/// Evaluate an integer constant expression whose identifiers are already resolved.
+pub fn eval_const_expr(expr: &ExprNode) -> Result<i64, ResolverError> {
+    let error = || ResolverError {
+        message: "not an integer constant".into(),
+        span: Some(expr.span()),
+    };
+    match expr {
+        ExprNode::Term(term, _) => eval_const_term(term).ok_or_else(error),
+        ExprNode::BinaryOp { op, lhs, rhs, .. } => {
+            let lhs = eval_const_expr(lhs)?;
+            let rhs = eval_const_expr(rhs)?;
+            let value = match op {
+                BinaryOp::Add => lhs.wrapping_add(rhs),
+                BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                BinaryOp::Mul => lhs.wrapping_mul(rhs),
+                BinaryOp::Div => lhs.checked_div(rhs).ok_or_else(error)?,
+                BinaryOp::Mod => lhs.checked_rem(rhs).ok_or_else(error)?,
+                BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+                BinaryOp::Shr => lhs.wrapping_shr(rhs as u32),
+                BinaryOp::BitAnd => lhs & rhs,
+                BinaryOp::BitOr => lhs | rhs,
+                BinaryOp::BitExOr => lhs ^ rhs,
+                BinaryOp::And => (lhs != 0 && rhs != 0) as i64,
+                BinaryOp::Or => (lhs != 0 || rhs != 0) as i64,
+                BinaryOp::Ge => (lhs >= rhs) as i64,
+                BinaryOp::Le => (lhs <= rhs) as i64,
+                BinaryOp::Gt => (lhs > rhs) as i64,
+                BinaryOp::Lt => (lhs < rhs) as i64,
+                BinaryOp::Eq => (lhs == rhs) as i64,
+                BinaryOp::Ne => (lhs != rhs) as i64,
+            };
+            Ok(value)
+        }
+        _ => Err(error()),
+    }
+}
+
+fn eval_const_term(term: &TermNode) -> Option<i64> {
+    let unary = match term {
+        TermNode::Unary(unary) => unary,
+        TermNode::Cast(_, _) => return None,
+    };
+    match unary.as_ref() {
+        UnaryNode::Plus(term) => eval_const_term(term),
+        UnaryNode::Minus(term) => eval_const_term(term).map(i64::wrapping_neg),
+        UnaryNode::Tilde(term) => eval_const_term(term).map(|v| !v),
+        UnaryNode::Not(term) => eval_const_term(term).map(|v| (v == 0) as i64),
+        UnaryNode::Primary(primary) => eval_const_primary(primary),
+        _ => None,
+    }
+}
+
+/// Evaluate a constant primary expression such as a `case` label.
+pub fn eval_const_primary(primary: &PrimaryNode) -> Option<i64> {
+    match primary {
+        PrimaryNode::Integer(i) => Some(*i),
+        PrimaryNode::Char(c) => Some(*c as i64),
+        PrimaryNode::Identifier(_, Some(Entity::Constant { value, .. })) => Some(*value),
+        PrimaryNode::Expr(expr) => eval_const_expr(expr).ok(),
+        _ => None,
+    }
+}
+
 pub fn get_ref(scope: &Rc<Scope>, name: &str) -> Option<Entity> {
     match scope.entities.borrow().get(name) {
         Some(e) => Some(e.clone()),
@@ -238,7 +429,10 @@ pub fn gen_scope_stmts(
     }
 
     for node in nodes {
-        gen_scope_stmt(node, scope.clone(), Weak::new())?;
+        gen_scope_stmt(node, scope.clone(), Weak::new()).map_err(|e| match node.span() {
+            Some(span) => e.or_span(span),
+            None => e,
+        })?;
     }
     Ok(scope)
 }
@@ -257,51 +451,85 @@ pub fn gen_scope_stmt(
         StmtNode::Expr(expr) => {
             get_variables_expr(expr, &scope)?;
         }
-        StmtNode::Return { expr } => {
+        StmtNode::Return { expr, .. } => {
             if let Some(expr) = expr {
                 get_variables_expr(expr, &scope)?;
             }
         }
-        StmtNode::If { cond, then, _else } => {
+        StmtNode::If {
+            cond, then, _else, ..
+        } => {
             get_variables_expr(cond, &scope)?;
             gen_scope_stmt(then, Rc::new(Scope::default()), Rc::downgrade(&scope))?;
             gen_scope_stmt(_else, Rc::new(Scope::default()), Rc::downgrade(&scope))?;
         }
-        StmtNode::Block { stmts } => {
+        StmtNode::Block { stmts, .. } => {
             gen_scope_stmts(stmts, Rc::new(Scope::default()), Rc::downgrade(&scope))?;
         }
-        StmtNode::While { cond, stmt } => {
+        StmtNode::While { cond, stmt, .. } | StmtNode::DoWhile { cond, stmt, .. } => {
+            get_variables_expr(cond, &scope)?;
+            gen_scope_stmt(stmt, Rc::new(Scope::default()), Rc::downgrade(&scope))?;
+        }
+        StmtNode::For {
+            init,
+            cond,
+            term,
+            stmt,
+            ..
+        } => {
+            get_variables_expr(init, &scope)?;
             get_variables_expr(cond, &scope)?;
+            get_variables_expr(term, &scope)?;
             gen_scope_stmt(stmt, Rc::new(Scope::default()), Rc::downgrade(&scope))?;
         }
-        e => panic!("{:#?}", e),
+        StmtNode::Switch {
+            cond,
+            cases,
+            default,
+            ..
+        } => {
+            get_variables_expr(cond, &scope)?;
+            // All clauses share the scope of the switch body.
+            let body = Rc::new(Scope::default());
+            for (values, stmts) in cases.iter_mut() {
+                for value in values.iter_mut() {
+                    get_variables_primary(value, &scope)?;
+                }
+                gen_scope_stmts(stmts, body.clone(), Rc::downgrade(&scope))?;
+            }
+            if let Some(stmts) = default {
+                gen_scope_stmts(stmts, body, Rc::downgrade(&scope))?;
+            }
+        }
+        StmtNode::Label { stmt, .. } => {
+            gen_scope_stmt(stmt, scope.clone(), Weak::new())?;
+        }
+        StmtNode::Goto { .. }
+        | StmtNode::Break { .. }
+        | StmtNode::Continue { .. }
+        | StmtNode::None => {}
     }
     Ok(scope)
 }
 
 pub fn get_variables_expr(expr: &mut ExprNode, scope: &Rc<Scope>) -> Result<(), ResolverError> {
     match expr {
-        ExprNode::Term(term) => {
-            get_variables_term(term, scope)?;
+        ExprNode::Term(term, span) => {
+            get_variables_term(term, scope).map_err(|e| e.or_span(*span))?;
         }
-        ExprNode::Assign { term, expr } => {
+        ExprNode::Assign { term, expr, .. } => {
             get_variables_term(term, scope)?;
             get_variables_expr(expr, scope)?;
         }
-        ExprNode::AssignOp { op: _, term, expr } => {
+        ExprNode::AssignOp { term, expr, .. } => {
             get_variables_term(term, scope)?;
             get_variables_expr(expr, scope)?;
         }
-        ExprNode::BinaryOp { op: _, lhs, rhs } => {
+        ExprNode::BinaryOp { lhs, rhs, .. } => {
             get_variables_expr(lhs, scope)?;
             get_variables_expr(rhs, scope)?;
         }
-        ExprNode::TernaryOp {
-            op: _,
-            lhs,
-            mhs,
-            rhs,
-        } => {
+        ExprNode::TernaryOp { lhs, mhs, rhs, .. } => {
             get_variables_expr(lhs, scope)?;
             get_variables_expr(mhs, scope)?;
             get_variables_expr(rhs, scope)?;
@@ -313,7 +541,10 @@ pub fn get_variables_expr(expr: &mut ExprNode, scope: &Rc<Scope>) -> Result<(),
 
 pub fn get_variables_term(term: &mut TermNode, scope: &Rc<Scope>) -> Result<(), ResolverError> {
     match term {
-        TermNode::Cast(_, term) => get_variables_term(term, scope),
+        TermNode::Cast(_type, term) => {
+            get_type_ref(scope, _type)?;
+            get_variables_term(term, scope)
+        }
         TermNode::Unary(unary) => get_variables_unary(unary, scope),
     }
 }
@@ -329,9 +560,15 @@ pub fn get_variables_unary(unary: &mut UnaryNode, scope: &Rc<Scope>) -> Result<(
         | UnaryNode::Tilde(term)
         | UnaryNode::Star(term)
         | UnaryNode::And(term) => get_variables_term(term, scope),
-        UnaryNode::Suffix(primary, suffix) => resolve_suffixop(primary, suffix, scope),
+        UnaryNode::SizeofType(_type) => get_type_ref(scope, _type),
+        UnaryNode::Suffix(primary, suffix) => {
+            // Calls are resolved by `resolve_suffixop`.
+            if !matches!(suffix.as_ref(), SuffixOp::CallFu(..)) {
+                get_variables_primary(primary, scope)?;
+            }
+            resolve_suffixop(primary, suffix, scope)
+        }
         UnaryNode::Primary(primary) => get_variables_primary(primary, scope),
-        _ => todo!(),
     }
 }
 
@@ -346,10 +583,13 @@ pub fn resolve_suffixop(
             for arg in args {
                 get_variables_expr(arg, scope)?;
             }
-            if let PrimaryNode::Identifier(name, _) = primary {
+            if let PrimaryNode::Identifier(name, primary_entity) = primary {
                 if let Some(e) = get_ref(scope, name) {
+                    *primary_entity = Some(e.clone());
                     *entity = Some(e);
                 }
+            } else {
+                get_variables_primary(primary, scope)?;
             }
             resolve_suffixop(primary, s, scope)
         }
@@ -357,8 +597,10 @@ pub fn resolve_suffixop(
             get_variables_expr(idx, scope)?;
             resolve_suffixop(primary, suffix, scope)
         }
-        SuffixOp::Dot(_, suffix) => resolve_suffixop(primary, suffix, scope),
-        e => panic!("{:?}", e),
+        SuffixOp::Dot(_, suffix)
+        | SuffixOp::Arrow(_, suffix)
+        | SuffixOp::Increment(suffix)
+        | SuffixOp::Decrement(suffix) => resolve_suffixop(primary, suffix, scope),
     }
 }
 
@@ -373,9 +615,11 @@ pub fn get_variables_primary(
             } else {
                 Err(ResolverError {
                     message: format!("{} is not defined", name),
+                    span: None,
                 })?;
             }
         }
+        PrimaryNode::Expr(expr) => get_variables_expr(expr, scope)?,
         _ => {}
     }
 
@@ -393,7 +637,7 @@ pub fn apply_vars(vars: &mut DefVars, scope: &Rc<Scope>) -> Result<(), ResolverE
                     Entity::Variable {
                         _type: vars._type.clone(),
                         is_static: vars.is_static,
-                        init: Some(expr.clone()),
+                        init: Some(Box::new(expr.clone())),
                     },
                 );
             }
@@ -535,3 +779,27 @@ fn test_scope_struct_union() {
     assert!(scope_tree.entities.borrow().get("unionC").is_some());
     assert!(scope_tree.entities.borrow().get("unionD").is_some());
 }
+
+#[test]
+fn test_scope_enum() {
+    let mut nodes =
+        crate::node::parse("enum Color { Red, Green = 5, Blue, Mask = Blue * 2 + 'a' }").unwrap();
+    let scope =
+        gen_scope_toplevel(&mut nodes, Rc::new(Scope::default()), Weak::new(), false).unwrap();
+
+    let value = |name: &str| match get_ref(&scope, name) {
+        Some(Entity::Constant { value, .. }) => value,
+        e => panic!("{:?}", e),
+    };
+    assert_eq!(value("Red"), 0);
+    assert_eq!(value("Green"), 5);
+    assert_eq!(value("Blue"), 6);
+    assert_eq!(value("Mask"), 12 + 97);
+    assert!(matches!(
+        get_ref(&scope, "Color"),
+        Some(Entity::Enum { .. })
+    ));
+
+    let mut nodes = crate::node::parse("int Red; enum Color { Red }").unwrap();
+    assert!(gen_scope_toplevel(&mut nodes, Rc::new(Scope::default()), Weak::new(), false).is_err());
+}
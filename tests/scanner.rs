@@ -9,7 +9,7 @@ enum Token<'a> {
     ParseError,
 }
 
-fn test_one_token<'a>(rule: Rule, input: &'a str) -> Token {
+fn test_one_token(rule: Rule, input: &str) -> Token<'_> {
     let ret: Result<pest::iterators::Pairs<Rule>, pest::error::Error<Rule>> =
         CBCScanner::parse(rule, input);
 
@@ -24,7 +24,7 @@ fn test_one_token<'a>(rule: Rule, input: &'a str) -> Token {
     if string.len() == input.len() {
         Token::Some(string, rule)
     } else {
-        Token::Remaining(&string, &input[string.len()..], rule)
+        Token::Remaining(string, &input[string.len()..], rule)
     }
 }
 
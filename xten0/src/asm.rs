@@ -1,11 +1,13 @@
 //! x64 assembler.
 
+pub mod cmov;
 mod encoding;
 pub mod inst;
 mod obj;
 mod operand;
 mod writer;
 
+pub use cmov::WriteCmovExt;
 pub use inst::{WriteInst, WriteInstExt};
 pub use obj::*;
 pub use operand::*;
@@ -148,6 +150,21 @@ mod tests {
                 verw ax
             "#
         );
+        assert_asm!(
+            asm(|w| {
+                w.cmoveq(Rax, Rcx)?;
+                w.cmovneq(R10, memory(Rax + 8i8))?;
+                w.cmovlq(Rbx, R15)?;
+                w.cmovaeq(R12, memory(Rbp + Rcx * 4))?;
+                Ok(())
+            }),
+            r#"
+                cmove rax, rcx
+                cmovne r10, [rax + 8]
+                cmovl rbx, r15
+                cmovae r12, [rbp + rcx * 4]
+            "#
+        );
         assert_asm!(
             asm(|w| {
                 w.crc32b(R10D, Dl)?;
@@ -0,0 +1,411 @@
+//! Conditional moves, which are not part of the instruction table of `inst`.
+
+use super::encoding::*;
+use super::inst::WriteInst;
+use super::operand::*;
+use std::io;
+
+pub trait WriteCmovExt: io::Write {
+    fn cmovaeq<Op0, Op1>(&mut self, op0: Op0, op1: Op1) -> io::Result<()>
+    where
+        Cmovaeq<Op0, Op1>: WriteInst<Self>,
+    {
+        Cmovaeq(op0, op1).write_inst(self)
+    }
+
+    fn cmovaq<Op0, Op1>(&mut self, op0: Op0, op1: Op1) -> io::Result<()>
+    where
+        Cmovaq<Op0, Op1>: WriteInst<Self>,
+    {
+        Cmovaq(op0, op1).write_inst(self)
+    }
+
+    fn cmovbeq<Op0, Op1>(&mut self, op0: Op0, op1: Op1) -> io::Result<()>
+    where
+        Cmovbeq<Op0, Op1>: WriteInst<Self>,
+    {
+        Cmovbeq(op0, op1).write_inst(self)
+    }
+
+    fn cmovbq<Op0, Op1>(&mut self, op0: Op0, op1: Op1) -> io::Result<()>
+    where
+        Cmovbq<Op0, Op1>: WriteInst<Self>,
+    {
+        Cmovbq(op0, op1).write_inst(self)
+    }
+
+    fn cmoveq<Op0, Op1>(&mut self, op0: Op0, op1: Op1) -> io::Result<()>
+    where
+        Cmoveq<Op0, Op1>: WriteInst<Self>,
+    {
+        Cmoveq(op0, op1).write_inst(self)
+    }
+
+    fn cmovgeq<Op0, Op1>(&mut self, op0: Op0, op1: Op1) -> io::Result<()>
+    where
+        Cmovgeq<Op0, Op1>: WriteInst<Self>,
+    {
+        Cmovgeq(op0, op1).write_inst(self)
+    }
+
+    fn cmovgq<Op0, Op1>(&mut self, op0: Op0, op1: Op1) -> io::Result<()>
+    where
+        Cmovgq<Op0, Op1>: WriteInst<Self>,
+    {
+        Cmovgq(op0, op1).write_inst(self)
+    }
+
+    fn cmovleq<Op0, Op1>(&mut self, op0: Op0, op1: Op1) -> io::Result<()>
+    where
+        Cmovleq<Op0, Op1>: WriteInst<Self>,
+    {
+        Cmovleq(op0, op1).write_inst(self)
+    }
+
+    fn cmovlq<Op0, Op1>(&mut self, op0: Op0, op1: Op1) -> io::Result<()>
+    where
+        Cmovlq<Op0, Op1>: WriteInst<Self>,
+    {
+        Cmovlq(op0, op1).write_inst(self)
+    }
+
+    fn cmovneq<Op0, Op1>(&mut self, op0: Op0, op1: Op1) -> io::Result<()>
+    where
+        Cmovneq<Op0, Op1>: WriteInst<Self>,
+    {
+        Cmovneq(op0, op1).write_inst(self)
+    }
+}
+
+impl<W: io::Write + ?Sized> WriteCmovExt for W {}
+
+pub struct Cmovaeq<Op0, Op1>(pub Op0, pub Op1);
+
+/// cmovaeq r64 r64: Move if above or equal (CF=0).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovaeq<Gpr64, Gpr64> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 43 /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x43)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+/// cmovaeq r64 m64: Move if above or equal (CF=0).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovaeq<Gpr64, Memory> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 43 /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x43)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+pub struct Cmovaq<Op0, Op1>(pub Op0, pub Op1);
+
+/// cmovaq r64 r64: Move if above (CF=0 and ZF=0).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovaq<Gpr64, Gpr64> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 47 /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x47)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+/// cmovaq r64 m64: Move if above (CF=0 and ZF=0).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovaq<Gpr64, Memory> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 47 /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x47)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+pub struct Cmovbeq<Op0, Op1>(pub Op0, pub Op1);
+
+/// cmovbeq r64 r64: Move if below or equal (CF=1 or ZF=1).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovbeq<Gpr64, Gpr64> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 46 /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x46)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+/// cmovbeq r64 m64: Move if below or equal (CF=1 or ZF=1).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovbeq<Gpr64, Memory> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 46 /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x46)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+pub struct Cmovbq<Op0, Op1>(pub Op0, pub Op1);
+
+/// cmovbq r64 r64: Move if below (CF=1).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovbq<Gpr64, Gpr64> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 42 /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x42)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+/// cmovbq r64 m64: Move if below (CF=1).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovbq<Gpr64, Memory> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 42 /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x42)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+pub struct Cmoveq<Op0, Op1>(pub Op0, pub Op1);
+
+/// cmoveq r64 r64: Move if equal (ZF=1).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmoveq<Gpr64, Gpr64> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 44 /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x44)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+/// cmoveq r64 m64: Move if equal (ZF=1).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmoveq<Gpr64, Memory> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 44 /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x44)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+pub struct Cmovgeq<Op0, Op1>(pub Op0, pub Op1);
+
+/// cmovgeq r64 r64: Move if greater or equal (SF=OF).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovgeq<Gpr64, Gpr64> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 4D /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x4D)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+/// cmovgeq r64 m64: Move if greater or equal (SF=OF).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovgeq<Gpr64, Memory> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 4D /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x4D)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+pub struct Cmovgq<Op0, Op1>(pub Op0, pub Op1);
+
+/// cmovgq r64 r64: Move if greater (ZF=0 and SF=OF).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovgq<Gpr64, Gpr64> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 4F /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x4F)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+/// cmovgq r64 m64: Move if greater (ZF=0 and SF=OF).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovgq<Gpr64, Memory> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 4F /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x4F)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+pub struct Cmovleq<Op0, Op1>(pub Op0, pub Op1);
+
+/// cmovleq r64 r64: Move if less or equal (ZF=1 or SF!= OF).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovleq<Gpr64, Gpr64> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 4E /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x4E)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+/// cmovleq r64 m64: Move if less or equal (ZF=1 or SF!= OF).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovleq<Gpr64, Memory> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 4E /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x4E)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+pub struct Cmovlq<Op0, Op1>(pub Op0, pub Op1);
+
+/// cmovlq r64 r64: Move if less (SF!= OF).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovlq<Gpr64, Gpr64> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 4C /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x4C)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+/// cmovlq r64 m64: Move if less (SF!= OF).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovlq<Gpr64, Memory> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 4C /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x4C)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+pub struct Cmovneq<Op0, Op1>(pub Op0, pub Op1);
+
+/// cmovneq r64 r64: Move if not equal (ZF=0).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovneq<Gpr64, Gpr64> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 45 /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x45)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+/// cmovneq r64 m64: Move if not equal (ZF=0).
+impl<W: io::Write + ?Sized> WriteInst<W> for Cmovneq<Gpr64, Memory> {
+    fn write_inst(&self, w: &mut W) -> io::Result<()> {
+        // REX.W+ 0F 45 /r
+        let modrm = ModRM::new(self.0, self.1);
+        puts(w, modrm.rex_byte(true))?;
+        put(w, 0x0F)?;
+        put(w, 0x45)?;
+        put(w, modrm.byte())?;
+        puts(w, modrm.sib_byte())?;
+        puts(w, modrm.disp_bytes().into_iter().flatten())?;
+        Ok(())
+    }
+}
+
+fn put(w: &mut (impl io::Write + ?Sized), b: u8) -> io::Result<()> {
+    w.write_all(&[b])
+}
+
+fn puts(w: &mut (impl io::Write + ?Sized), bs: impl IntoIterator<Item = u8>) -> io::Result<()> {
+    for b in bs {
+        w.write_all(&[b])?;
+    }
+    Ok(())
+}
@@ -100,25 +100,27 @@ impl RelocType {
             RelocType::PcRel8 => {
                 // S + A - P
                 let value = def_pos as i64 + addend - use_pos as i64;
-                assert!(
-                    i8::MIN as i64 <= value && value <= i8::MAX as i64,
-                    "Cannot encode offset={} as PcRel8",
-                    value
-                );
+                let value = i8::try_from(value).map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("Cannot encode offset={} as PcRel8", value),
+                    )
+                })?;
                 buf.seek(io::SeekFrom::Start(use_pos))?;
-                buf.write_all(&(value as i8).to_le_bytes())?;
+                buf.write_all(&value.to_le_bytes())?;
                 Ok(true)
             }
             RelocType::PcRel32 => {
                 // S + A - P
                 let value = def_pos as i64 + addend - use_pos as i64;
-                assert!(
-                    i32::MIN as i64 <= value && value <= i32::MAX as i64,
-                    "Cannot encode offset={} as PcRel32",
-                    value
-                );
+                let value = i32::try_from(value).map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("Cannot encode offset={} as PcRel32", value),
+                    )
+                })?;
                 buf.seek(io::SeekFrom::Start(use_pos))?;
-                buf.write_all(&(value as i32).to_le_bytes())?;
+                buf.write_all(&value.to_le_bytes())?;
                 Ok(true)
             }
             RelocType::PcRelToAddressTable32 => Ok(false),
@@ -167,6 +169,9 @@ mod tests {
             RelocType::PcRelToAddressTable32.resolve_statically(&mut buf, 6, 2, 0),
             Ok(false)
         ));
+        assert!(RelocType::PcRel8
+            .resolve_statically(&mut buf, 200, 0, 0)
+            .is_err());
         assert!(matches!(
             buf.into_inner().as_slice(),
             &[0, 1, 2, 3, _, _, _, _, 8, _, _, 11],
@@ -14,6 +14,8 @@ pub struct Writer {
     bss: u64,
     defs: Vec<Option<Def>>, // Indexed by label.index
     uses: Vec<Use>,
+    branches: Vec<Branch>,
+    differences: Vec<Difference>,
     names: BTreeMap<String, Label>,
 }
 
@@ -27,6 +29,8 @@ impl Writer {
             bss: 0,
             defs: Vec::new(),
             uses: Vec::new(),
+            branches: Vec::new(),
+            differences: Vec::new(),
             names: BTreeMap::new(),
         }
     }
@@ -71,7 +75,13 @@ impl Writer {
     }
 
     /// Produce an object by resolving definitions and uses.
+    ///
+    /// Branches are relaxed first, so locations on `.text` that were obtained while writing are
+    /// no longer valid if any branch is widened.
     pub fn produce(mut self) -> io::Result<Object> {
+        self.relax_branches()?;
+        self.resolve_differences()?;
+
         let mut label_symbols = BTreeMap::new(); // label: Label -> symbol.name: String
 
         // Named labels remain on the object as symbols.
@@ -144,6 +154,165 @@ impl Writer {
     }
 }
 
+impl Writer {
+    /// Widen the branches whose target cannot be reached with a rel8 displacement, and turn the
+    /// branches into label uses.
+    ///
+    /// Every branch starts short, and widening a branch only moves the code after it further
+    /// away, so branches are widened until all of the remaining short ones reach their target.
+    fn relax_branches(&mut self) -> io::Result<()> {
+        if self.branches.is_empty() {
+            return Ok(());
+        }
+        if let Some(b) = self
+            .branches
+            .iter()
+            .find(|b| b.location.section != LocationSection::Text)
+        {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "Cannot relax a branch to label={} outside of .text",
+                    b.target.index
+                ),
+            ))?;
+        }
+        self.branches.sort_by_key(|b| b.location.pos);
+
+        let text = self.text.get_ref();
+        let growth = self
+            .branches
+            .iter()
+            .map(|b| match text[b.location.pos as usize] {
+                0xeb => 3,        // jmp rel8 -> jmp rel32
+                0x70..=0x7f => 4, // jcc rel8 -> 0f jcc rel32
+                op => unreachable!("Not a short branch: {:#x}", op),
+            })
+            .collect::<Vec<u64>>();
+        // Branches to labels that are not defined on .text are always widened.
+        let targets = self
+            .branches
+            .iter()
+            .map(|b| match self.defs[b.target.index] {
+                Some(def) if def.location.section == LocationSection::Text => {
+                    Some(def.location.pos)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut long = targets.iter().map(Option::is_none).collect::<Vec<_>>();
+        let shifts = loop {
+            // shifts[i] is the total growth of the widened branches before the i-th branch.
+            let mut shifts = vec![0];
+            for i in 0..self.branches.len() {
+                shifts.push(shifts[i] + if long[i] { growth[i] } else { 0 });
+            }
+            let shift = |pos: u64| shifts[self.branches.partition_point(|b| b.location.pos < pos)];
+
+            let mut changed = false;
+            for (i, b) in self.branches.iter().enumerate() {
+                if let (false, Some(target)) = (long[i], targets[i]) {
+                    let from = b.location.pos + shift(b.location.pos) + 2;
+                    let value = (target + shift(target)) as i64 - from as i64;
+                    if i8::try_from(value).is_err() {
+                        long[i] = true;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break shifts;
+            }
+        };
+        let shift = |pos: u64| shifts[self.branches.partition_point(|b| b.location.pos < pos)];
+
+        let mut relaxed = Vec::with_capacity(text.len() + *shifts.last().unwrap() as usize);
+        let mut copied = 0;
+        let mut uses = Vec::new();
+        for (i, b) in self.branches.iter().enumerate() {
+            let pos = b.location.pos as usize;
+            relaxed.extend_from_slice(&text[copied..pos]);
+            copied = pos + 2;
+            let location = Location::new(LocationSection::Text, relaxed.len() as u64);
+            match (long[i], text[pos]) {
+                (false, op) => {
+                    relaxed.extend_from_slice(&[op, 0]);
+                    uses.push(Use::new(
+                        location.offset(1),
+                        b.target,
+                        -1,
+                        RelocType::PcRel8,
+                    ));
+                }
+                (true, 0xeb) => {
+                    relaxed.extend_from_slice(&[0xe9, 0, 0, 0, 0]);
+                    uses.push(Use::new(
+                        location.offset(1),
+                        b.target,
+                        -4,
+                        RelocType::PcRel32,
+                    ));
+                }
+                (true, op) => {
+                    relaxed.extend_from_slice(&[0x0f, op + 0x10, 0, 0, 0, 0]);
+                    uses.push(Use::new(
+                        location.offset(2),
+                        b.target,
+                        -4,
+                        RelocType::PcRel32,
+                    ));
+                }
+            }
+        }
+        relaxed.extend_from_slice(&text[copied..]);
+
+        for def in self.defs.iter_mut().flatten() {
+            if def.location.section == LocationSection::Text {
+                def.location.pos += shift(def.location.pos);
+            }
+        }
+        for u in self.uses.iter_mut() {
+            if u.location.section == LocationSection::Text {
+                u.location.pos += shift(u.location.pos);
+            }
+        }
+        for d in self.differences.iter_mut() {
+            if d.location.section == LocationSection::Text {
+                d.location.pos += shift(d.location.pos);
+            }
+        }
+        self.uses.extend(uses);
+        self.branches.clear();
+        let len = relaxed.len() as u64;
+        self.text = Cursor::new(relaxed);
+        self.text.set_position(len);
+        Ok(())
+    }
+
+    /// Turn the label differences into label uses. Since `base` is defined on the same section,
+    /// `target - base` at `P` is `target + (P - base) - P`, which is a PC-relative use.
+    fn resolve_differences(&mut self) -> io::Result<()> {
+        for d in std::mem::take(&mut self.differences) {
+            match self.defs[d.base.index] {
+                Some(def) if def.location.section == d.location.section => {
+                    let addend = d.location.pos as i64 - def.location.pos as i64;
+                    self.uses
+                        .push(Use::new(d.location, d.target, addend, RelocType::PcRel32));
+                }
+                _ => Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "Cannot compute label={} - label={}: The base label is not defined on the same section as the difference.",
+                        d.target.index, d.base.index
+                    ),
+                ))?,
+            }
+        }
+        Ok(())
+    }
+}
+
 /// On the Writer, a specific position on an object is identified as a label.
 ///
 /// Labels may or may not be named. Named labels remain on the object as symbols.
@@ -159,6 +328,22 @@ struct Def {
     location: Location,
 }
 
+/// A branch to a label, written as a short jump at `location` and widened at object production
+/// time if necessary.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, new)]
+struct Branch {
+    location: Location,
+    target: Label,
+}
+
+/// A label difference `target - base`, written as 32-bit signed data at `location`.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, new)]
+struct Difference {
+    location: Location,
+    target: Label,
+    base: Label,
+}
+
 /// A label use. Uses that cannot be resolved at object production time become relocations.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, new)]
 struct Use {
@@ -196,6 +381,37 @@ pub trait SectionWrite {
         self.writer().r#use(location, label, addend, ty)
     }
 
+    /// Use the specified label as the target of the short jump that has just been written. The
+    /// jump is widened to rel32 at object production time if the label is out of range.
+    fn use_branch(&mut self, label: Label) {
+        let location = self.location().offset(-2);
+        self.writer().branches.push(Branch::new(location, label))
+    }
+
+    /// Write the absolute address of `label` as 64-bit data.
+    fn write_label_address(&mut self, label: Label) -> io::Result<()>
+    where
+        Self: io::Write,
+    {
+        self.write_all(&0u64.to_le_bytes())?;
+        self.use_relative(-8, label, 0, RelocType::Abs64);
+        Ok(())
+    }
+
+    /// Write `label - base` as 32-bit signed data. `base` must be defined on this section, while
+    /// `label` may be defined anywhere, such as the case labels of a jump table on .text.
+    fn write_label_difference(&mut self, label: Label, base: Label) -> io::Result<()>
+    where
+        Self: io::Write,
+    {
+        self.write_all(&0i32.to_le_bytes())?;
+        let location = self.location().offset(-4);
+        self.writer()
+            .differences
+            .push(Difference::new(location, label, base));
+        Ok(())
+    }
+
     /// Adjust alignment by writing invalid values.
     fn align(&mut self, align: u64) -> io::Result<()> {
         let current = self.location().pos;
@@ -293,6 +509,8 @@ impl<'a> SectionWrite for BssWriter<'a> {
 }
 
 // * In this implementation, we use `RelocType::PcRel32` for label uses by default.
+// * jmp instructions to a label start with a rel8 displacement, and are relaxed to rel32 by
+//   `Writer::produce` if the label is out of range.
 // * We use `RelocType::PcRel8` for jmp instructions if the operand is wrapped by `Short`.
 // * We use `RelocType::PcRelToAddressTable32` for call or mov instruction if the operand is wrapped
 //   by `AddressTable`. This may be necessary for linking shared objects.
@@ -303,6 +521,7 @@ impl<'a> SectionWrite for BssWriter<'a> {
 pub struct AddressTable<T>(pub T);
 
 /// Wrapper type for short jmp, used to force `RelocType::PcRel8` relocation for jmp instructions.
+/// Producing the object fails if the label is out of range.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
 pub struct Short<T>(pub T);
 
@@ -336,8 +555,8 @@ where
     W: Write + SectionWrite,
 {
     fn write_inst(&self, w: &mut W) -> io::Result<()> {
-        w.jmpq(0i32)?;
-        w.use_relative(-4, self.0, -4, RelocType::PcRel32);
+        w.jmpq(0i8)?;
+        w.use_branch(self.0);
         Ok(())
     }
 }
@@ -374,8 +593,8 @@ macro_rules! impl_conditional_jmp {
             W: Write + SectionWrite,
         {
             fn write_inst(&self, w: &mut W) -> io::Result<()> {
-                w.$method(0i32)?;
-                w.use_relative(-4, self.0, -4, RelocType::PcRel32);
+                w.$method(0i8)?;
+                w.use_branch(self.0);
                 Ok(())
             }
         }
@@ -620,6 +839,157 @@ mod tests {
         );
     }
 
+    #[test]
+    fn relax() {
+        let fill = |w: &mut Writer, n: usize| -> io::Result<()> {
+            for _ in 0..n {
+                w.movl(Eax, 0x1)?; // 5 bytes
+            }
+            Ok(())
+        };
+        let fill_asm = |n: usize| "mov eax, 0x1\n".repeat(n);
+
+        let mut jmp_ext_end = Location::new(LocationSection::Text, 0);
+        let o = write(|w| {
+            let ext = w.get_label("ext");
+            let near = w.issue_label();
+            let far = w.issue_label();
+            let top = w.issue_label();
+            let edge = w.issue_label();
+
+            w.define(top, false);
+            w.jmpq(near)?;
+            w.je(far)?;
+            fill(w, 2)?;
+            w.define(near, false);
+            // Reaches `edge` only if the jump to `far` stays short, which it cannot.
+            w.jne(edge)?;
+            fill(w, 25)?;
+            w.define(edge, false);
+            fill(w, 1)?;
+            w.define(far, false);
+            w.jl(top)?;
+            w.jmpq(ext)?;
+            jmp_ext_end = w.location();
+            w.retq()?;
+            Ok(())
+        });
+
+        assert_asm!(
+            o.text,
+            r#"
+                ltop:
+                  jmp lnear
+                  je lfar
+                  {}
+                lnear:
+                  jne ledge
+                  {}
+                ledge:
+                  {}
+                lfar:
+                  jl ltop
+                  jmp ext
+                  ret
+            "#,
+            fill_asm(2),
+            fill_asm(25),
+            fill_asm(1),
+        );
+        assert_eq!(o.text[0..2], [0xeb, 0x10]);
+        assert_eq!(o.text[2..4], [0x0f, 0x84]);
+        assert_eq!(o.text[18], 0x75);
+        assert_eq!(o.text[20 + 26 * 5..][..2], [0x0f, 0x8c]);
+        assert_eq!(
+            o.relocs,
+            vec![Reloc::new(
+                Location::new(LocationSection::Text, 20 + 26 * 5 + 6 + 1),
+                RelocTarget::Symbol("ext".to_string()),
+                -4,
+                RelocType::PcRel32
+            )]
+        );
+        // Locations obtained while writing do not account for the widened je, jl and jmp.
+        assert_eq!(jmp_ext_end.pos + 4 + 4 + 3, 20 + 26 * 5 + 6 + 5);
+    }
+
+    #[test]
+    fn short_out_of_range() {
+        let mut w = Writer::new();
+        let l = w.issue_label();
+        w.jmpq(Short(l)).unwrap();
+        for _ in 0..26 {
+            w.movl(Eax, 0x1).unwrap();
+        }
+        w.define(l, false);
+        assert!(w.produce().is_err());
+    }
+
+    #[test]
+    fn label_data() {
+        let o = write(|w| {
+            let start = w.issue_label();
+            let end = w.issue_label();
+            let table = w.issue_label();
+            let entry = w.issue_label();
+
+            w.define(start, false);
+            w.jmpq(end)?;
+            for _ in 0..30 {
+                w.movl(Eax, 0x1)?;
+            }
+            w.define(end, false);
+            w.retq()?;
+
+            w.rodata().define(table, false);
+            w.rodata().write_label_difference(start, table)?;
+            w.rodata().write_label_difference(end, table)?;
+            w.rodata().write_label_difference(entry, table)?;
+            w.rodata().define(entry, false);
+            w.rodata().write_all(&[0; 4])?;
+            w.data().write_label_address(end)?;
+            Ok(())
+        });
+
+        // The jmp is widened, which moves `end` to 5 + 30 * 5.
+        assert_eq!(
+            o.relocs,
+            vec![
+                Reloc::new(
+                    Location::new(LocationSection::Data, 0),
+                    RelocTarget::Section(LocationSection::Text),
+                    155,
+                    RelocType::Abs64
+                ),
+                Reloc::new(
+                    Location::new(LocationSection::Rodata, 0),
+                    RelocTarget::Section(LocationSection::Text),
+                    0,
+                    RelocType::PcRel32
+                ),
+                Reloc::new(
+                    Location::new(LocationSection::Rodata, 4),
+                    RelocTarget::Section(LocationSection::Text),
+                    155 + 4,
+                    RelocType::PcRel32
+                ),
+            ]
+        );
+        assert_eq!(o.rodata[8..12], 12i32.to_le_bytes());
+    }
+
+    #[test]
+    fn label_difference_across_sections() {
+        let mut w = Writer::new();
+        let base = w.issue_label();
+        let target = w.issue_label();
+        w.define(base, false);
+        w.define(target, false);
+        w.retq().unwrap();
+        w.rodata().write_label_difference(target, base).unwrap();
+        assert!(w.produce().is_err());
+    }
+
     #[test]
     fn data() {
         let o = write(|w| {
@@ -135,6 +135,42 @@ Relocation section '.rela.text' at offset 0x156 contains 2 entries:
   Offset          Info           Type           Sym. Value    Sym. Name + Addend
 000000000007  000600000002 R_X86_64_PC32     0000000000000000 hello_text - 4
 00000000000d  000700000009 R_X86_64_GOTPCREL 0000000000000000 puts - 4
+"###
+            )
+        );
+    }
+
+    #[test]
+    fn elf_label_data() {
+        let dir = tempdir().unwrap();
+        let mut w = Writer::new();
+        let select = w.get_label("select");
+        let table = w.get_label("table");
+        let addresses = w.get_label("addresses");
+        let case = w.issue_label();
+
+        w.define(select, true);
+        w.retq().unwrap();
+        w.define(case, false);
+        w.retq().unwrap();
+        w.rodata().define(table, false);
+        w.rodata().write_label_difference(case, table).unwrap();
+        w.rodata().write_label_difference(select, table).unwrap();
+        w.data().define(addresses, true);
+        w.data().write_label_address(case).unwrap();
+        let obj = w.produce().unwrap();
+        assert!(write_relocatable_object(&dir.path().join("elf.o"), obj).is_ok());
+
+        assert_eq!(
+            output_lines(&readelf(dir.path(), &["-r", "elf.o"]))
+                .into_iter()
+                .filter(|l| l.starts_with("0000"))
+                .collect::<Vec<_>>(),
+            output_lines(
+                r###"
+000000000000  000200000001 R_X86_64_64       0000000000000000 .text + 1
+000000000000  000200000002 R_X86_64_PC32     0000000000000000 .text + 1
+000000000004  000800000002 R_X86_64_PC32     0000000000000000 select + 4
 "###
             )
         );
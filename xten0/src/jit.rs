@@ -186,7 +186,7 @@ impl<'a, R: SymbolResolver> Linker<'a, R> {
                 // S + A - P
                 let src = self.reloc_target_direct(reloc)?;
                 let value = unsafe { src.wrapping_offset(addend).offset_from(dest) };
-                if value < (i8::MIN as isize) && (i8::MAX as isize) < value {
+                if value < (i8::MIN as isize) || (i8::MAX as isize) < value {
                     Err(Error::OffsetOutOfRange(reloc.ty, value))?;
                 }
                 unsafe { ptr::write(dest as *mut i8, value as i8) };
@@ -195,7 +195,7 @@ impl<'a, R: SymbolResolver> Linker<'a, R> {
                 // S + A - P
                 let src = self.reloc_target_direct(reloc)?;
                 let value = unsafe { src.wrapping_offset(addend).offset_from(dest) };
-                if value < (i32::MIN as isize) && (i32::MAX as isize) < value {
+                if value < (i32::MIN as isize) || (i32::MAX as isize) < value {
                     Err(Error::OffsetOutOfRange(reloc.ty, value))?;
                 }
                 unsafe { ptr::write(dest as *mut i32, value as i32) };
@@ -204,7 +204,7 @@ impl<'a, R: SymbolResolver> Linker<'a, R> {
                 // <AddressTableEntry> + A - P
                 let src = self.reloc_target_address_table_entry(reloc)? as *const u8;
                 let value = unsafe { src.wrapping_offset(addend).offset_from(dest) };
-                if value < (i32::MIN as isize) && (i32::MAX as isize) < value {
+                if value < (i32::MIN as isize) || (i32::MAX as isize) < value {
                     Err(Error::OffsetOutOfRange(reloc.ty, value))?;
                 }
                 unsafe { ptr::write(dest as *mut i32, value as i32) };
@@ -354,6 +354,58 @@ mod tests {
         w.produce()
     }
 
+    // int select(unsigned n) {
+    //   switch (n) { case 0: return 10; case 1: return 20; case 2: return 30; default: return -1; }
+    // }
+    //
+    // select_rel looks up a table of label differences on .rodata, and select_abs looks up a
+    // table of absolute addresses on .data.
+    fn test_object_jump_table() -> io::Result<Object> {
+        let mut w = Writer::new();
+        let select_rel = w.get_label("select_rel");
+        let select_abs = w.get_label("select_abs");
+        let rel_table = w.issue_label();
+        let abs_table = w.issue_label();
+        let cases = [w.issue_label(), w.issue_label(), w.issue_label()];
+        let default = w.issue_label();
+
+        w.define(select_rel, true);
+        w.movl(Edi, Edi)?;
+        w.cmpq(Rdi, 2i8)?;
+        w.ja(default)?;
+        w.leaq(Rcx, rel_table)?;
+        w.movslq(Rax, memory(Rcx + Rdi * 4))?;
+        w.addq(Rax, Rcx)?;
+        w.jmpq(Rax)?;
+
+        w.define(select_abs, true);
+        w.movl(Edi, Edi)?;
+        w.cmpq(Rdi, 2i8)?;
+        w.ja(default)?;
+        w.leaq(Rcx, abs_table)?;
+        w.jmpq(memory(Rcx + Rdi * 8))?;
+
+        for (case, value) in cases.iter().zip([10, 20, 30]) {
+            w.define(*case, false);
+            w.movl(Eax, value)?;
+            w.retq()?;
+        }
+        w.define(default, false);
+        w.movl(Eax, -1)?;
+        w.retq()?;
+
+        w.rodata().define(rel_table, false);
+        for case in cases {
+            w.rodata().write_label_difference(case, rel_table)?;
+        }
+        w.data().define(abs_table, false);
+        for case in cases {
+            w.data().write_label_address(case)?;
+        }
+
+        w.produce()
+    }
+
     // #include <math.h>
     // double foo(double n) { return log10(n); }
     fn test_object_dl() -> io::Result<Object> {
@@ -417,6 +469,23 @@ mod tests {
         assert_eq!(baz(), 456);
     }
 
+    #[test]
+    fn jit_jump_table() {
+        let mut engine = Engine::new(symbol_resolver::none);
+
+        let obj = test_object_jump_table().unwrap();
+        assert_eq!(engine.add_object(&obj), Ok(()));
+
+        for name in ["select_rel", "select_abs"] {
+            let select = engine.get(name).expect(name);
+            let select = unsafe { std::mem::transmute::<_, extern "C" fn(u32) -> i32>(select) };
+            assert_eq!(
+                (0..5).map(|n| select(n)).collect::<Vec<_>>(),
+                vec![10, 20, 30, -1, -1]
+            );
+        }
+    }
+
     #[test]
     fn jit_dl() {
         let mut engine = Engine::new(symbol_resolver::dl::default);